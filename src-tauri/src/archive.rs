@@ -0,0 +1,314 @@
+//! Portable project archives.
+//!
+//! An archive is a zip file holding the project directory under `project/`
+//! and a `manifest.json` listing every file with its size and SHA-256.
+//! Backups, migration logs, caches and in-flight temp files are left out.
+//! Dataset files kept in the blob store are written next to their metadata,
+//! so an archive is self-contained, and go back into the store on import.
+//! Files linked in place are not copied; the archive only records their
+//! paths.
+
+use crate::atomic;
+use crate::blobs;
+use crate::checksum;
+use crate::error::{IoOp, ReciprocalError, Result};
+use crate::ids::ProjectId;
+use crate::jobs::Progress;
+use crate::migrations::{self, CURRENT_SCHEMA_VERSION};
+use crate::store::Project;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Version of the archive layout itself, independent of the project schema.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &str = "manifest.json";
+const PROJECT_PREFIX: &str = "project/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    /// Path relative to the project directory, always `/`-separated.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub project_id: String,
+    pub project_name: String,
+    pub files: Vec<ArchiveEntry>,
+}
+
+fn zip_error(op: IoOp, path: &Path) -> impl FnOnce(ZipError) -> ReciprocalError {
+    let path = path.to_path_buf();
+    move |e| match e {
+        ZipError::Io(source) => ReciprocalError::Io { path, op, source },
+        other => ReciprocalError::Corrupt {
+            path,
+            reason: other.to_string(),
+        },
+    }
+}
+
+/// Files that belong in an archive: everything except local bookkeeping.
+fn is_exported(name: &str) -> bool {
+    !(name.starts_with('.')
+        || name.ends_with(".bak")
+        || name.ends_with(".tmp")
+        || name.ends_with(".cache")
+        || name == "migrations.log")
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, String)>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(ReciprocalError::io(IoOp::ReadDir, dir))?
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(ReciprocalError::io(IoOp::ReadDir, dir))?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !is_exported(&name) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = relative_path(root, &path);
+            files.push((path, relative));
+        }
+    }
+    Ok(())
+}
+
+/// Relative path, `/`-separated, of a file in the project directory.
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Writes the project in `project_dir` to a zip archive at `dest`, taking
+/// dataset files that live in the blob store from `blobs_dir`.
+pub fn export_project(
+    project_dir: &Path,
+    blobs_dir: &Path,
+    dest: &Path,
+    progress: &Progress,
+) -> Result<ArchiveManifest> {
+    let project: Project = atomic::read_json(&project_dir.join("project.json"))?;
+
+    let mut files = Vec::new();
+    collect_files(project_dir, project_dir, &mut files)?;
+
+    let (references, _) = blobs::find_references(&[project_dir])?;
+    for reference in references {
+        let Some(dataset_dir) = reference.metadata.parent() else {
+            continue;
+        };
+        let inline = dataset_dir.join(&reference.name);
+        if !inline.exists() {
+            let blob = blobs::blob_path(blobs_dir, &reference.blob.sha256)?;
+            files.push((blob, relative_path(project_dir, &inline)));
+        }
+    }
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let bytes_total = files
+        .iter()
+        .filter_map(|(path, _)| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    progress.phase("Writing archive", Some(bytes_total), Some(files.len()));
+
+    // Write next to the destination and rename, so a failed export never
+    // leaves a truncated archive behind.
+    let tmp_dest = dest.with_extension("partial");
+    let result = (|| {
+        let out = File::create(&tmp_dest).map_err(ReciprocalError::io(IoOp::Write, &tmp_dest))?;
+        let mut zip = ZipWriter::new(BufWriter::new(out));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(true);
+
+        let mut entries = Vec::with_capacity(files.len());
+        for (path, relative) in &files {
+            progress.check()?;
+            zip.start_file(format!("{}{}", PROJECT_PREFIX, relative), options)
+                .map_err(zip_error(IoOp::Write, &tmp_dest))?;
+            let source = File::open(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+            let (sha256, size) =
+                checksum::copy_hashed(BufReader::new(progress.reader(source)), &mut zip)
+                    .map_err(ReciprocalError::io(IoOp::Write, &tmp_dest))?;
+            entries.push(ArchiveEntry {
+                path: relative.clone(),
+                size,
+                sha256,
+            });
+            progress.file_done();
+        }
+
+        let manifest = ArchiveManifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            schema_version: project.schema_version,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: Utc::now(),
+            project_id: project.id.clone(),
+            project_name: project.name.clone(),
+            files: entries,
+        };
+        let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| {
+            ReciprocalError::internal(format!("Failed to serialize manifest: {}", e))
+        })?;
+        zip.start_file(MANIFEST_NAME, options)
+            .map_err(zip_error(IoOp::Write, &tmp_dest))?;
+        zip.write_all(&manifest_json)
+            .map_err(ReciprocalError::io(IoOp::Write, &tmp_dest))?;
+        zip.finish().map_err(zip_error(IoOp::Write, &tmp_dest))?;
+
+        fs::rename(&tmp_dest, dest).map_err(ReciprocalError::io(IoOp::Write, dest))?;
+        Ok(manifest)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_dest);
+    }
+    result
+}
+
+/// Unpacks the archive at `archive_path` into a new project under
+/// `projects_dir`, verifying every checksum in the manifest.
+///
+/// The archive is extracted into `staging_dir` first and only moved into
+/// the library once it is complete and migrated, with dataset files moved
+/// into the blob store at `blobs_dir`. If a project with the same ID already
+/// exists, the imported project gets a fresh ID.
+pub fn import_project(
+    archive_path: &Path,
+    projects_dir: &Path,
+    blobs_dir: &Path,
+    staging_dir: &Path,
+    progress: &Progress,
+) -> Result<Project> {
+    let file = File::open(archive_path).map_err(ReciprocalError::io(IoOp::Read, archive_path))?;
+    let mut zip =
+        ZipArchive::new(BufReader::new(file)).map_err(zip_error(IoOp::Read, archive_path))?;
+
+    let manifest: ArchiveManifest = {
+        let entry = zip
+            .by_name(MANIFEST_NAME)
+            .map_err(zip_error(IoOp::Read, archive_path))?;
+        serde_json::from_reader(entry).map_err(ReciprocalError::corrupt(archive_path))?
+    };
+    let too_new = if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        Some(manifest.format_version)
+    } else if manifest.schema_version > CURRENT_SCHEMA_VERSION {
+        Some(manifest.schema_version)
+    } else {
+        None
+    };
+    if let Some(version) = too_new {
+        return Err(ReciprocalError::UnsupportedSchema {
+            path: archive_path.to_path_buf(),
+            version,
+        });
+    }
+
+    let stage = staging_dir.join(Uuid::new_v4().to_string());
+    let result = (|| {
+        fs::create_dir_all(&stage).map_err(ReciprocalError::io(IoOp::CreateDir, &stage))?;
+        progress.phase(
+            "Unpacking archive",
+            Some(manifest.files.iter().map(|file| file.size).sum()),
+            Some(manifest.files.len()),
+        );
+
+        for expected in &manifest.files {
+            progress.check()?;
+            let mut entry = zip
+                .by_name(&format!("{}{}", PROJECT_PREFIX, expected.path))
+                .map_err(zip_error(IoOp::Read, archive_path))?;
+            // `enclosed_name` rejects absolute paths and `..` components.
+            let relative = entry
+                .enclosed_name()
+                .and_then(|name| {
+                    name.strip_prefix(PROJECT_PREFIX)
+                        .ok()
+                        .map(Path::to_path_buf)
+                })
+                .ok_or_else(|| ReciprocalError::Corrupt {
+                    path: archive_path.to_path_buf(),
+                    reason: format!("Unsafe path in archive: {}", expected.path),
+                })?;
+
+            let dest = stage.join(relative);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).map_err(ReciprocalError::io(IoOp::CreateDir, parent))?;
+            }
+            let mut out = BufWriter::new(
+                File::create(&dest).map_err(ReciprocalError::io(IoOp::Write, &dest))?,
+            );
+            let (sha256, size) = checksum::copy_hashed(progress.reader(&mut entry), &mut out)
+                .and_then(|hashed| out.flush().map(|_| hashed))
+                .map_err(ReciprocalError::io(IoOp::Write, &dest))?;
+            if sha256 != expected.sha256 || size != expected.size {
+                return Err(ReciprocalError::Corrupt {
+                    path: archive_path.to_path_buf(),
+                    reason: format!("Checksum mismatch for {}", expected.path),
+                });
+            }
+            progress.file_done();
+        }
+
+        migrations::migrate_project_dir(&stage)?;
+
+        let (references, _) = blobs::find_references(&[&stage])?;
+        for reference in references {
+            if let Some(dataset_dir) = reference.metadata.parent() {
+                let inline = dataset_dir.join(&reference.name);
+                if inline.exists() {
+                    blobs::adopt(blobs_dir, &inline, &reference.blob)?;
+                }
+            }
+        }
+
+        let project_file = stage.join("project.json");
+        let mut project: Project = atomic::read_json(&project_file)?;
+        // The ID comes from the archive, so it is only kept if it is a valid
+        // UUID that doesn't collide with an existing project.
+        let project_id = match ProjectId::parse(&project.id) {
+            Ok(id) if !projects_dir.join(&id).exists() => id,
+            _ => {
+                let id = ProjectId::new();
+                project.id = id.to_string();
+                atomic::write_json(&project_file, &project)?;
+                id
+            }
+        };
+
+        let dest = projects_dir.join(&project_id);
+        fs::rename(&stage, &dest).map_err(ReciprocalError::io(IoOp::Write, &dest))?;
+        Ok(project)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&stage);
+    }
+    result
+}
@@ -0,0 +1,103 @@
+//! Crash-safe reads and writes for the JSON documents in the library.
+//!
+//! Writes go to a sibling temp file which is fsynced and then renamed over
+//! the target, so readers only ever see the old or the new contents. The
+//! previous version is kept as `<name>.bak`, and the readers fall back to it
+//! when the primary file does not parse.
+
+use crate::error::{IoOp, ReciprocalError, Result};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, ".bak")
+}
+
+/// Atomically replaces `path` with `contents`, keeping the old file as a backup.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp_path = sibling(path, ".tmp");
+
+    let result = (|| {
+        let mut file =
+            File::create(&tmp_path).map_err(ReciprocalError::io(IoOp::Write, &tmp_path))?;
+        file.write_all(contents)
+            .map_err(ReciprocalError::io(IoOp::Write, &tmp_path))?;
+        file.sync_all()
+            .map_err(ReciprocalError::io(IoOp::Write, &tmp_path))?;
+
+        if path.exists() {
+            let bak_path = backup_path(path);
+            fs::copy(path, &bak_path).map_err(ReciprocalError::io(IoOp::Copy, path))?;
+        }
+
+        fs::rename(&tmp_path, path).map_err(ReciprocalError::io(IoOp::Write, path))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // Persist the rename itself. Directories can't be opened for syncing on
+    // Windows, where the rename is already durable enough.
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let content = serde_json::to_string_pretty(value).map_err(|e| {
+        ReciprocalError::internal(format!("Failed to serialize {}: {}", path.display(), e))
+    })?;
+    write_atomic(path, content.as_bytes())
+}
+
+/// Reads `path` with `parse`, retrying with the backup if the primary is
+/// unreadable or fails to parse. The primary's error is returned if neither
+/// copy is usable.
+fn read_with_backup<T>(
+    path: &Path,
+    parse: impl Fn(&str) -> std::result::Result<T, serde_json::Error>,
+) -> Result<T> {
+    let primary = fs::read_to_string(path)
+        .map_err(ReciprocalError::io(IoOp::Read, path))
+        .and_then(|content| parse(&content).map_err(ReciprocalError::corrupt(path)));
+
+    match primary {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            let bak_path = backup_path(path);
+            fs::read_to_string(&bak_path)
+                .ok()
+                .and_then(|content| parse(&content).ok())
+                .ok_or(err)
+        }
+    }
+}
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    read_with_backup(path, |content| serde_json::from_str(content))
+}
+
+/// Like `read_json`, but returns the raw text of documents the backend stores
+/// opaquely for the frontend.
+pub fn read_json_text(path: &Path) -> Result<String> {
+    read_with_backup(path, |content| {
+        serde_json::from_str::<IgnoredAny>(content).map(|_| content.to_string())
+    })
+}
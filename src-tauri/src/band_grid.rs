@@ -0,0 +1,89 @@
+//! Band energies on a regular grid of k-points, the form Fermi surfaces
+//! are drawn from.
+
+use crate::error::{ReciprocalError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Electronvolts per hartree.
+pub const HARTREE_TO_EV: f64 = 27.211_386_245_988;
+
+/// Energies of each band at every point of an `nx`×`ny`×`nz` grid over the
+/// reciprocal cell, end points included on both sides as in BXSF.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BandGrid {
+    pub nx: usize,
+    pub ny: usize,
+    pub nz: usize,
+    /// eV.
+    pub fermi_energy: f64,
+    /// Grid origin in the units of `reciprocal_vectors`, normally Γ.
+    pub origin: [f64; 3],
+    /// Vectors the grid spans, as rows.
+    pub reciprocal_vectors: [[f64; 3]; 3],
+    /// eV, one array per band, indexed `x + y·nx + z·nx·ny` like the
+    /// frontend's `EnergyGrid`.
+    pub bands: Vec<Vec<f32>>,
+    /// The spin channel of each band, for grids of a spin-polarized run.
+    /// Empty otherwise.
+    pub spins: Vec<SpinChannel>,
+}
+
+/// One channel of a spin-polarized calculation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpinChannel {
+    Up,
+    Down,
+}
+
+impl BandGrid {
+    pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + y * self.nx + z * self.nx * self.ny
+    }
+
+    pub fn points(&self) -> usize {
+        self.nx * self.ny * self.nz
+    }
+
+    /// Bands with grid points both above and below the Fermi energy.
+    pub fn crossing_bands(&self) -> Vec<usize> {
+        let fermi = self.fermi_energy as f32;
+        (0..self.bands.len())
+            .filter(|&b| {
+                let band = &self.bands[b];
+                band.iter().any(|&e| e > fermi) && band.iter().any(|&e| e < fermi)
+            })
+            .collect()
+    }
+
+    /// Checks that every index in `bands` is a band of the grid.
+    pub fn check_bands(&self, bands: &[usize]) -> Result<()> {
+        match bands.iter().find(|&&b| b >= self.bands.len()) {
+            Some(b) => Err(ReciprocalError::internal(format!(
+                "Band {} is out of range; the grid has {} bands",
+                b,
+                self.bands.len()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// File formats a band grid can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridFormat {
+    /// XCrysDen band grid.
+    Bxsf,
+    /// FermiSurfer input.
+    Frmsf,
+}
+
+/// Per-vertex colour data to add to a FermiSurfer file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridColour {
+    /// |∇E|, the Fermi velocity up to a factor of ħ, in eV times the units
+    /// of the reciprocal vectors' inverse.
+    FermiVelocity,
+}
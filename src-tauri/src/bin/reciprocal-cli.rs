@@ -0,0 +1,891 @@
+//! Headless access to the Reciprocal project library.
+//!
+//! Operates on the same library as the desktop app unless `--library`
+//! points somewhere else. Pass `--json` for machine-readable output.
+
+use clap::{Parser, Subcommand};
+use reciprocal_lib::band_grid::{GridColour, GridFormat, SpinChannel};
+use reciprocal_lib::cif::{self, CrystalData};
+use reciprocal_lib::error::{IoOp, ReciprocalError, Result};
+use reciprocal_lib::ids::{DatasetId, ProjectId, TrashId};
+use reciprocal_lib::jobs::Progress;
+use reciprocal_lib::links::{LinkCheck, LinkStatus};
+use reciprocal_lib::listing::Listing;
+use reciprocal_lib::provenance::Parameters;
+use reciprocal_lib::wien2k::{self, Wien2kStruct};
+use reciprocal_lib::{Eigenvalues, FermiSurfaceFiles, ImportOptions, ProjectStore, Spin};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(
+    name = "reciprocal-cli",
+    version,
+    about = "Manage the Reciprocal project library without the GUI"
+)]
+struct Cli {
+    /// Library directory. Defaults to the one the desktop app uses.
+    #[arg(long, global = true, value_name = "DIR")]
+    library: Option<PathBuf>,
+
+    /// Print results as JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List projects, most recently opened first.
+    Projects,
+    /// Create an empty project.
+    Create {
+        name: String,
+        #[arg(long, default_value = "")]
+        formula: String,
+    },
+    /// Create a project from a CIF, Wien2k case.struct or POSCAR file.
+    CreateFrom { file: PathBuf },
+    /// Copy a CIF file into a project.
+    ImportCif {
+        project: String,
+        cif: PathBuf,
+        /// Data block to import; repeat to keep further blocks as separate
+        /// structures. Defaults to the whole file.
+        #[arg(long = "block", value_name = "NAME")]
+        blocks: Vec<String>,
+    },
+    /// Make a POSCAR or CONTCAR file a project's structure.
+    ImportPoscar { project: String, poscar: PathBuf },
+    /// Write a project's structure to a POSCAR file.
+    ExportPoscar { project: String, dest: PathBuf },
+    /// Write a project's structure to a CIF file.
+    ExportCif { project: String, dest: PathBuf },
+    /// List the structure data blocks in a CIF file.
+    CifBlocks { cif: PathBuf },
+    /// List a project's additional structures.
+    Structures { project: String },
+    /// Show the crystal structure in a project's CIF file.
+    Structure { project: String },
+    /// Parse a CIF file and show the crystal structure in it.
+    ParseCif { cif: PathBuf },
+    /// Show the structure in a Wien2k case.struct file.
+    ParseStruct { file: PathBuf },
+    /// Write a Wien2k case.struct for a project's structure.
+    Cif2struct { project: String, dest: PathBuf },
+    /// Import a band structure from a .qtl and a .klist_band file.
+    ImportBands {
+        project: String,
+        qtl: PathBuf,
+        klist: PathBuf,
+        /// The case.qtldn of a spin-polarized run, with its case.qtlup as
+        /// the .qtl file. Both are kept under one band structure.
+        #[arg(long, value_name = "QTLDN")]
+        down: Option<PathBuf>,
+        /// Display name. Defaults to the .qtl file name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Calculation parameter to record with the import, as KEY=VALUE.
+        #[arg(long = "param", value_name = "KEY=VALUE", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+        /// Reference the files where they are instead of copying them.
+        #[arg(long)]
+        link: bool,
+    },
+    /// Import a Fermi surface from a Wien2k case directory. A spin-polarized
+    /// run, one with case.output2up, is read from the up and dn files.
+    ImportFermi {
+        project: String,
+        case_dir: PathBuf,
+        /// Wien2k case name. Defaults to the directory name.
+        #[arg(long)]
+        case: Option<String>,
+        /// Display name. Defaults to the case name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Read eigenvalues from case.<EXTENSION> and case.klist instead of
+        /// case.output1, e.g. `--energy energyso`. Without case.output1,
+        /// energyso or energy is used anyway. k-parallel splits are merged.
+        #[arg(long, value_name = "EXTENSION")]
+        energy: Option<String>,
+        /// Calculation parameter to record with the import, as KEY=VALUE.
+        #[arg(long = "param", value_name = "KEY=VALUE", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+        /// Reference the files where they are instead of copying them.
+        #[arg(long)]
+        link: bool,
+    },
+    /// Import a Fermi surface from an XCrysDen .bxsf band grid.
+    ImportBxsf {
+        project: String,
+        bxsf: PathBuf,
+        /// Display name. Defaults to the file name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Calculation parameter to record with the import, as KEY=VALUE.
+        #[arg(long = "param", value_name = "KEY=VALUE", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+        /// Reference the file where it is instead of copying it.
+        #[arg(long)]
+        link: bool,
+    },
+    /// List a project's band structures.
+    Bands { project: String },
+    /// Show the k-path and the energy range of each band of a band
+    /// structure, parsed as the app loads it.
+    BandStructure { project: String, id: String },
+    /// List a project's Fermi surfaces.
+    FermiSurfaces { project: String },
+    /// List the bands of a Fermi surface that cross the Fermi energy, with
+    /// the energy range of each on the grid.
+    CrossingBands { project: String, id: String },
+    /// Show where a band structure or Fermi surface was imported from.
+    Provenance { project: String, id: String },
+    /// Check the files a linked dataset refers to.
+    Links { project: String, id: String },
+    /// Point a linked dataset at the files of the same names in a directory.
+    Relink {
+        project: String,
+        id: String,
+        dir: PathBuf,
+        /// Relink files even if their contents have changed.
+        #[arg(long)]
+        accept_changes: bool,
+    },
+    /// Copy a band structure's .qtl and .klist_band files, and the .qtldn of
+    /// a spin-polarized one, into a directory.
+    ExportBands {
+        project: String,
+        id: String,
+        dest_dir: PathBuf,
+    },
+    /// Copy the files a Fermi surface was imported from into a directory.
+    ExportFermi {
+        project: String,
+        id: String,
+        dest_dir: PathBuf,
+    },
+    /// Write a Fermi surface's band grid as .bxsf or FermiSurfer .frmsf,
+    /// chosen by the file extension.
+    ExportGrid {
+        project: String,
+        id: String,
+        dest: PathBuf,
+        /// Band to include, counting from 0. Repeat for several; defaults
+        /// to the bands crossing the Fermi energy.
+        #[arg(long = "band", value_name = "N")]
+        bands: Vec<usize>,
+        /// Colour FermiSurfer vertices by Fermi velocity.
+        #[arg(long)]
+        velocity: bool,
+    },
+    /// Write a project to a portable archive.
+    Export { project: String, dest: PathBuf },
+    /// Add a project from an archive.
+    ImportArchive { archive: PathBuf },
+    /// Rebuild unreadable project and dataset metadata.
+    Repair { project: String },
+    /// Re-hash every stored data file and report mismatches.
+    Verify,
+    /// Inspect, restore or empty the trash.
+    #[command(subcommand)]
+    Trash(TrashCommand),
+}
+
+/// A band with grid points both above and below the Fermi energy.
+#[derive(Serialize)]
+struct CrossingBand {
+    /// Counting from 0, as `export-grid --band` takes it.
+    band: usize,
+    spin: Option<SpinChannel>,
+    /// eV.
+    min: f32,
+    max: f32,
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    /// List trashed items, most recent first.
+    List,
+    /// Move a trashed item back into the library.
+    Restore { id: String },
+    /// Permanently delete everything in the trash.
+    Empty,
+}
+
+fn parse_parameter(arg: &str) -> std::result::Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| format!("expected KEY=VALUE, got {:?}", arg))
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| ReciprocalError::internal(format!("Failed to serialize output: {}", e)))?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// Prints `value` as JSON, or as text with `text`.
+fn output<T: Serialize>(json: bool, value: &T, text: impl FnOnce(&T)) -> Result<()> {
+    if json {
+        print_json(value)
+    } else {
+        text(value);
+        Ok(())
+    }
+}
+
+/// Prints a listing's items, with any diagnostics as warnings on stderr.
+fn output_listing<T: Serialize>(json: bool, listing: &Listing<T>, row: impl Fn(&T)) -> Result<()> {
+    for diagnostic in &listing.diagnostics {
+        eprintln!(
+            "warning: {}: {}",
+            diagnostic.path.display(),
+            diagnostic.reason
+        );
+    }
+    output(json, &listing.items, |items| items.iter().for_each(row))
+}
+
+/// Prints link checks, failing if any link is broken.
+fn output_link_checks(json: bool, checks: &[LinkCheck]) -> Result<()> {
+    output(json, &checks, |checks| {
+        for check in checks.iter() {
+            println!(
+                "{:<8} {}  {}",
+                format!("{:?}", check.status).to_lowercase(),
+                check.name,
+                check.path.display()
+            );
+        }
+    })?;
+    let broken = checks.iter().filter(|c| c.status != LinkStatus::Ok).count();
+    if broken > 0 {
+        return Err(ReciprocalError::internal(format!(
+            "{} linked files are missing or changed",
+            broken
+        )));
+    }
+    Ok(())
+}
+
+/// Prints a crystal structure's cell and atom sites.
+fn output_crystal(json: bool, data: &CrystalData) -> Result<()> {
+    output(json, data, |data| {
+        let formula = data.formula_sum.as_deref().unwrap_or("unknown formula");
+        match (&data.space_group_hm, data.space_group_it_number) {
+            (Some(hm), Some(number)) => println!("{}  {} (No. {})", formula, hm, number),
+            (Some(hm), None) => println!("{}  {}", formula, hm),
+            _ => println!("{}", formula),
+        }
+        println!(
+            "a={} b={} c={}  alpha={} beta={} gamma={}",
+            data.cell_length_a.value,
+            data.cell_length_b.value,
+            data.cell_length_c.value,
+            data.cell_angle_alpha.value,
+            data.cell_angle_beta.value,
+            data.cell_angle_gamma.value
+        );
+        for site in &data.atom_sites {
+            println!(
+                "  {:<6} {:<3} {:>9.5} {:>9.5} {:>9.5}  occ {}",
+                site.label,
+                site.type_symbol,
+                site.fract_x,
+                site.fract_y,
+                site.fract_z,
+                site.occupancy
+            );
+        }
+    })
+}
+
+fn output_struct(json: bool, structure: &Wien2kStruct) -> Result<()> {
+    output(json, structure, |s| {
+        println!("{}", s.title);
+        let [a, b, c, alpha, beta, gamma] = s.cell;
+        println!(
+            "{:?} lattice  a={} b={} c={} bohr  alpha={} beta={} gamma={}",
+            s.lattice, a, b, c, alpha, beta, gamma
+        );
+        for atom in &s.atoms {
+            println!(
+                "  {:<10} Z={:<5} MULT={:<2} RMT={}",
+                atom.name,
+                atom.z,
+                atom.positions.len(),
+                atom.rmt
+            );
+        }
+        println!("{} symmetry operations", s.symmetry_operations.len());
+    })
+}
+
+fn write_file(dest: &Path, contents: &str) -> Result<()> {
+    fs::write(dest, contents).map_err(ReciprocalError::io(IoOp::Write, dest))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The Wien2k extension a Fermi surface data file is exported with, with
+/// the `up` and `dn` of a spin-polarized run so it can be imported again.
+fn fermi_extension(name: &str, spin: Spin) -> String {
+    let extension = name.trim_start_matches("data.");
+    let (kind, split) = match extension.split_once('_') {
+        Some((kind, split)) => (kind, Some(split)),
+        None => (extension, None),
+    };
+    if spin == Spin::Unpolarized || !["output1", "output2", "energy"].contains(&kind) {
+        return extension.to_string();
+    }
+    let (channel, split) = match split {
+        Some("dn") => ("dn", None),
+        Some(split) => match split.strip_prefix("dn_") {
+            Some(split) => ("dn", Some(split)),
+            None => ("up", Some(split)),
+        },
+        None => ("up", None),
+    };
+    match split {
+        Some(split) => format!("{}{}_{}", kind, channel, split),
+        None => format!("{}{}", kind, channel),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let root = match cli.library {
+        Some(root) => root,
+        None => ProjectStore::default_root().ok_or_else(|| {
+            ReciprocalError::internal("No data directory on this platform; pass --library")
+        })?,
+    };
+    let store = ProjectStore::new(root);
+    let json = cli.json;
+
+    match cli.command {
+        Command::Projects => output_listing(json, &store.load_projects()?, |p| {
+            println!("{}  {:<24} {}", p.id, p.name, p.formula)
+        }),
+        Command::Create { name, formula } => {
+            let project = store.create_project(name, formula)?;
+            output(json, &project, |p| println!("{}", p.id))
+        }
+        Command::CreateFrom { file } => {
+            let project = store.create_project_from_structure(&file)?;
+            output(json, &project, |p| {
+                println!("{}  {} ({})", p.id, p.name, p.formula)
+            })
+        }
+        Command::ImportCif {
+            project,
+            cif,
+            blocks,
+        } => {
+            let project = store.import_cif_file(
+                &ProjectId::parse(&project)?,
+                &cif,
+                file_name(&cif),
+                &blocks,
+            )?;
+            output(json, &project, |p| {
+                println!("Imported {} into {}", file_name(&cif), p.name)
+            })
+        }
+        Command::ImportPoscar { project, poscar } => {
+            let project =
+                store.import_poscar(&ProjectId::parse(&project)?, &poscar, file_name(&poscar))?;
+            output(json, &project, |p| {
+                println!("Imported {} into {}", file_name(&poscar), p.name)
+            })
+        }
+        Command::ExportPoscar { project, dest } => {
+            store.export_poscar(&ProjectId::parse(&project)?, &dest)?;
+            output(json, &dest, |dest| println!("Wrote {}", dest.display()))
+        }
+        Command::Structure { project } => {
+            output_crystal(json, &store.parse_cif_file(&ProjectId::parse(&project)?)?)
+        }
+        Command::ParseCif { cif } => output_crystal(json, &cif::read_crystal_data(&cif)?),
+        Command::ParseStruct { file } => output_struct(json, &wien2k::read_struct(&file)?),
+        Command::Cif2struct { project, dest } => {
+            store.export_struct(&ProjectId::parse(&project)?, &dest)?;
+            output(json, &dest, |dest| println!("Wrote {}", dest.display()))
+        }
+        Command::ExportCif { project, dest } => {
+            store.export_cif(&ProjectId::parse(&project)?, &dest)?;
+            output(json, &dest, |dest| println!("Wrote {}", dest.display()))
+        }
+        Command::CifBlocks { cif } => output(json, &cif::read_blocks(&cif)?, |blocks| {
+            for block in blocks.iter() {
+                println!(
+                    "{:<20} {:<16} {:<12} a={} b={} c={}",
+                    block.name,
+                    block.formula.as_deref().unwrap_or("?"),
+                    block.space_group_hm.as_deref().unwrap_or("?"),
+                    block.cell_length_a,
+                    block.cell_length_b,
+                    block.cell_length_c
+                );
+            }
+        }),
+        Command::Structures { project } => output_listing(
+            json,
+            &store.list_structures(&ProjectId::parse(&project)?)?,
+            |info| println!("{}  {:<24} {}", info.id, info.name, info.formula),
+        ),
+        Command::ImportBands {
+            project,
+            qtl,
+            klist,
+            down,
+            name,
+            parameters,
+            link,
+        } => {
+            let project_id = ProjectId::parse(&project)?;
+            let name = name.unwrap_or_else(|| file_stem(&qtl));
+            let options = ImportOptions {
+                parameters: parameters.into_iter().collect::<Parameters>(),
+                link,
+            };
+            let info = match down {
+                Some(down) => store.import_spin_band_structure(
+                    &project_id,
+                    name,
+                    &qtl,
+                    file_name(&qtl),
+                    &down,
+                    file_name(&down),
+                    &klist,
+                    file_name(&klist),
+                    options,
+                    &Progress::none(),
+                )?,
+                None => store.import_band_structure(
+                    &project_id,
+                    name,
+                    &qtl,
+                    file_name(&qtl),
+                    &klist,
+                    file_name(&klist),
+                    options,
+                    &Progress::none(),
+                )?,
+            };
+            output(json, &info, |info| println!("{}", info.id))
+        }
+        Command::ImportFermi {
+            project,
+            case_dir,
+            case,
+            name,
+            energy,
+            parameters,
+            link,
+        } => {
+            let case = match case {
+                Some(case) => case,
+                None => file_name(
+                    &case_dir
+                        .canonicalize()
+                        .map_err(ReciprocalError::io(IoOp::Read, &case_dir))?,
+                ),
+            };
+            let file = |extension: &str| case_dir.join(format!("{}.{}", case, extension));
+            let spin = !file("output2").exists() && file("output2up").exists();
+            let channel = |extension: &str, suffix: &str| match spin {
+                true => file(&format!("{}{}", extension, suffix)),
+                false => file(extension),
+            };
+            let (output1, output1_dn, output2, outputkgen, struct_file) = (
+                channel("output1", "up"),
+                file("output1dn"),
+                channel("output2", "up"),
+                file("outputkgen"),
+                file("struct"),
+            );
+            let energy = energy.or_else(|| {
+                let found = |ext: &&str| wien2k::energy_splits(&channel(ext, "up"))[0].exists();
+                (!output1.exists())
+                    .then(|| ["energyso", "energy"].into_iter().find(found))
+                    .flatten()
+                    .map(str::to_string)
+            });
+            let (energy_files, energy_files_dn, klist) = match &energy {
+                Some(ext) => (
+                    wien2k::energy_splits(&channel(ext, "up")),
+                    wien2k::energy_splits(&channel(ext, "dn")),
+                    file("klist"),
+                ),
+                None => (Vec::new(), Vec::new(), PathBuf::new()),
+            };
+            let (eigenvalues, down) = match energy {
+                Some(_) => (
+                    Eigenvalues::Energy {
+                        files: &energy_files,
+                        klist: &klist,
+                    },
+                    Eigenvalues::Energy {
+                        files: &energy_files_dn,
+                        klist: &klist,
+                    },
+                ),
+                None => (
+                    Eigenvalues::Output1(&output1),
+                    Eigenvalues::Output1(&output1_dn),
+                ),
+            };
+            let info = store.import_fermi_surface(
+                &ProjectId::parse(&project)?,
+                name.unwrap_or_else(|| case.clone()),
+                FermiSurfaceFiles {
+                    eigenvalues,
+                    down: spin.then_some(down),
+                    output2: &output2,
+                    outputkgen: &outputkgen,
+                    struct_file: &struct_file,
+                },
+                case,
+                ImportOptions {
+                    parameters: parameters.into_iter().collect::<Parameters>(),
+                    link,
+                },
+                &Progress::none(),
+            )?;
+            output(json, &info, |info| println!("{}", info.id))
+        }
+        Command::Bands { project } => output_listing(
+            json,
+            &store.list_band_structures(&ProjectId::parse(&project)?)?,
+            |info| println!("{}  {:<24} {}", info.id, info.name, info.created_at),
+        ),
+        Command::BandStructure { project, id } => {
+            let data = store.load_band_structure_data(
+                &ProjectId::parse(&project)?,
+                &DatasetId::parse(&id)?,
+                &Progress::none(),
+            )?;
+            output(json, &data, |data| {
+                let labels: Vec<&str> = data
+                    .path
+                    .iter()
+                    .filter_map(|point| point.label.as_deref())
+                    .collect();
+                println!("k-path: {} points ({})", data.path.len(), labels.join(" "));
+                let channels = [("up", Some(&data.up)), ("down", data.down.as_ref())];
+                for (channel, qtl) in channels {
+                    let Some(qtl) = qtl else { continue };
+                    println!(
+                        "{}: {} bands, Fermi energy {:.4} eV",
+                        channel,
+                        qtl.bands.len(),
+                        qtl.fermi_energy
+                    );
+                    for band in &qtl.bands {
+                        let min = band.energies.iter().copied().fold(f32::INFINITY, f32::min);
+                        let max = band
+                            .energies
+                            .iter()
+                            .copied()
+                            .fold(f32::NEG_INFINITY, f32::max);
+                        println!("  band {:<4} {:>9.4} .. {:.4} eV", band.index, min, max);
+                    }
+                }
+            })
+        }
+        Command::ImportBxsf {
+            project,
+            bxsf,
+            name,
+            parameters,
+            link,
+        } => {
+            let info = store.import_bxsf_fermi_surface(
+                &ProjectId::parse(&project)?,
+                name.unwrap_or_else(|| file_name(&bxsf)),
+                &bxsf,
+                ImportOptions {
+                    parameters: parameters.into_iter().collect::<Parameters>(),
+                    link,
+                },
+                &Progress::none(),
+            )?;
+            output(json, &info, |info| println!("{}", info.id))
+        }
+        Command::FermiSurfaces { project } => output_listing(
+            json,
+            &store.list_fermi_surfaces(&ProjectId::parse(&project)?)?,
+            |info| println!("{}  {:<24} {}", info.id, info.name, info.created_at),
+        ),
+        Command::CrossingBands { project, id } => {
+            let grid = store.load_fermi_surface_grid(
+                &ProjectId::parse(&project)?,
+                &DatasetId::parse(&id)?,
+                &Progress::none(),
+            )?;
+            let crossing: Vec<CrossingBand> = grid
+                .crossing_bands()
+                .into_iter()
+                .map(|band| {
+                    let energies = grid.bands[band].iter().copied();
+                    CrossingBand {
+                        band,
+                        spin: grid.spins.get(band).copied(),
+                        min: energies.clone().fold(f32::INFINITY, f32::min),
+                        max: energies.fold(f32::NEG_INFINITY, f32::max),
+                    }
+                })
+                .collect();
+            output(json, &crossing, |crossing| {
+                println!("Fermi energy {:.4} eV", grid.fermi_energy);
+                for band in crossing {
+                    let spin = match band.spin {
+                        Some(SpinChannel::Up) => " up",
+                        Some(SpinChannel::Down) => " down",
+                        None => "",
+                    };
+                    println!(
+                        "  band {:<4}{:<5} {:>9.4} .. {:.4} eV",
+                        band.band, spin, band.min, band.max
+                    );
+                }
+            })
+        }
+        Command::Provenance { project, id } => {
+            let provenance =
+                store.dataset_provenance(&ProjectId::parse(&project)?, &DatasetId::parse(&id)?)?;
+            output(json, &provenance, |provenance| match provenance {
+                None => println!("No provenance recorded for {}", id),
+                Some(p) => {
+                    println!(
+                        "Imported {} on {} with Reciprocal {}",
+                        p.imported_at,
+                        p.host.as_deref().unwrap_or("unknown host"),
+                        p.app_version
+                    );
+                    for source in &p.sources {
+                        println!(
+                            "  {}  {}  {} bytes  sha256 {}",
+                            source.name,
+                            source.path.display(),
+                            source.size,
+                            source.sha256
+                        );
+                    }
+                    for (key, value) in &p.parameters {
+                        println!("  {} = {}", key, value);
+                    }
+                }
+            })
+        }
+        Command::Links { project, id } => {
+            let checks =
+                store.check_dataset_links(&ProjectId::parse(&project)?, &DatasetId::parse(&id)?)?;
+            output_link_checks(json, &checks)
+        }
+        Command::Relink {
+            project,
+            id,
+            dir,
+            accept_changes,
+        } => {
+            let checks = store.relink_dataset(
+                &ProjectId::parse(&project)?,
+                &DatasetId::parse(&id)?,
+                &dir,
+                accept_changes,
+            )?;
+            output_link_checks(json, &checks)
+        }
+        Command::ExportBands {
+            project,
+            id,
+            dest_dir,
+        } => {
+            let project_id = ProjectId::parse(&project)?;
+            let id = DatasetId::parse(&id)?;
+            let info = store.band_structure_info(&project_id, &id)?;
+            fs::create_dir_all(&dest_dir)
+                .map_err(ReciprocalError::io(IoOp::CreateDir, &dest_dir))?;
+            let qtl_path = dest_dir.join(&info.qtl_filename);
+            let klist_path = dest_dir.join(&info.klist_filename);
+            let written = match (info.spin, &info.qtl_dn_filename) {
+                (Spin::Polarized, Some(qtl_dn_filename)) => {
+                    let (up, down, klist) = store.load_spin_band_structure_files(
+                        &project_id,
+                        &id,
+                        &Progress::none(),
+                    )?;
+                    vec![
+                        (qtl_path, up),
+                        (dest_dir.join(qtl_dn_filename), down),
+                        (klist_path, klist),
+                    ]
+                }
+                _ => {
+                    let (qtl, klist) =
+                        store.load_band_structure_files(&project_id, &id, &Progress::none())?;
+                    vec![(qtl_path, qtl), (klist_path, klist)]
+                }
+            };
+            for (path, contents) in &written {
+                write_file(path, contents)?;
+            }
+            let written: Vec<PathBuf> = written.into_iter().map(|(path, _)| path).collect();
+            output(json, &written, |paths| {
+                paths.iter().for_each(|p| println!("{}", p.display()))
+            })
+        }
+        Command::ExportFermi {
+            project,
+            id,
+            dest_dir,
+        } => {
+            let project_id = ProjectId::parse(&project)?;
+            let id = DatasetId::parse(&id)?;
+            let info = store.fermi_surface_info(&project_id, &id)?;
+            fs::create_dir_all(&dest_dir)
+                .map_err(ReciprocalError::io(IoOp::CreateDir, &dest_dir))?;
+            let mut written = Vec::new();
+            for (name, contents) in
+                store.load_fermi_surface_data(&project_id, &id, &Progress::none())?
+            {
+                let extension = fermi_extension(&name, info.spin);
+                let dest = dest_dir.join(format!("{}.{}", info.case_name, extension));
+                write_file(&dest, &contents)?;
+                written.push(dest);
+            }
+            output(json, &written, |paths| {
+                paths.iter().for_each(|p| println!("{}", p.display()))
+            })
+        }
+        Command::ExportGrid {
+            project,
+            id,
+            dest,
+            bands,
+            velocity,
+        } => {
+            let extension = dest
+                .extension()
+                .map(|e| e.to_string_lossy().to_ascii_lowercase());
+            let format = match extension.as_deref() {
+                Some("bxsf") => GridFormat::Bxsf,
+                Some("frmsf") => GridFormat::Frmsf,
+                _ => {
+                    return Err(ReciprocalError::internal(format!(
+                        "{} should end in .bxsf or .frmsf",
+                        dest.display()
+                    )))
+                }
+            };
+            store.export_fermi_surface_grid(
+                &ProjectId::parse(&project)?,
+                &DatasetId::parse(&id)?,
+                &dest,
+                format,
+                (!bands.is_empty()).then_some(bands),
+                velocity.then_some(GridColour::FermiVelocity),
+                &Progress::none(),
+            )?;
+            output(json, &dest, |dest| println!("{}", dest.display()))
+        }
+        Command::Export { project, dest } => {
+            let manifest = store.export_project_archive(
+                &ProjectId::parse(&project)?,
+                &dest,
+                &Progress::none(),
+            )?;
+            output(json, &manifest, |m| {
+                println!(
+                    "Exported {} ({} files) to {}",
+                    m.project_name,
+                    m.files.len(),
+                    dest.display()
+                )
+            })
+        }
+        Command::ImportArchive { archive } => {
+            let project = store.import_project_archive(&archive, &Progress::none())?;
+            output(json, &project, |p| println!("{}", p.id))
+        }
+        Command::Repair { project } => {
+            let report = store.repair_project(&ProjectId::parse(&project)?)?;
+            output(json, &report, |report| {
+                for d in &report.repaired {
+                    println!("repaired: {}: {}", d.path.display(), d.reason);
+                }
+                for d in &report.unrecoverable {
+                    println!("unrecoverable: {}: {}", d.path.display(), d.reason);
+                }
+            })
+        }
+        Command::Verify => {
+            let report = store.verify_library(&Progress::none())?;
+            let problems = report.corrupt.len() + report.missing.len() + report.unreadable.len();
+            output(json, &report, |report| {
+                for (label, diagnostics) in [
+                    ("corrupt", &report.corrupt),
+                    ("missing", &report.missing),
+                    ("unreadable", &report.unreadable),
+                ] {
+                    for d in diagnostics {
+                        println!("{}: {}: {}", label, d.path.display(), d.reason);
+                    }
+                }
+                println!("Checked {} files, {} problems", report.checked, problems);
+            })?;
+            if problems > 0 {
+                return Err(ReciprocalError::internal(format!(
+                    "Library verification found {} problems",
+                    problems
+                )));
+            }
+            Ok(())
+        }
+        Command::Trash(TrashCommand::List) => output_listing(json, &store.list_trash()?, |e| {
+            println!(
+                "{}  {:<14} {:<24} {}",
+                e.id,
+                e.kind.to_string(),
+                e.name,
+                e.deleted_at
+            )
+        }),
+        Command::Trash(TrashCommand::Restore { id }) => {
+            let entry = store.restore_from_trash(&TrashId::parse(&id)?)?;
+            output(json, &entry, |e| println!("Restored {} {}", e.kind, e.name))
+        }
+        Command::Trash(TrashCommand::Empty) => {
+            let removed = store.empty_trash()?;
+            output(json, &removed, |n| println!("Removed {} items", n))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
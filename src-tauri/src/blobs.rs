@@ -0,0 +1,294 @@
+//! Content-addressed storage for imported data files.
+//!
+//! Each distinct file is stored once, at `blobs/<first two hex digits>/<sha256>`,
+//! however many datasets import it. Dataset metadata refers to its files by
+//! hash through a `files` map, and a blob is only removed once nothing in the
+//! library or the trash refers to it any more.
+
+use crate::atomic;
+use crate::checksum;
+use crate::error::{IoOp, ReciprocalError, Result};
+use crate::jobs::Progress;
+use crate::listing::Diagnostic;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+pub const BLOBS_DIR: &str = "blobs";
+
+/// Blobs younger than this are never collected, so a file whose dataset is
+/// still being imported isn't removed from under it.
+const GARBAGE_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobRef {
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Stored file name (e.g. `data.qtl`) to blob, as kept in dataset metadata.
+pub type BlobFiles = BTreeMap<String, BlobRef>;
+
+/// A blob reference found in dataset metadata.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// The info.json holding the reference.
+    pub metadata: PathBuf,
+    pub name: String,
+    pub blob: BlobRef,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    /// Number of blobs re-hashed.
+    pub checked: usize,
+    /// Blobs whose contents no longer match their hash.
+    pub corrupt: Vec<Diagnostic>,
+    /// References to blobs that aren't in the store.
+    pub missing: Vec<Diagnostic>,
+    /// Dataset metadata that couldn't be read, so its references are unknown.
+    pub unreadable: Vec<Diagnostic>,
+}
+
+fn is_sha256(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Where the blob with `sha256` is stored. Hashes come from metadata on
+/// disk, so anything that isn't a lowercase hex SHA-256 is rejected before
+/// it is joined onto a path.
+pub fn blob_path(blobs_dir: &Path, sha256: &str) -> Result<PathBuf> {
+    if !is_sha256(sha256) {
+        return Err(ReciprocalError::Corrupt {
+            path: blobs_dir.to_path_buf(),
+            reason: format!("Invalid blob hash {:?}", sha256),
+        });
+    }
+    Ok(blobs_dir.join(&sha256[..2]).join(sha256))
+}
+
+/// Moves a fully written `tmp` file to `dest`, unless the store already
+/// holds the same content.
+fn commit(tmp: &Path, dest: &Path) -> Result<()> {
+    if dest.exists() {
+        fs::remove_file(tmp).map_err(ReciprocalError::io(IoOp::Delete, tmp))?;
+        // Freshen the existing blob so it gets the grace period too.
+        if let Ok(file) = File::options().write(true).open(dest) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        return Ok(());
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(ReciprocalError::io(IoOp::CreateDir, parent))?;
+    }
+    fs::rename(tmp, dest).map_err(ReciprocalError::io(IoOp::Write, dest))
+}
+
+/// Copies `source` into the store and returns its reference.
+pub fn put(blobs_dir: &Path, source: &Path, progress: &Progress) -> Result<BlobRef> {
+    fs::create_dir_all(blobs_dir).map_err(ReciprocalError::io(IoOp::CreateDir, blobs_dir))?;
+    let tmp = blobs_dir.join(format!("{}.tmp", Uuid::new_v4()));
+
+    let result = (|| {
+        let input = File::open(source).map_err(ReciprocalError::io(IoOp::Read, source))?;
+        let mut out =
+            BufWriter::new(File::create(&tmp).map_err(ReciprocalError::io(IoOp::Write, &tmp))?);
+        let (sha256, size) =
+            checksum::copy_hashed(BufReader::new(progress.reader(input)), &mut out)
+                .and_then(|hashed| out.flush().map(|_| hashed))
+                .map_err(ReciprocalError::io(IoOp::Copy, source))?;
+        out.get_ref()
+            .sync_all()
+            .map_err(ReciprocalError::io(IoOp::Write, &tmp))?;
+        drop(out);
+
+        commit(&tmp, &blob_path(blobs_dir, &sha256)?)?;
+        Ok(BlobRef { sha256, size })
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Moves the file at `path` into the store, checking that it matches
+/// `expected`. Used for files unpacked next to their metadata, such as
+/// datasets restored from an archive.
+pub fn adopt(blobs_dir: &Path, path: &Path, expected: &BlobRef) -> Result<()> {
+    let (sha256, size) = checksum::sha256_file(path)?;
+    if sha256 != expected.sha256 || size != expected.size {
+        return Err(ReciprocalError::Corrupt {
+            path: path.to_path_buf(),
+            reason: "Contents don't match the hash in the dataset metadata".to_string(),
+        });
+    }
+    commit(path, &blob_path(blobs_dir, &sha256)?)
+}
+
+#[derive(Deserialize)]
+struct DatasetFiles {
+    #[serde(default)]
+    files: BlobFiles,
+}
+
+fn find_references_in(
+    dir: &Path,
+    references: &mut Vec<Reference>,
+    unreadable: &mut Vec<Diagnostic>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(ReciprocalError::io(IoOp::ReadDir, dir))? {
+        let entry = entry.map_err(ReciprocalError::io(IoOp::ReadDir, dir))?;
+        let path = entry.path();
+        // Skips `.backups`, whose copies don't keep anything alive.
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            find_references_in(&path, references, unreadable)?;
+        } else if entry.file_name() == "info.json" {
+            match atomic::read_json::<DatasetFiles>(&path) {
+                Ok(dataset) => {
+                    references.extend(dataset.files.into_iter().map(|(name, blob)| Reference {
+                        metadata: path.clone(),
+                        name,
+                        blob,
+                    }))
+                }
+                Err(e) => unreadable.push(Diagnostic::new(&path, e.to_string())),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Every blob reference in dataset metadata below `dirs`, and a diagnostic
+/// for each info.json that couldn't be read.
+pub fn find_references(dirs: &[&Path]) -> Result<(Vec<Reference>, Vec<Diagnostic>)> {
+    let mut references = Vec::new();
+    let mut unreadable = Vec::new();
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        find_references_in(dir, &mut references, &mut unreadable)?;
+    }
+    Ok((references, unreadable))
+}
+
+/// Every file in the store, as (hash it is filed under, path).
+fn stored_blobs(blobs_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut blobs = Vec::new();
+    if !blobs_dir.exists() {
+        return Ok(blobs);
+    }
+    for shard in fs::read_dir(blobs_dir).map_err(ReciprocalError::io(IoOp::ReadDir, blobs_dir))? {
+        let shard = shard
+            .map_err(ReciprocalError::io(IoOp::ReadDir, blobs_dir))?
+            .path();
+        if !shard.is_dir() {
+            continue;
+        }
+        for blob in fs::read_dir(&shard).map_err(ReciprocalError::io(IoOp::ReadDir, &shard))? {
+            let blob = blob.map_err(ReciprocalError::io(IoOp::ReadDir, &shard))?;
+            blobs.push((blob.file_name().to_string_lossy().into_owned(), blob.path()));
+        }
+    }
+    Ok(blobs)
+}
+
+/// Re-hashes every blob and checks that every reference resolves.
+pub fn verify(
+    blobs_dir: &Path,
+    references: &[Reference],
+    unreadable: Vec<Diagnostic>,
+    progress: &Progress,
+) -> Result<VerifyReport> {
+    let mut report = VerifyReport {
+        checked: 0,
+        corrupt: Vec::new(),
+        missing: Vec::new(),
+        unreadable,
+    };
+
+    let stored = stored_blobs(blobs_dir)?;
+    let bytes_total = stored
+        .iter()
+        .filter_map(|(_, path)| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    progress.phase("Verifying files", Some(bytes_total), Some(stored.len()));
+
+    for (name, path) in stored {
+        progress.check()?;
+        report.checked += 1;
+        let hashed = checksum::sha256_file_with_progress(&path, progress);
+        progress.file_done();
+        match hashed {
+            Ok((sha256, _)) if sha256 == name => {}
+            Ok((sha256, _)) => report.corrupt.push(Diagnostic::new(
+                &path,
+                format!("Contents hash to {}", sha256),
+            )),
+            Err(e) => report.corrupt.push(Diagnostic::new(&path, e.to_string())),
+        }
+    }
+
+    for reference in references {
+        let reason = match blob_path(blobs_dir, &reference.blob.sha256) {
+            Ok(path) if path.exists() => continue,
+            Ok(_) => format!(
+                "Missing blob {} for {}",
+                reference.blob.sha256, reference.name
+            ),
+            Err(e) => format!("{} for {}", e, reference.name),
+        };
+        report
+            .missing
+            .push(Diagnostic::new(&reference.metadata, reason));
+    }
+
+    Ok(report)
+}
+
+fn older_than_grace_period(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > GARBAGE_GRACE_PERIOD)
+}
+
+/// Removes blobs that no reference points to, and temp files left by
+/// interrupted imports. Returns how many files were removed.
+pub fn collect_garbage(blobs_dir: &Path, references: &[Reference]) -> Result<usize> {
+    let live: HashSet<&str> = references
+        .iter()
+        .map(|reference| reference.blob.sha256.as_str())
+        .collect();
+
+    let mut removed = 0;
+    for (name, path) in stored_blobs(blobs_dir)? {
+        if !live.contains(name.as_str()) && older_than_grace_period(&path) {
+            fs::remove_file(&path).map_err(ReciprocalError::io(IoOp::Delete, &path))?;
+            removed += 1;
+        }
+    }
+
+    if blobs_dir.exists() {
+        for entry in
+            fs::read_dir(blobs_dir).map_err(ReciprocalError::io(IoOp::ReadDir, blobs_dir))?
+        {
+            let path = entry
+                .map_err(ReciprocalError::io(IoOp::ReadDir, blobs_dir))?
+                .path();
+            if path.is_file() && older_than_grace_period(&path) {
+                fs::remove_file(&path).map_err(ReciprocalError::io(IoOp::Delete, &path))?;
+                removed += 1;
+            }
+        }
+    }
+
+    Ok(removed)
+}
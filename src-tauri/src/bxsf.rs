@@ -0,0 +1,213 @@
+//! XCrysDen band grid files (`.bxsf`), the format Quantum ESPRESSO, VASP
+//! post-processing tools and FermiSurfer exchange Fermi surfaces in.
+//!
+//! Energies are read as hartree. Values run with z fastest, the opposite
+//! of `BandGrid`, and are reordered on reading.
+
+use crate::band_grid::{BandGrid, HARTREE_TO_EV};
+use crate::error::{IoOp, ReciprocalError, Result};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+struct Tokens<'a> {
+    /// (line number, token) pairs, comments removed.
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+    file: &'a Path,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str, file: &'a Path) -> Self {
+        let tokens = text
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let line = line.split('#').next().unwrap_or("");
+                line.split_whitespace().map(move |t| (i + 1, t))
+            })
+            .collect();
+        Tokens {
+            tokens,
+            next: 0,
+            file,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ReciprocalError {
+        let line = self
+            .tokens
+            .get(self.next.saturating_sub(1))
+            .map(|&(line, _)| line);
+        ReciprocalError::Parse {
+            file: self.file.to_path_buf(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    fn next(&mut self, what: &str) -> Result<&'a str> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .map(|&(_, t)| t)
+            .ok_or_else(|| self.error(format!("File ends before the {}", what)))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T> {
+        let token = self.next(what)?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("Expected {}, found {}", what, token)))
+    }
+
+    fn vector(&mut self, what: &str) -> Result<[f64; 3]> {
+        Ok([self.number(what)?, self.number(what)?, self.number(what)?])
+    }
+
+    /// Moves past the next token starting with `prefix`, ignoring case.
+    fn skip_past(&mut self, prefix: &str) -> Result<&'a str> {
+        while let Some(&(_, token)) = self.tokens.get(self.next) {
+            self.next += 1;
+            if token.to_ascii_uppercase().starts_with(prefix) {
+                return Ok(token);
+            }
+        }
+        Err(self.error(format!("No {} in the file", prefix)))
+    }
+}
+
+/// Reads the Fermi energy from `Fermi Energy: 0.5432` in the info block.
+fn fermi_energy(text: &str) -> Option<f64> {
+    text.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case("fermi energy")
+            .then(|| value.split_whitespace().next()?.parse().ok())
+            .flatten()
+    })
+}
+
+pub fn parse(text: &str, file: &Path) -> Result<BandGrid> {
+    let fermi_energy = fermi_energy(text).ok_or_else(|| ReciprocalError::Parse {
+        file: file.to_path_buf(),
+        line: None,
+        message: "No Fermi Energy in the info block".to_string(),
+    })?;
+
+    let mut tokens = Tokens::new(text, file);
+    tokens.skip_past("BEGIN_BLOCK_BANDGRID_3D")?;
+    tokens.skip_past("BEGIN_BANDGRID_3D")?;
+    let band_count: usize = tokens.number("the number of bands")?;
+    let nx: usize = tokens.number("the grid size")?;
+    let ny: usize = tokens.number("the grid size")?;
+    let nz: usize = tokens.number("the grid size")?;
+    if nx < 2 || ny < 2 || nz < 2 {
+        return Err(tokens.error("The grid needs at least two points along each axis"));
+    }
+    let origin = tokens.vector("the grid origin")?;
+    let reciprocal_vectors = [
+        tokens.vector("a spanning vector")?,
+        tokens.vector("a spanning vector")?,
+        tokens.vector("a spanning vector")?,
+    ];
+
+    let points = nx * ny * nz;
+    let mut bands = Vec::with_capacity(band_count);
+    for _ in 0..band_count {
+        if !tokens.next("BAND: line")?.eq_ignore_ascii_case("BAND:") {
+            return Err(tokens.error("Expected BAND:"));
+        }
+        let _number: usize = tokens.number("the band number")?;
+        let mut energies = vec![0f32; points];
+        for x in 0..nx {
+            for y in 0..ny {
+                for z in 0..nz {
+                    let hartree: f64 = tokens.number("a band energy")?;
+                    energies[x + y * nx + z * nx * ny] = (hartree * HARTREE_TO_EV) as f32;
+                }
+            }
+        }
+        bands.push(energies);
+    }
+    if !tokens
+        .next("END_BANDGRID_3D")?
+        .to_ascii_uppercase()
+        .starts_with("END_BANDGRID_3D")
+    {
+        return Err(tokens.error(format!(
+            "Expected END_BANDGRID_3D after {} bands of {} values",
+            band_count, points
+        )));
+    }
+
+    Ok(BandGrid {
+        nx,
+        ny,
+        nz,
+        fermi_energy: fermi_energy * HARTREE_TO_EV,
+        origin,
+        reciprocal_vectors,
+        bands,
+        spins: Vec::new(),
+    })
+}
+
+pub fn read_bxsf(path: &Path) -> Result<BandGrid> {
+    let text = fs::read_to_string(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+    parse(&text, path)
+}
+
+/// The chosen bands of `grid` as a BXSF file, in hartree. `name` titles
+/// the grid block.
+pub fn to_text(grid: &BandGrid, bands: &[usize], name: &str) -> String {
+    let mut out = String::new();
+    let vector = |v: &[f64; 3]| format!("{:16.10}{:16.10}{:16.10}\n", v[0], v[1], v[2]);
+    out.push_str("BEGIN_INFO\n");
+    out.push_str("  # Band grid exported by Reciprocal\n");
+    let _ = writeln!(
+        out,
+        "  Fermi Energy: {:.10}",
+        grid.fermi_energy / HARTREE_TO_EV
+    );
+    out.push_str("END_INFO\n");
+    out.push_str("BEGIN_BLOCK_BANDGRID_3D\n");
+    let _ = writeln!(
+        out,
+        "{}",
+        name.split_whitespace().collect::<Vec<_>>().join("_")
+    );
+    out.push_str("BEGIN_BANDGRID_3D_fermi\n");
+    let _ = writeln!(out, "{}", bands.len());
+    let _ = writeln!(out, "{} {} {}", grid.nx, grid.ny, grid.nz);
+    out.push_str(&vector(&grid.origin));
+    for v in &grid.reciprocal_vectors {
+        out.push_str(&vector(v));
+    }
+    for &b in bands {
+        let _ = writeln!(out, "BAND: {}", b + 1);
+        let band = &grid.bands[b];
+        let mut column = 0;
+        for x in 0..grid.nx {
+            for y in 0..grid.ny {
+                for z in 0..grid.nz {
+                    let hartree = band[grid.index(x, y, z)] as f64 / HARTREE_TO_EV;
+                    let _ = write!(out, "{:14.7}", hartree);
+                    column += 1;
+                    if column == 6 {
+                        out.push('\n');
+                        column = 0;
+                    }
+                }
+            }
+        }
+        if column != 0 {
+            out.push('\n');
+        }
+    }
+    out.push_str("END_BANDGRID_3D\n");
+    out.push_str("END_BLOCK_BANDGRID_3D\n");
+    out
+}
@@ -0,0 +1,37 @@
+use crate::error::{IoOp, ReciprocalError, Result};
+use crate::jobs::Progress;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Copies `reader` into `writer`, returning the hex SHA-256 of the bytes
+/// copied and their count.
+pub fn copy_hashed(mut reader: impl Read, mut writer: impl Write) -> io::Result<(String, u64)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut total = 0u64;
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        writer.write_all(&buffer[..n])?;
+        total += n as u64;
+    }
+
+    Ok((hex::encode(hasher.finalize()), total))
+}
+
+/// Hex SHA-256 and size of the file at `path`.
+pub fn sha256_file(path: &Path) -> Result<(String, u64)> {
+    sha256_file_with_progress(path, &Progress::none())
+}
+
+/// Like `sha256_file`, counting the bytes read towards `progress`.
+pub fn sha256_file_with_progress(path: &Path, progress: &Progress) -> Result<(String, u64)> {
+    let file = File::open(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+    copy_hashed(progress.reader(file), io::sink()).map_err(ReciprocalError::io(IoOp::Read, path))
+}
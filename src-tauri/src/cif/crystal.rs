@@ -0,0 +1,485 @@
+//! The crystal structure described by a CIF data block, with the same
+//! fields as `CrystalData` in the frontend.
+
+use super::syntax::{DataBlock, Loop, Value};
+use crate::lattice::Cell;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::f64::consts::PI;
+
+/// Numbers the frontend couldn't parse are NaN, which JSON stores as null.
+fn nan_if_null<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatticeParameter {
+    #[serde(deserialize_with = "nan_if_null")]
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uncertainty: Option<f64>,
+}
+
+impl LatticeParameter {
+    pub fn exact(value: f64) -> Self {
+        LatticeParameter {
+            value,
+            uncertainty: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtomSite {
+    pub label: String,
+    pub type_symbol: String,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub fract_x: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub fract_y: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub fract_z: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wyckoff_symbol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symmetry_multiplicity: Option<u32>,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub occupancy: f64,
+}
+
+/// Anisotropic displacement as dimensionless β_ij, whichever form the
+/// file gave them in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnisotropicParams {
+    pub label: String,
+    pub type_symbol: String,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub beta_11: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub beta_22: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub beta_33: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub beta_12: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub beta_13: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub beta_23: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_first: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_last: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrystalData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chemical_name_common: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formula_structural: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formula_sum: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structure_type: Option<String>,
+
+    pub cell_length_a: LatticeParameter,
+    pub cell_length_b: LatticeParameter,
+    pub cell_length_c: LatticeParameter,
+    pub cell_angle_alpha: LatticeParameter,
+    pub cell_angle_beta: LatticeParameter,
+    pub cell_angle_gamma: LatticeParameter,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell_volume: Option<f64>,
+    #[serde(
+        rename = "cell_formula_units_Z",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cell_formula_units_z: Option<u32>,
+
+    #[serde(
+        rename = "space_group_HM",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub space_group_hm: Option<String>,
+    #[serde(
+        rename = "space_group_IT_number",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub space_group_it_number: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub density: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurement_temperature: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_creation_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citation: Option<Citation>,
+
+    #[serde(default)]
+    pub atom_sites: Vec<AtomSite>,
+    #[serde(default)]
+    pub symmetry_operations: Vec<String>,
+    #[serde(default)]
+    pub anisotropic_params: Vec<AnisotropicParams>,
+}
+
+/// Parses a CIF number with an optional standard uncertainty in the last
+/// digits, e.g. `5.194(1)` is 5.194 ± 0.001 and `1.2e-3(4)` is
+/// 0.0012 ± 0.0004.
+pub fn parse_number(text: &str) -> Option<LatticeParameter> {
+    let text = text.trim();
+    let (number, uncertainty) = match text.split_once('(') {
+        Some((number, rest)) => (number, Some(rest.strip_suffix(')')?)),
+        None => (text, None),
+    };
+    let value: f64 = number.parse().ok()?;
+    if !value.is_finite() {
+        return None;
+    }
+    let Some(digits) = uncertainty else {
+        return Some(LatticeParameter::exact(value));
+    };
+    let digits: u64 = digits.parse().ok()?;
+
+    let (mantissa, exponent) = match number.find(['e', 'E']) {
+        Some(i) => (&number[..i], number[i + 1..].parse::<i32>().ok()?),
+        None => (number, 0),
+    };
+    let decimals = mantissa
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len() as i32);
+    Some(LatticeParameter {
+        value,
+        uncertainty: Some(digits as f64 * 10f64.powi(exponent - decimals)),
+    })
+}
+
+fn number(value: Option<&Value>) -> Option<LatticeParameter> {
+    value.and_then(Value::as_str).and_then(parse_number)
+}
+
+fn integer(value: Option<&Value>) -> Option<u32> {
+    value
+        .and_then(Value::as_str)
+        .and_then(|text| text.trim().parse().ok())
+}
+
+/// Values of a single-line item; text fields may wrap long titles and
+/// names, so whitespace is collapsed.
+fn string(value: Option<&Value>) -> Option<String> {
+    let text = value.and_then(Value::as_str)?;
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+fn first_text(block: &DataBlock, tags: &[&str]) -> Option<String> {
+    tags.iter().find_map(|tag| string(block.first(tag)))
+}
+
+fn first_number(block: &DataBlock, tags: &[&str]) -> Option<LatticeParameter> {
+    tags.iter().find_map(|tag| number(block.first(tag)))
+}
+
+fn first_integer(block: &DataBlock, tags: &[&str]) -> Option<u32> {
+    tags.iter().find_map(|tag| integer(block.first(tag)))
+}
+
+/// One row of a loop, looked up by tag.
+struct Row<'a> {
+    l: &'a Loop,
+    values: &'a [Value],
+}
+
+impl Row<'_> {
+    fn get(&self, tag: &str) -> Option<&Value> {
+        self.l.column(tag).and_then(|i| self.values.get(i))
+    }
+
+    fn number(&self, tag: &str) -> Option<f64> {
+        number(self.get(tag)).map(|n| n.value)
+    }
+}
+
+fn rows<'a>(block: &'a DataBlock, tag: &str) -> impl Iterator<Item = Row<'a>> {
+    block
+        .find_loop(tag)
+        .into_iter()
+        .flat_map(|l| l.rows.iter().map(move |values| Row { l, values }))
+}
+
+/// The element of an atom site whose type symbol is missing, from the
+/// leading letters of its label (`Fe1` is Fe).
+fn element_from_label(label: &str) -> String {
+    label
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .take(2)
+        .collect()
+}
+
+fn atom_sites(block: &DataBlock) -> Vec<AtomSite> {
+    rows(block, "_atom_site_label")
+        .filter_map(|row| {
+            let label = row.get("_atom_site_label")?.as_str()?.to_string();
+            let type_symbol = string(row.get("_atom_site_type_symbol"))
+                .unwrap_or_else(|| element_from_label(&label));
+            Some(AtomSite {
+                fract_x: row.number("_atom_site_fract_x")?,
+                fract_y: row.number("_atom_site_fract_y")?,
+                fract_z: row.number("_atom_site_fract_z")?,
+                wyckoff_symbol: string(row.get("_atom_site_Wyckoff_symbol")),
+                symmetry_multiplicity: integer(row.get("_atom_site_symmetry_multiplicity")),
+                occupancy: row.number("_atom_site_occupancy").unwrap_or(1.0),
+                label,
+                type_symbol,
+            })
+        })
+        .collect()
+}
+
+fn symmetry_operations(block: &DataBlock) -> Vec<String> {
+    [
+        "_space_group_symop_operation_xyz",
+        "_symmetry_equiv_pos_as_xyz",
+    ]
+    .iter()
+    .find_map(|tag| {
+        block.find_loop(tag).map(|_| {
+            rows(block, tag)
+                .filter_map(|row| string(row.get(tag)))
+                .collect()
+        })
+    })
+    .or_else(|| {
+        // A single operation may be written as a plain item.
+        first_text(block, &["_space_group_symop_operation_xyz"]).map(|op| vec![op])
+    })
+    .unwrap_or_default()
+}
+
+/// Reciprocal cell lengths a*, b*, c*.
+fn reciprocal_lengths(data: &CrystalData) -> [f64; 3] {
+    let (a, b, c) = (
+        data.cell_length_a.value,
+        data.cell_length_b.value,
+        data.cell_length_c.value,
+    );
+    let [alpha, beta, gamma] = [
+        data.cell_angle_alpha.value,
+        data.cell_angle_beta.value,
+        data.cell_angle_gamma.value,
+    ]
+    .map(f64::to_radians);
+    let volume = a
+        * b
+        * c
+        * (1.0 - alpha.cos().powi(2) - beta.cos().powi(2) - gamma.cos().powi(2)
+            + 2.0 * alpha.cos() * beta.cos() * gamma.cos())
+        .sqrt();
+    [
+        b * c * alpha.sin() / volume,
+        a * c * beta.sin() / volume,
+        a * b * gamma.sin() / volume,
+    ]
+}
+
+const ANISO_INDICES: [(usize, usize); 6] = [(1, 1), (2, 2), (3, 3), (1, 2), (1, 3), (2, 3)];
+
+/// Anisotropic parameters as β_ij. Files give β directly or as U_ij or
+/// B_ij in Å², which are scaled by 2π²·a*_i·a*_j and a*_i·a*_j/4.
+fn anisotropic_params(block: &DataBlock, data: &CrystalData) -> Vec<AnisotropicParams> {
+    let reciprocal = reciprocal_lengths(data);
+    let Some(form) = ["beta", "U", "B"].into_iter().find(|form| {
+        block
+            .find_loop(&format!("_atom_site_aniso_{}_11", form))
+            .is_some()
+    }) else {
+        return Vec::new();
+    };
+    let factor = |i: usize, j: usize| {
+        let scale = reciprocal[i - 1] * reciprocal[j - 1];
+        match form {
+            "U" => 2.0 * PI * PI * scale,
+            "B" => scale / 4.0,
+            _ => 1.0,
+        }
+    };
+
+    rows(block, &format!("_atom_site_aniso_{}_11", form))
+        .map(|row| {
+            let label = string(row.get("_atom_site_aniso_label")).unwrap_or_default();
+            let type_symbol = string(row.get("_atom_site_aniso_type_symbol"))
+                .unwrap_or_else(|| element_from_label(&label));
+            let [beta_11, beta_22, beta_33, beta_12, beta_13, beta_23] =
+                ANISO_INDICES.map(|(i, j)| {
+                    row.number(&format!("_atom_site_aniso_{}_{}{}", form, i, j))
+                        .map_or(0.0, |value| value * factor(i, j))
+                });
+            AnisotropicParams {
+                label,
+                type_symbol,
+                beta_11,
+                beta_22,
+                beta_33,
+                beta_12,
+                beta_13,
+                beta_23,
+            }
+        })
+        .collect()
+}
+
+fn citation(block: &DataBlock) -> Option<Citation> {
+    let authors = ["_citation_author_name", "_publ_author_name"]
+        .iter()
+        .find_map(|tag| {
+            block.find_loop(tag).map(|_| {
+                rows(block, tag)
+                    .filter_map(|row| string(row.get(tag)))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .unwrap_or_default();
+    let citation = Citation {
+        title: first_text(block, &["_citation_title", "_publ_section_title"]),
+        journal: first_text(
+            block,
+            &[
+                "_citation_journal_full",
+                "_citation_journal_abbrev",
+                "_journal_name_full",
+            ],
+        ),
+        year: first_text(block, &["_citation_year", "_journal_year"])
+            .and_then(|year| year.parse().ok()),
+        volume: first_text(block, &["_citation_journal_volume", "_journal_volume"]),
+        page_first: first_text(block, &["_citation_page_first", "_journal_page_first"]),
+        page_last: first_text(block, &["_citation_page_last", "_journal_page_last"]),
+        authors,
+    };
+    (citation.title.is_some() || citation.journal.is_some() || !citation.authors.is_empty())
+        .then_some(citation)
+}
+
+impl CrystalData {
+    /// Reads the structure from `block`. Missing cell lengths are 0 and
+    /// missing angles 90°, as in the frontend parser.
+    pub fn from_block(block: &DataBlock) -> Self {
+        let length = |tag| first_number(block, &[tag]).unwrap_or(LatticeParameter::exact(0.0));
+        let angle = |tag| first_number(block, &[tag]).unwrap_or(LatticeParameter::exact(90.0));
+        let mut data = CrystalData {
+            chemical_name_common: first_text(block, &["_chemical_name_common"]),
+            formula_structural: first_text(block, &["_chemical_formula_structural"]),
+            formula_sum: first_text(block, &["_chemical_formula_sum"]),
+            structure_type: first_text(block, &["_chemical_name_structure_type"]),
+
+            cell_length_a: length("_cell_length_a"),
+            cell_length_b: length("_cell_length_b"),
+            cell_length_c: length("_cell_length_c"),
+            cell_angle_alpha: angle("_cell_angle_alpha"),
+            cell_angle_beta: angle("_cell_angle_beta"),
+            cell_angle_gamma: angle("_cell_angle_gamma"),
+            cell_volume: first_number(block, &["_cell_volume"]).map(|n| n.value),
+            cell_formula_units_z: first_integer(block, &["_cell_formula_units_Z"]),
+
+            space_group_hm: first_text(
+                block,
+                &[
+                    "_space_group_name_H-M_alt",
+                    "_symmetry_space_group_name_H-M",
+                ],
+            ),
+            space_group_it_number: first_integer(
+                block,
+                &["_space_group_IT_number", "_symmetry_Int_Tables_number"],
+            ),
+
+            density: first_number(block, &["_exptl_crystal_density_diffrn"]).map(|n| n.value),
+            measurement_temperature: first_number(
+                block,
+                &[
+                    "_diffrn_ambient_temperature",
+                    "_cell_measurement_temperature",
+                ],
+            )
+            .map(|n| n.value),
+
+            database_code: first_text(block, &["_database_code_ICSD", "_cod_database_code"]),
+            audit_creation_date: first_text(block, &["_audit_creation_date"]),
+            citation: citation(block),
+
+            atom_sites: atom_sites(block),
+            symmetry_operations: symmetry_operations(block),
+            anisotropic_params: Vec::new(),
+        };
+        data.anisotropic_params = anisotropic_params(block, &data);
+        data
+    }
+
+    /// A structure with just the cell filled in, for building one from
+    /// another file format. Lengths in Å.
+    pub fn from_cell(cell: &Cell) -> Self {
+        CrystalData {
+            chemical_name_common: None,
+            formula_structural: None,
+            formula_sum: None,
+            structure_type: None,
+            cell_length_a: LatticeParameter::exact(cell.a),
+            cell_length_b: LatticeParameter::exact(cell.b),
+            cell_length_c: LatticeParameter::exact(cell.c),
+            cell_angle_alpha: LatticeParameter::exact(cell.alpha),
+            cell_angle_beta: LatticeParameter::exact(cell.beta),
+            cell_angle_gamma: LatticeParameter::exact(cell.gamma),
+            cell_volume: Some(cell.volume()),
+            cell_formula_units_z: None,
+            space_group_hm: None,
+            space_group_it_number: None,
+            density: None,
+            measurement_temperature: None,
+            database_code: None,
+            audit_creation_date: None,
+            citation: None,
+            atom_sites: Vec::new(),
+            symmetry_operations: Vec::new(),
+            anisotropic_params: Vec::new(),
+        }
+    }
+
+    /// The cell's lattice parameters.
+    pub fn cell(&self) -> Cell {
+        Cell {
+            a: self.cell_length_a.value,
+            b: self.cell_length_b.value,
+            c: self.cell_length_c.value,
+            alpha: self.cell_angle_alpha.value,
+            beta: self.cell_angle_beta.value,
+            gamma: self.cell_angle_gamma.value,
+        }
+    }
+
+    /// Whether the block describes a structure at all, rather than e.g.
+    /// only publication details.
+    pub fn describes_structure(block: &DataBlock) -> bool {
+        block.first("_cell_length_a").is_some() || block.find_loop("_atom_site_label").is_some()
+    }
+}
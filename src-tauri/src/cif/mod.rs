@@ -0,0 +1,124 @@
+//! Crystallographic Information Files (CIF 1.1).
+//!
+//! `syntax` turns a file into data blocks of tagged values and loops;
+//! `crystal` reads the structure the frontend shows out of a block, and
+//! `write` turns one back into a file. The backend and the CLI use this
+//! instead of the webview's parser.
+
+mod crystal;
+mod syntax;
+mod write;
+
+pub use crystal::{
+    parse_number, AnisotropicParams, AtomSite, Citation, CrystalData, LatticeParameter,
+};
+pub use syntax::{parse, CifFile, DataBlock, Loop, Value};
+pub use write::write_cif;
+
+use crate::error::{IoOp, ReciprocalError, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// What a data block holds, for choosing among the blocks of a file with
+/// several phases or temperatures.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockSummary {
+    pub name: String,
+    pub formula: Option<String>,
+    #[serde(rename = "space_group_HM")]
+    pub space_group_hm: Option<String>,
+    #[serde(rename = "space_group_IT_number")]
+    pub space_group_it_number: Option<u32>,
+    pub cell_length_a: f64,
+    pub cell_length_b: f64,
+    pub cell_length_c: f64,
+    pub cell_angle_alpha: f64,
+    pub cell_angle_beta: f64,
+    pub cell_angle_gamma: f64,
+    pub atom_sites: usize,
+}
+
+impl BlockSummary {
+    fn new(name: &str, data: &CrystalData) -> Self {
+        BlockSummary {
+            name: name.to_string(),
+            formula: data
+                .formula_sum
+                .clone()
+                .or_else(|| data.formula_structural.clone()),
+            space_group_hm: data.space_group_hm.clone(),
+            space_group_it_number: data.space_group_it_number,
+            cell_length_a: data.cell_length_a.value,
+            cell_length_b: data.cell_length_b.value,
+            cell_length_c: data.cell_length_c.value,
+            cell_angle_alpha: data.cell_angle_alpha.value,
+            cell_angle_beta: data.cell_angle_beta.value,
+            cell_angle_gamma: data.cell_angle_gamma.value,
+            atom_sites: data.atom_sites.len(),
+        }
+    }
+}
+
+fn no_structure(file: &Path, message: String) -> ReciprocalError {
+    ReciprocalError::Parse {
+        file: file.to_path_buf(),
+        line: None,
+        message,
+    }
+}
+
+/// The blocks of `cif` that describe a crystal structure, in file order.
+/// Blocks of only publication details are left out.
+pub fn structure_blocks(cif: &CifFile) -> impl Iterator<Item = &DataBlock> {
+    cif.blocks
+        .iter()
+        .filter(|block| CrystalData::describes_structure(block))
+}
+
+/// The block named `name`, which must describe a crystal structure.
+pub fn structure_block<'a>(cif: &'a CifFile, name: &str, file: &Path) -> Result<&'a DataBlock> {
+    match cif.block(name) {
+        Some(block) if CrystalData::describes_structure(block) => Ok(block),
+        Some(_) => Err(no_structure(
+            file,
+            format!("Data block {} doesn't describe a crystal structure", name),
+        )),
+        None => Err(no_structure(file, format!("No data block named {}", name))),
+    }
+}
+
+/// Summaries of the structure blocks in CIF `text`.
+pub fn list_blocks(text: &str, file: &Path) -> Result<Vec<BlockSummary>> {
+    let cif = parse(text, file)?;
+    Ok(structure_blocks(&cif)
+        .map(|block| BlockSummary::new(&block.name, &CrystalData::from_block(block)))
+        .collect())
+}
+
+/// The structure in CIF `text`: the first data block that has a cell or
+/// atom sites, since some files open with a block of publication details.
+pub fn parse_crystal_data(text: &str, file: &Path) -> Result<CrystalData> {
+    let cif = parse(text, file)?;
+    let block = structure_blocks(&cif).next().ok_or_else(|| {
+        no_structure(
+            file,
+            "No data block describes a crystal structure".to_string(),
+        )
+    })?;
+    Ok(CrystalData::from_block(block))
+}
+
+pub fn read_text(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(ReciprocalError::io(IoOp::Read, path))
+}
+
+/// Reads and parses the CIF file at `path`.
+pub fn read_crystal_data(path: &Path) -> Result<CrystalData> {
+    parse_crystal_data(&read_text(path)?, path)
+}
+
+/// Summaries of the structure blocks in the CIF file at `path`.
+pub fn read_blocks(path: &Path) -> Result<Vec<BlockSummary>> {
+    list_blocks(&read_text(path)?, path)
+}
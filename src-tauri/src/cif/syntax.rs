@@ -0,0 +1,353 @@
+//! The CIF 1.1 grammar: data blocks, tag/value pairs and loops.
+
+use crate::error::{ReciprocalError, Result};
+use std::ops::Range;
+use std::path::Path;
+
+/// A value as written in the file. `?` and `.` are only special when
+/// unquoted; `'?'` is the text "?".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Text(String),
+    /// `?`: the value is unknown.
+    Unknown,
+    /// `.`: the item doesn't apply.
+    Inapplicable,
+}
+
+impl Value {
+    /// The text of the value, or `None` for `?` and `.`.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            Value::Unknown | Value::Inapplicable => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Loop {
+    pub tags: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Loop {
+    /// Column of `tag`, compared case-insensitively like all CIF tags.
+    pub fn column(&self, tag: &str) -> Option<usize> {
+        self.tags.iter().position(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DataBlock {
+    /// Block name without the `data_` prefix.
+    pub name: String,
+    /// Items set outside loops, in file order.
+    pub items: Vec<(String, Value)>,
+    pub loops: Vec<Loop>,
+    /// Byte range of the block in the parsed text, from its `data_` line
+    /// up to the next block, so a block can be saved on its own.
+    pub source: Range<usize>,
+}
+
+impl DataBlock {
+    /// Value of `tag` set outside a loop.
+    pub fn value(&self, tag: &str) -> Option<&Value> {
+        self.items
+            .iter()
+            .find(|(t, _)| t.eq_ignore_ascii_case(tag))
+            .map(|(_, value)| value)
+    }
+
+    /// The loop that has a column for `tag`.
+    pub fn find_loop(&self, tag: &str) -> Option<&Loop> {
+        self.loops.iter().find(|l| l.column(tag).is_some())
+    }
+
+    /// Value of `tag`, whether set directly or as the first row of a loop.
+    /// Single-row categories such as the citation are written either way.
+    pub fn first(&self, tag: &str) -> Option<&Value> {
+        self.value(tag).or_else(|| {
+            let l = self.find_loop(tag)?;
+            l.rows.first()?.get(l.column(tag)?)
+        })
+    }
+
+    /// Text of `tag` as `first` finds it, skipping `?` and `.`.
+    pub fn text(&self, tag: &str) -> Option<&str> {
+        self.first(tag).and_then(Value::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CifFile {
+    pub blocks: Vec<DataBlock>,
+}
+
+impl CifFile {
+    /// The block named `name`; block names are case-insensitive.
+    pub fn block(&self, name: &str) -> Option<&DataBlock> {
+        self.blocks
+            .iter()
+            .find(|block| block.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    DataBlock(String),
+    Loop,
+    Global,
+    SaveBegin,
+    SaveEnd,
+    Stop,
+    Tag(String),
+    Value(Value),
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    offset: usize,
+}
+
+struct Tokenizer<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+    file: &'a Path,
+}
+
+fn starts_with_ignore_case(word: &str, prefix: &str) -> bool {
+    word.get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+}
+
+impl<'a> Tokenizer<'a> {
+    fn error(&self, line: usize, message: impl Into<String>) -> ReciprocalError {
+        ReciprocalError::Parse {
+            file: self.file.to_path_buf(),
+            line: Some(line),
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.pos == 0 || self.text.as_bytes()[self.pos - 1] == b'\n'
+    }
+
+    /// Advances past `len` bytes, counting the newlines in them.
+    fn advance(&mut self, len: usize) {
+        self.line += self.text[self.pos..self.pos + len].matches('\n').count();
+        self.pos += len;
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start_matches([' ', '\t', '\r', '\n', '\u{feff}']);
+            self.advance(rest.len() - trimmed.len());
+            if trimmed.starts_with('#') {
+                self.advance(trimmed.find('\n').unwrap_or(trimmed.len()));
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// A `;`-delimited text field starting at the current position, which
+    /// is a `;` at the start of a line.
+    fn text_field(&mut self) -> Result<Value> {
+        let start_line = self.line;
+        let body = &self.rest()[1..];
+        let end = body
+            .match_indices("\n;")
+            .next()
+            .map(|(i, _)| i)
+            .ok_or_else(|| self.error(start_line, "Unterminated text field"))?;
+        let content = body[..end].strip_suffix('\r').unwrap_or(&body[..end]);
+        let value = Value::Text(content.to_string());
+        self.advance(1 + end + 2);
+        Ok(value)
+    }
+
+    /// A quoted string, which ends at a matching quote followed by
+    /// whitespace, so `'O'Brien'` is the text `O'Brien`.
+    fn quoted(&mut self, quote: char) -> Result<Value> {
+        let line = self.line;
+        let rest = &self.rest()[1..];
+        let line_end = rest.find(['\n', '\r']).unwrap_or(rest.len());
+        let on_line = &rest[..line_end];
+        let close = on_line
+            .match_indices(quote)
+            .map(|(i, _)| i)
+            .find(|&i| {
+                on_line[i + 1..]
+                    .chars()
+                    .next()
+                    .is_none_or(|c| c == ' ' || c == '\t')
+            })
+            .ok_or_else(|| self.error(line, "Unterminated quoted string"))?;
+        let value = Value::Text(on_line[..close].to_string());
+        self.advance(1 + close + 1);
+        Ok(value)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>> {
+        self.skip_whitespace_and_comments();
+        let (line, offset) = (self.line, self.pos);
+        let Some(c) = self.rest().chars().next() else {
+            return Ok(None);
+        };
+
+        let kind = match c {
+            ';' if self.at_line_start() => TokenKind::Value(self.text_field()?),
+            '\'' | '"' => TokenKind::Value(self.quoted(c)?),
+            _ => {
+                let rest = self.rest();
+                let len = rest.find([' ', '\t', '\r', '\n']).unwrap_or(rest.len());
+                let word = &rest[..len];
+                self.advance(len);
+
+                if word.starts_with('_') {
+                    TokenKind::Tag(word.to_string())
+                } else if starts_with_ignore_case(word, "data_") {
+                    if word.len() == 5 {
+                        return Err(self.error(line, "Data block has no name"));
+                    }
+                    TokenKind::DataBlock(word[5..].to_string())
+                } else if word.eq_ignore_ascii_case("loop_") {
+                    TokenKind::Loop
+                } else if word.eq_ignore_ascii_case("global_") {
+                    TokenKind::Global
+                } else if word.eq_ignore_ascii_case("save_") {
+                    TokenKind::SaveEnd
+                } else if starts_with_ignore_case(word, "save_") {
+                    TokenKind::SaveBegin
+                } else if word.eq_ignore_ascii_case("stop_") {
+                    TokenKind::Stop
+                } else if word == "?" {
+                    TokenKind::Value(Value::Unknown)
+                } else if word == "." {
+                    TokenKind::Value(Value::Inapplicable)
+                } else {
+                    TokenKind::Value(Value::Text(word.to_string()))
+                }
+            }
+        };
+        Ok(Some(Token { kind, line, offset }))
+    }
+}
+
+fn tokenize(text: &str, file: &Path) -> Result<Vec<Token>> {
+    let mut tokenizer = Tokenizer {
+        text,
+        pos: 0,
+        line: 1,
+        file,
+    };
+    let mut tokens = Vec::new();
+    while let Some(token) = tokenizer.next_token()? {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Parses CIF 1.1 `text`. `file` is only used to locate errors.
+pub fn parse(text: &str, file: &Path) -> Result<CifFile> {
+    let error = |line: usize, message: String| ReciprocalError::Parse {
+        file: file.to_path_buf(),
+        line: Some(line),
+        message,
+    };
+
+    let tokens = tokenize(text, file)?;
+    let mut cif = CifFile::default();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        i += 1;
+
+        if let TokenKind::DataBlock(name) = &token.kind {
+            if let Some(previous) = cif.blocks.last_mut() {
+                previous.source.end = token.offset;
+            }
+            cif.blocks.push(DataBlock {
+                name: name.clone(),
+                source: token.offset..text.len(),
+                ..Default::default()
+            });
+            continue;
+        }
+        let block = match (&token.kind, cif.blocks.last_mut()) {
+            (TokenKind::SaveBegin, _) => {
+                // Save frames only appear in dictionaries; skip their contents.
+                while i < tokens.len() && tokens[i].kind != TokenKind::SaveEnd {
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            (TokenKind::Global, _) => {
+                return Err(error(token.line, "global_ blocks are not supported".into()))
+            }
+            (TokenKind::Stop, _) | (TokenKind::SaveEnd, _) => {
+                return Err(error(token.line, "Unexpected reserved word".into()))
+            }
+            (_, None) => {
+                return Err(error(
+                    token.line,
+                    "Data before the first data_ block".into(),
+                ))
+            }
+            (_, Some(block)) => block,
+        };
+
+        match &token.kind {
+            TokenKind::Tag(tag) => match tokens.get(i).map(|t| &t.kind) {
+                Some(TokenKind::Value(value)) => {
+                    block.items.push((tag.clone(), value.clone()));
+                    i += 1;
+                }
+                _ => return Err(error(token.line, format!("{} has no value", tag))),
+            },
+            TokenKind::Loop => {
+                let mut l = Loop::default();
+                while let Some(TokenKind::Tag(tag)) = tokens.get(i).map(|t| &t.kind) {
+                    l.tags.push(tag.clone());
+                    i += 1;
+                }
+                if l.tags.is_empty() {
+                    return Err(error(token.line, "loop_ has no tags".into()));
+                }
+                let mut values = Vec::new();
+                while let Some(TokenKind::Value(value)) = tokens.get(i).map(|t| &t.kind) {
+                    values.push(value.clone());
+                    i += 1;
+                }
+                if values.len() % l.tags.len() != 0 {
+                    return Err(error(
+                        token.line,
+                        format!(
+                            "loop_ has {} values, which isn't a multiple of its {} tags",
+                            values.len(),
+                            l.tags.len()
+                        ),
+                    ));
+                }
+                l.rows = values.chunks(l.tags.len()).map(<[Value]>::to_vec).collect();
+                block.loops.push(l);
+            }
+            TokenKind::Value(_) => {
+                return Err(error(token.line, "Value without a tag".into()));
+            }
+            _ => unreachable!("handled above"),
+        }
+    }
+    Ok(cif)
+}
@@ -0,0 +1,347 @@
+//! Writing a `CrystalData` back out as a CIF 1.1 data block.
+//!
+//! Items use the core dictionary names (`_symmetry_*` rather than the
+//! newer `_space_group_*`) since those are what older programs such as
+//! VESTA look for, and everything written here reads back through `parse`
+//! to the same `CrystalData`.
+
+use super::crystal::{CrystalData, LatticeParameter};
+use std::fmt::Write;
+
+/// Values longer than this go in a text field rather than on the tag's
+/// line, keeping lines within the recommended 80 columns.
+const MAX_INLINE_LEN: usize = 60;
+
+/// A number as CIF writes it, `?` if it isn't one.
+fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "?".to_string()
+    }
+}
+
+/// A number with its standard uncertainty in the last digits, rounded so
+/// the uncertainty has one digit: 5.194 ± 0.001 is `5.194(1)`.
+fn measured(parameter: &LatticeParameter) -> String {
+    let Some(su) = parameter
+        .uncertainty
+        .filter(|su| su.is_finite() && *su > 0.0 && parameter.value.is_finite())
+    else {
+        return number(parameter.value);
+    };
+    let mut decimals = -su.log10().floor() as i32;
+    let mut digits = (su * 10f64.powi(decimals)).round();
+    if digits >= 10.0 {
+        decimals -= 1;
+        digits = (su * 10f64.powi(decimals)).round();
+    }
+    if decimals > 0 {
+        format!("{:.*}({})", decimals as usize, parameter.value, digits)
+    } else {
+        // Uncertainties of 10 or more are written in whole units.
+        format!("{:.0}({})", parameter.value, su.round())
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    let lower = word.to_ascii_lowercase();
+    ["data_", "save_"].iter().any(|p| lower.starts_with(p))
+        || ["loop_", "global_", "stop_"].contains(&lower.as_str())
+}
+
+/// Whether `text` can be written between `quote`s: the quote may appear
+/// inside, but not followed by whitespace, which would end the string.
+fn quotable(text: &str, quote: char) -> bool {
+    !text.ends_with(quote)
+        && !text.contains(['\n', '\r'])
+        && !text
+            .match_indices(quote)
+            .any(|(i, _)| text[i + 1..].starts_with([' ', '\t']))
+}
+
+enum Text {
+    Inline(String),
+    /// Needs a `;` text field on lines of its own.
+    Field(String),
+}
+
+fn text(value: &str) -> Text {
+    let bare = !value.is_empty()
+        && !value.contains(char::is_whitespace)
+        && !value.starts_with(['_', '#', '$', '\'', '"', '[', ']', ';'])
+        && !is_reserved(value)
+        && value != "?"
+        && value != ".";
+    if value.len() > MAX_INLINE_LEN || value.contains('\n') {
+        // A line starting with ';' would end the field early.
+        let body = value.replace("\n;", "\n ;");
+        return Text::Field(body);
+    }
+    if bare {
+        Text::Inline(value.to_string())
+    } else if quotable(value, '\'') {
+        Text::Inline(format!("'{}'", value))
+    } else if quotable(value, '"') {
+        Text::Inline(format!("\"{}\"", value))
+    } else {
+        Text::Field(value.to_string())
+    }
+}
+
+/// Appends a value after a tag or within a loop row.
+fn push_value(out: &mut String, value: Text) {
+    match value {
+        Text::Inline(value) => {
+            out.push(' ');
+            out.push_str(&value);
+        }
+        Text::Field(body) => {
+            out.push_str("\n;");
+            out.push_str(&body);
+            out.push_str("\n;\n");
+        }
+    }
+}
+
+struct Writer {
+    out: String,
+}
+
+impl Writer {
+    fn item(&mut self, tag: &str, value: String) {
+        self.out.push_str(tag);
+        push_value(&mut self.out, Text::Inline(value));
+        self.out.push('\n');
+    }
+
+    fn text_item(&mut self, tag: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.out.push_str(tag);
+            let value = text(value);
+            let is_field = matches!(value, Text::Field(_));
+            push_value(&mut self.out, value);
+            if !is_field {
+                self.out.push('\n');
+            }
+        }
+    }
+
+    /// Writes a loop whose rows are given as already formatted values.
+    fn table(&mut self, tags: &[&str], rows: impl IntoIterator<Item = Vec<Text>>) {
+        self.out.push_str("\nloop_\n");
+        for tag in tags {
+            self.out.push_str(tag);
+            self.out.push('\n');
+        }
+        for row in rows {
+            let mut line_open = false;
+            for value in row {
+                match value {
+                    Text::Inline(_) => {
+                        if !line_open {
+                            // Indent so values never start a line with ';'.
+                            self.out.push(' ');
+                        }
+                        push_value(&mut self.out, value);
+                        line_open = true;
+                    }
+                    Text::Field(_) => {
+                        push_value(&mut self.out, value);
+                        line_open = false;
+                    }
+                }
+            }
+            if line_open {
+                self.out.push('\n');
+            }
+        }
+    }
+}
+
+fn optional_text(value: Option<&str>) -> Text {
+    value.map_or(Text::Inline("?".into()), text)
+}
+
+fn optional_number(value: Option<f64>) -> Text {
+    Text::Inline(value.map_or("?".to_string(), number))
+}
+
+/// A data block name: CIF allows no whitespace in it.
+fn block_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        "structure".to_string()
+    } else {
+        name
+    }
+}
+
+/// `data` as a CIF 1.1 file with a single block called `name`.
+pub fn write_cif(data: &CrystalData, name: &str) -> String {
+    let mut w = Writer { out: String::new() };
+    let _ = writeln!(w.out, "#\\#CIF_1.1");
+    let _ = writeln!(
+        w.out,
+        "# Written by Reciprocal {}",
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(w.out, "data_{}", block_name(name));
+    w.out.push('\n');
+
+    w.text_item("_audit_creation_date", data.audit_creation_date.as_deref());
+    w.text_item(
+        "_audit_creation_method",
+        Some(&format!("Reciprocal {}", env!("CARGO_PKG_VERSION"))),
+    );
+    w.text_item("_database_code_ICSD", data.database_code.as_deref());
+    w.text_item(
+        "_chemical_name_common",
+        data.chemical_name_common.as_deref(),
+    );
+    w.text_item(
+        "_chemical_formula_structural",
+        data.formula_structural.as_deref(),
+    );
+    w.text_item("_chemical_formula_sum", data.formula_sum.as_deref());
+    w.text_item(
+        "_chemical_name_structure_type",
+        data.structure_type.as_deref(),
+    );
+    if let Some(density) = data.density {
+        w.item("_exptl_crystal_density_diffrn", number(density));
+    }
+    if let Some(temperature) = data.measurement_temperature {
+        w.item("_diffrn_ambient_temperature", number(temperature));
+    }
+
+    w.out.push('\n');
+    w.item("_cell_length_a", measured(&data.cell_length_a));
+    w.item("_cell_length_b", measured(&data.cell_length_b));
+    w.item("_cell_length_c", measured(&data.cell_length_c));
+    w.item("_cell_angle_alpha", measured(&data.cell_angle_alpha));
+    w.item("_cell_angle_beta", measured(&data.cell_angle_beta));
+    w.item("_cell_angle_gamma", measured(&data.cell_angle_gamma));
+    if let Some(volume) = data.cell_volume {
+        w.item("_cell_volume", number(volume));
+    }
+    if let Some(z) = data.cell_formula_units_z {
+        w.item("_cell_formula_units_Z", z.to_string());
+    }
+    w.text_item(
+        "_symmetry_space_group_name_H-M",
+        data.space_group_hm.as_deref(),
+    );
+    if let Some(number) = data.space_group_it_number {
+        w.item("_symmetry_Int_Tables_number", number.to_string());
+    }
+
+    if let Some(citation) = &data.citation {
+        w.table(
+            &[
+                "_citation_id",
+                "_citation_title",
+                "_citation_journal_full",
+                "_citation_year",
+                "_citation_journal_volume",
+                "_citation_page_first",
+                "_citation_page_last",
+            ],
+            [vec![
+                Text::Inline("primary".into()),
+                optional_text(citation.title.as_deref()),
+                optional_text(citation.journal.as_deref()),
+                optional_number(citation.year.map(f64::from)),
+                optional_text(citation.volume.as_deref()),
+                optional_text(citation.page_first.as_deref()),
+                optional_text(citation.page_last.as_deref()),
+            ]],
+        );
+        if !citation.authors.is_empty() {
+            w.table(
+                &["_citation_author_citation_id", "_citation_author_name"],
+                citation
+                    .authors
+                    .iter()
+                    .map(|author| vec![Text::Inline("primary".into()), text(author)]),
+            );
+        }
+    }
+
+    // Without operations, readers fall back to the space group symbol.
+    if !data.symmetry_operations.is_empty() {
+        w.table(
+            &["_symmetry_equiv_pos_site_id", "_symmetry_equiv_pos_as_xyz"],
+            data.symmetry_operations
+                .iter()
+                .enumerate()
+                .map(|(i, op)| vec![Text::Inline((i + 1).to_string()), text(op)]),
+        );
+    }
+
+    if !data.atom_sites.is_empty() {
+        let sites = &data.atom_sites;
+        let with_multiplicity = sites.iter().any(|s| s.symmetry_multiplicity.is_some());
+        let with_wyckoff = sites.iter().any(|s| s.wyckoff_symbol.is_some());
+        let mut tags = vec!["_atom_site_label", "_atom_site_type_symbol"];
+        if with_multiplicity {
+            tags.push("_atom_site_symmetry_multiplicity");
+        }
+        if with_wyckoff {
+            tags.push("_atom_site_Wyckoff_symbol");
+        }
+        tags.extend([
+            "_atom_site_fract_x",
+            "_atom_site_fract_y",
+            "_atom_site_fract_z",
+            "_atom_site_occupancy",
+        ]);
+        w.table(
+            &tags,
+            sites.iter().map(|site| {
+                let mut row = vec![text(&site.label), text(&site.type_symbol)];
+                if with_multiplicity {
+                    row.push(optional_number(site.symmetry_multiplicity.map(f64::from)));
+                }
+                if with_wyckoff {
+                    row.push(optional_text(site.wyckoff_symbol.as_deref()));
+                }
+                row.extend(
+                    [site.fract_x, site.fract_y, site.fract_z, site.occupancy]
+                        .map(|v| Text::Inline(number(v))),
+                );
+                row
+            }),
+        );
+    }
+
+    if !data.anisotropic_params.is_empty() {
+        w.table(
+            &[
+                "_atom_site_aniso_label",
+                "_atom_site_aniso_type_symbol",
+                "_atom_site_aniso_beta_11",
+                "_atom_site_aniso_beta_22",
+                "_atom_site_aniso_beta_33",
+                "_atom_site_aniso_beta_12",
+                "_atom_site_aniso_beta_13",
+                "_atom_site_aniso_beta_23",
+            ],
+            data.anisotropic_params.iter().map(|p| {
+                let mut row = vec![text(&p.label), text(&p.type_symbol)];
+                row.extend(
+                    [
+                        p.beta_11, p.beta_22, p.beta_33, p.beta_12, p.beta_13, p.beta_23,
+                    ]
+                    .map(|v| Text::Inline(number(v))),
+                );
+                row
+            }),
+        );
+    }
+
+    w.out
+}
@@ -0,0 +1,40 @@
+//! Chemical element symbols and atomic numbers.
+
+const SYMBOLS: [&str; 103] = [
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl",
+    "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As",
+    "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+    "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb",
+    "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl",
+    "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk",
+    "Cf", "Es", "Fm", "Md", "No", "Lr",
+];
+
+/// Atomic number of an element symbol such as `Fe`, ignoring case.
+pub fn atomic_number(symbol: &str) -> Option<u32> {
+    SYMBOLS
+        .iter()
+        .position(|s| s.eq_ignore_ascii_case(symbol))
+        .map(|i| i as u32 + 1)
+}
+
+pub fn symbol(atomic_number: u32) -> Option<&'static str> {
+    SYMBOLS
+        .get((atomic_number as usize).checked_sub(1)?)
+        .copied()
+}
+
+/// The element at the start of an atom type or label, such as `Sr2+`,
+/// `Fe1` or `O1a`: two letters if they name an element, otherwise one.
+pub fn element_of(name: &str) -> Option<&'static str> {
+    let letters: String = name
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .take(2)
+        .collect();
+    let two = (letters.len() == 2)
+        .then(|| atomic_number(&letters))
+        .flatten();
+    two.or_else(|| atomic_number(letters.get(..1)?))
+        .and_then(symbol)
+}
@@ -0,0 +1,267 @@
+use crate::links::LinkStatus;
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub type Result<T> = std::result::Result<T, ReciprocalError>;
+
+/// The kind of library item a `NotFound` error refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Project,
+    BandStructure,
+    FermiSurface,
+    Structure,
+    /// A band structure or Fermi surface, when it isn't known which.
+    Dataset,
+    File,
+    TrashEntry,
+    Job,
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResourceKind::Project => "Project",
+            ResourceKind::BandStructure => "Band structure",
+            ResourceKind::FermiSurface => "Fermi surface",
+            ResourceKind::Structure => "Structure",
+            ResourceKind::Dataset => "Dataset",
+            ResourceKind::File => "File",
+            ResourceKind::TrashEntry => "Trash entry",
+            ResourceKind::Job => "Job",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The filesystem operation that failed in an `Io` error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoOp {
+    Read,
+    Write,
+    Copy,
+    CreateDir,
+    ReadDir,
+    Delete,
+}
+
+impl fmt::Display for IoOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = match self {
+            IoOp::Read => "read",
+            IoOp::Write => "write",
+            IoOp::Copy => "copy",
+            IoOp::CreateDir => "create directory",
+            IoOp::ReadDir => "read directory",
+            IoOp::Delete => "delete",
+        };
+        f.write_str(verb)
+    }
+}
+
+/// Error returned by every Tauri command.
+///
+/// Serialized as an object tagged by `type`, carrying the variant's fields
+/// plus a human readable `message`, so the frontend can branch on the
+/// variant and still show something sensible.
+#[derive(Debug)]
+pub enum ReciprocalError {
+    NotFound {
+        kind: ResourceKind,
+        id: String,
+    },
+    /// An ID supplied by the caller is not a valid UUID.
+    InvalidId {
+        kind: ResourceKind,
+        id: String,
+    },
+    /// Something already occupies the place an item would be put.
+    AlreadyExists {
+        kind: ResourceKind,
+        id: String,
+    },
+    Io {
+        path: PathBuf,
+        op: IoOp,
+        source: io::Error,
+    },
+    /// An input file (CIF, QTL, ...) could not be parsed.
+    Parse {
+        file: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A file in the library exists but its contents are unusable.
+    Corrupt {
+        path: PathBuf,
+        reason: String,
+    },
+    /// A document was written by a newer build with a schema we can't read.
+    UnsupportedSchema {
+        path: PathBuf,
+        version: u32,
+    },
+    /// A file a dataset links to is missing or no longer matches what was
+    /// imported.
+    BrokenLink {
+        path: PathBuf,
+        /// Name the file is stored under in the dataset, e.g. `data.output1`.
+        name: String,
+        status: LinkStatus,
+    },
+    /// The job doing the work was cancelled.
+    Cancelled,
+    Internal {
+        message: String,
+    },
+}
+
+impl ReciprocalError {
+    pub fn not_found(kind: ResourceKind, id: impl Into<String>) -> Self {
+        ReciprocalError::NotFound {
+            kind,
+            id: id.into(),
+        }
+    }
+
+    /// Returns a closure for `map_err` that wraps an `io::Error` with the
+    /// operation and path it happened on.
+    pub fn io(op: IoOp, path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| ReciprocalError::Io { path, op, source }
+    }
+
+    /// Returns a closure for `map_err` that marks a stored JSON document as
+    /// corrupt.
+    pub fn corrupt(path: impl AsRef<Path>) -> impl FnOnce(serde_json::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |e| ReciprocalError::Corrupt {
+            path,
+            reason: e.to_string(),
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ReciprocalError::Internal {
+            message: message.into(),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            ReciprocalError::NotFound { .. } => "not_found",
+            ReciprocalError::InvalidId { .. } => "invalid_id",
+            ReciprocalError::AlreadyExists { .. } => "already_exists",
+            ReciprocalError::Io { .. } => "io",
+            ReciprocalError::Parse { .. } => "parse",
+            ReciprocalError::Corrupt { .. } => "corrupt",
+            ReciprocalError::UnsupportedSchema { .. } => "unsupported_schema",
+            ReciprocalError::BrokenLink { .. } => "broken_link",
+            ReciprocalError::Cancelled => "cancelled",
+            ReciprocalError::Internal { .. } => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ReciprocalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReciprocalError::NotFound { kind, id } => write!(f, "{} {} not found", kind, id),
+            ReciprocalError::InvalidId { kind, id } => {
+                write!(f, "{:?} is not a valid {} ID", id, kind.to_string().to_lowercase())
+            }
+            ReciprocalError::AlreadyExists { kind, id } => {
+                write!(f, "{} {} already exists", kind, id)
+            }
+            ReciprocalError::Io { path, op, source } => {
+                write!(f, "Failed to {} {}: {}", op, path.display(), source)
+            }
+            ReciprocalError::Parse {
+                file,
+                line: Some(line),
+                message,
+            } => write!(f, "{}:{}: {}", file.display(), line, message),
+            ReciprocalError::Parse {
+                file,
+                line: None,
+                message,
+            } => write!(f, "{}: {}", file.display(), message),
+            ReciprocalError::Corrupt { path, reason } => {
+                write!(f, "{} is corrupt: {}", path.display(), reason)
+            }
+            ReciprocalError::UnsupportedSchema { path, version } => write!(
+                f,
+                "{} uses schema version {}, which is newer than this version of Reciprocal supports",
+                path.display(),
+                version
+            ),
+            ReciprocalError::BrokenLink {
+                path,
+                status: LinkStatus::Missing,
+                ..
+            } => write!(f, "Linked file {} is missing", path.display()),
+            ReciprocalError::BrokenLink { path, .. } => write!(
+                f,
+                "Linked file {} has changed since it was imported",
+                path.display()
+            ),
+            ReciprocalError::Cancelled => f.write_str("Cancelled"),
+            ReciprocalError::Internal { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ReciprocalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReciprocalError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for ReciprocalError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", self.type_name())?;
+        match self {
+            ReciprocalError::NotFound { kind, id }
+            | ReciprocalError::InvalidId { kind, id }
+            | ReciprocalError::AlreadyExists { kind, id } => {
+                map.serialize_entry("kind", kind)?;
+                map.serialize_entry("id", id)?;
+            }
+            ReciprocalError::Io { path, op, source } => {
+                map.serialize_entry("path", path)?;
+                map.serialize_entry("op", op)?;
+                // `io::ErrorKind` has no serde support; its Debug name
+                // ("NotFound", "StorageFull", ...) is stable enough to match on.
+                map.serialize_entry("io_kind", &format!("{:?}", source.kind()))?;
+            }
+            ReciprocalError::Parse { file, line, .. } => {
+                map.serialize_entry("file", file)?;
+                map.serialize_entry("line", line)?;
+            }
+            ReciprocalError::Corrupt { path, .. } => {
+                map.serialize_entry("path", path)?;
+            }
+            ReciprocalError::UnsupportedSchema { path, version } => {
+                map.serialize_entry("path", path)?;
+                map.serialize_entry("version", version)?;
+            }
+            ReciprocalError::BrokenLink { path, name, status } => {
+                map.serialize_entry("path", path)?;
+                map.serialize_entry("name", name)?;
+                map.serialize_entry("status", status)?;
+            }
+            ReciprocalError::Cancelled | ReciprocalError::Internal { .. } => {}
+        }
+        map.serialize_entry("message", &self.to_string())?;
+        map.end()
+    }
+}
@@ -0,0 +1,78 @@
+//! FermiSurfer input files (`.frmsf`).
+//!
+//! FermiSurfer takes a Γ-centred grid without the periodic end points and
+//! draws the surface where the energy is zero, so energies are written
+//! relative to the Fermi energy. Values run with the third axis fastest.
+
+use crate::band_grid::{BandGrid, GridColour};
+use crate::lattice;
+use std::fmt::Write;
+
+/// Points along each axis once the end points BXSF and Wien2k include are
+/// dropped.
+fn periodic_dims(grid: &BandGrid) -> [usize; 3] {
+    [grid.nx, grid.ny, grid.nz].map(|n| n.saturating_sub(1).max(1))
+}
+
+/// |∇E| at every point of the periodic grid, from central differences.
+fn fermi_velocity(grid: &BandGrid, band: &[f32]) -> Vec<f64> {
+    let dims = periodic_dims(grid);
+    let to_cartesian = lattice::invert(&grid.reciprocal_vectors).unwrap_or([[0.0; 3]; 3]);
+    let energy = |p: [usize; 3]| band[grid.index(p[0], p[1], p[2])] as f64;
+
+    let mut speeds = Vec::with_capacity(dims.iter().product());
+    for x in 0..dims[0] {
+        for y in 0..dims[1] {
+            for z in 0..dims[2] {
+                let p = [x, y, z];
+                // dE/df along each reciprocal vector, f in fractions of it.
+                let slope: [f64; 3] = std::array::from_fn(|axis| {
+                    let n = dims[axis];
+                    let mut next = p;
+                    let mut previous = p;
+                    next[axis] = (p[axis] + 1) % n;
+                    previous[axis] = (p[axis] + n - 1) % n;
+                    (energy(next) - energy(previous)) * n as f64 / 2.0
+                });
+                let gradient: [f64; 3] =
+                    std::array::from_fn(|i| (0..3).map(|j| to_cartesian[i][j] * slope[j]).sum());
+                speeds.push(lattice::dot(&gradient, &gradient).sqrt());
+            }
+        }
+    }
+    speeds
+}
+
+/// The chosen bands of `grid` as a FermiSurfer file, with colour data
+/// after the energies when `colour` is given.
+pub fn to_text(grid: &BandGrid, bands: &[usize], colour: Option<GridColour>) -> String {
+    let dims = periodic_dims(grid);
+    let mut out = String::new();
+    let _ = writeln!(out, "{} {} {}", dims[0], dims[1], dims[2]);
+    // 0: the grid starts at Γ.
+    out.push_str("0\n");
+    let _ = writeln!(out, "{}", bands.len());
+    for v in &grid.reciprocal_vectors {
+        let _ = writeln!(out, "{:16.10} {:16.10} {:16.10}", v[0], v[1], v[2]);
+    }
+
+    for &b in bands {
+        let band = &grid.bands[b];
+        for x in 0..dims[0] {
+            for y in 0..dims[1] {
+                for z in 0..dims[2] {
+                    let energy = band[grid.index(x, y, z)] as f64 - grid.fermi_energy;
+                    let _ = writeln!(out, "{:.8}", energy);
+                }
+            }
+        }
+    }
+    if let Some(GridColour::FermiVelocity) = colour {
+        for &b in bands {
+            for speed in fermi_velocity(grid, &grid.bands[b]) {
+                let _ = writeln!(out, "{:.8}", speed);
+            }
+        }
+    }
+    out
+}
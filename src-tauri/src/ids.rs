@@ -0,0 +1,182 @@
+//! Validated identifiers for library items.
+//!
+//! IDs arrive from the frontend, archives and trash entries and are joined
+//! onto filesystem paths, so anything that isn't a UUID is rejected before
+//! it gets near a `Path`. Parsed IDs are stored in canonical lowercase,
+//! hyphenated form, which can't contain separators or `..`.
+
+use crate::error::{ReciprocalError, ResourceKind, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use uuid::Uuid;
+
+macro_rules! uuid_id {
+    ($(#[$meta:meta])* $name:ident, $kind:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            /// A fresh random ID.
+            pub fn new() -> Self {
+                $name(Uuid::new_v4().to_string())
+            }
+
+            pub fn parse(id: &str) -> Result<Self> {
+                Uuid::try_parse(id)
+                    .map(|uuid| $name(uuid.to_string()))
+                    .map_err(|_| ReciprocalError::InvalidId {
+                        kind: $kind,
+                        id: id.to_string(),
+                    })
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> String {
+                id.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<std::path::Path> for $name {
+            fn as_ref(&self) -> &std::path::Path {
+                std::path::Path::new(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                let id = String::deserialize(deserializer)?;
+                $name::parse(&id).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+uuid_id!(
+    /// ID of a project directory under `projects/`.
+    ProjectId,
+    ResourceKind::Project
+);
+
+uuid_id!(
+    /// ID of a band structure or Fermi surface directory within a project.
+    DatasetId,
+    ResourceKind::Dataset
+);
+
+uuid_id!(
+    /// ID of an entry in the trash.
+    TrashId,
+    ResourceKind::TrashEntry
+);
+
+uuid_id!(
+    /// ID of a background job. Jobs only live as long as the process.
+    JobId,
+    ResourceKind::Job
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Component, Path};
+
+    const TRAVERSALS: &[&str] = &[
+        "",
+        ".",
+        "..",
+        "../..",
+        "../../etc/passwd",
+        "/etc",
+        "C:\\Windows",
+        "..\\..",
+        "a/b",
+        "6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11/..",
+        "../6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11",
+        "6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11\0",
+        "6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c1",
+    ];
+
+    #[test]
+    fn rejects_traversal_attempts() {
+        for id in TRAVERSALS {
+            assert!(
+                ProjectId::parse(id).is_err(),
+                "accepted project id {:?}",
+                id
+            );
+            assert!(
+                DatasetId::parse(id).is_err(),
+                "accepted dataset id {:?}",
+                id
+            );
+            assert!(TrashId::parse(id).is_err(), "accepted trash id {:?}", id);
+        }
+    }
+
+    #[test]
+    fn rejection_is_a_typed_error() {
+        match ProjectId::parse("../..") {
+            Err(ReciprocalError::InvalidId { kind, id }) => {
+                assert_eq!(kind, ResourceKind::Project);
+                assert_eq!(id, "../..");
+            }
+            other => panic!("expected InvalidId, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_uuids_in_canonical_form() {
+        let id = ProjectId::parse("6F1C1B43-7C2E-4F43-9A43-0B7F8F7F2C11").unwrap();
+        assert_eq!(id.as_str(), "6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11");
+
+        let id = DatasetId::parse("{6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11}").unwrap();
+        assert_eq!(id.as_str(), "6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11");
+    }
+
+    #[test]
+    fn parsed_ids_are_a_single_normal_path_component() {
+        let root = Path::new("/library/projects");
+        let id = ProjectId::new();
+        let joined = root.join(&id);
+        assert_eq!(joined.parent(), Some(root));
+        assert!(matches!(
+            Path::new(id.as_str()).components().collect::<Vec<_>>()[..],
+            [Component::Normal(_)]
+        ));
+    }
+
+    #[test]
+    fn deserialization_validates() {
+        assert!(serde_json::from_str::<ProjectId>("\"../..\"").is_err());
+        let id: DatasetId =
+            serde_json::from_str("\"6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11\"").unwrap();
+        assert_eq!(id.as_str(), "6f1c1b43-7c2e-4f43-9a43-0b7f8f7f2c11");
+    }
+}
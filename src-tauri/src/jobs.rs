@@ -0,0 +1,314 @@
+//! Long-running work that reports progress and can be cancelled.
+//!
+//! A job runs on a worker thread and stays in the `JobRegistry` until its
+//! result is collected. The work gets a `Progress` handle to report its
+//! phase and how many bytes and files are done, and to notice that it has
+//! been cancelled. Nothing here depends on Tauri: the app forwards updates
+//! to the frontend as events, and headless callers pass `Progress::none()`.
+
+use crate::error::{ReciprocalError, ResourceKind, Result};
+use crate::ids::JobId;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Byte counts change constantly; listeners hear about them at most this
+/// often. Phase, file and state changes are always reported.
+const NOTIFY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Finished jobs whose results were never collected are dropped once there
+/// are more than this many.
+const MAX_FINISHED_JOBS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    ImportBandStructure,
+    ImportFermiSurface,
+    LoadBandStructure,
+    LoadFermiSurface,
+    ExportFermiSurface,
+    ExportArchive,
+    ImportArchive,
+    VerifyLibrary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobProgress {
+    /// What the job is doing right now, e.g. "Copying files".
+    pub phase: String,
+    pub bytes_done: u64,
+    pub bytes_total: Option<u64>,
+    pub files_done: usize,
+    pub files_total: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    /// What the job is working on, for display.
+    pub label: String,
+    pub state: JobState,
+    pub progress: JobProgress,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+type Notify = Box<dyn Fn(&Job) + Send + Sync>;
+
+struct Shared {
+    job: Mutex<Job>,
+    cancelled: AtomicBool,
+    notify: Notify,
+    last_notified: Mutex<Instant>,
+    /// The serialized result or the error, until it is collected.
+    outcome: Mutex<Option<Result<Value>>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Shared {
+    fn snapshot(&self) -> Job {
+        lock(&self.job).clone()
+    }
+
+    fn is_running(&self) -> bool {
+        lock(&self.job).state == JobState::Running
+    }
+
+    /// Applies `change` to the job and tells the listener, unless only the
+    /// byte count moved and the listener heard about it very recently.
+    fn update(&self, always: bool, change: impl FnOnce(&mut Job)) {
+        let job = {
+            let mut job = lock(&self.job);
+            change(&mut job);
+            job.clone()
+        };
+        let mut last_notified = lock(&self.last_notified);
+        if always || last_notified.elapsed() >= NOTIFY_INTERVAL {
+            *last_notified = Instant::now();
+            (self.notify)(&job);
+        }
+    }
+}
+
+/// Handle through which a job's work reports progress. Cheap to clone.
+#[derive(Clone, Default)]
+pub struct Progress {
+    shared: Option<Arc<Shared>>,
+}
+
+impl Progress {
+    /// A handle that reports nowhere and is never cancelled, for work run
+    /// outside a job.
+    pub fn none() -> Self {
+        Progress::default()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared
+            .as_ref()
+            .is_some_and(|shared| shared.cancelled.load(Ordering::Relaxed))
+    }
+
+    /// Fails with `Cancelled` once the job has been cancelled. Work calls
+    /// this between steps.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(ReciprocalError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Starts a new phase, resetting the byte and file counts.
+    pub fn phase(&self, phase: &str, bytes_total: Option<u64>, files_total: Option<usize>) {
+        if let Some(shared) = &self.shared {
+            shared.update(true, |job| {
+                job.progress = JobProgress {
+                    phase: phase.to_string(),
+                    bytes_done: 0,
+                    bytes_total,
+                    files_done: 0,
+                    files_total,
+                }
+            });
+        }
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        if let Some(shared) = &self.shared {
+            shared.update(false, |job| job.progress.bytes_done += bytes);
+        }
+    }
+
+    pub fn file_done(&self) {
+        if let Some(shared) = &self.shared {
+            shared.update(true, |job| job.progress.files_done += 1);
+        }
+    }
+
+    /// Wraps `inner` so that reading from it counts towards `bytes_done`
+    /// and fails once the job is cancelled.
+    pub fn reader<R: Read>(&self, inner: R) -> ProgressReader<'_, R> {
+        ProgressReader {
+            inner,
+            progress: self,
+        }
+    }
+
+    /// Records the outcome of the job's work and tells the listener. A job
+    /// that fails after being cancelled counts as cancelled, since the
+    /// error is usually just how the work noticed.
+    pub fn finish<T: Serialize>(&self, result: Result<T>) {
+        let Some(shared) = &self.shared else {
+            return;
+        };
+        let result = result.and_then(|value| {
+            serde_json::to_value(value).map_err(|e| {
+                ReciprocalError::internal(format!("Failed to serialize job result: {}", e))
+            })
+        });
+        let state = match &result {
+            Ok(_) => JobState::Completed,
+            Err(_) if self.is_cancelled() => JobState::Cancelled,
+            Err(_) => JobState::Failed,
+        };
+        *lock(&shared.outcome) = Some(match state {
+            JobState::Cancelled => Err(ReciprocalError::Cancelled),
+            _ => result,
+        });
+        shared.update(true, |job| {
+            job.state = state;
+            job.finished_at = Some(Utc::now());
+        });
+    }
+}
+
+/// Reader returned by `Progress::reader`.
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a Progress,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.progress.is_cancelled() {
+            return Err(io::Error::other("Cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        self.progress.add_bytes(n as u64);
+        Ok(n)
+    }
+}
+
+/// The jobs started in this process, oldest first.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<Vec<Arc<Shared>>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        JobRegistry::default()
+    }
+
+    /// Registers a running job and returns it with the handle its work
+    /// reports through. `notify` is called with the job on every update.
+    pub fn start(
+        &self,
+        kind: JobKind,
+        label: impl Into<String>,
+        notify: impl Fn(&Job) + Send + Sync + 'static,
+    ) -> (Job, Progress) {
+        let job = Job {
+            id: JobId::new(),
+            kind,
+            label: label.into(),
+            state: JobState::Running,
+            progress: JobProgress::default(),
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        let shared = Arc::new(Shared {
+            job: Mutex::new(job.clone()),
+            cancelled: AtomicBool::new(false),
+            notify: Box::new(notify),
+            last_notified: Mutex::new(Instant::now()),
+            outcome: Mutex::new(None),
+        });
+
+        let mut jobs = lock(&self.jobs);
+        let finished = jobs.iter().filter(|shared| !shared.is_running()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS - 1);
+        jobs.retain(|shared| {
+            let drop = excess > 0 && !shared.is_running();
+            if drop {
+                excess -= 1;
+            }
+            !drop
+        });
+        jobs.push(shared.clone());
+
+        (
+            job,
+            Progress {
+                shared: Some(shared),
+            },
+        )
+    }
+
+    fn find(&self, id: &JobId) -> Result<Arc<Shared>> {
+        lock(&self.jobs)
+            .iter()
+            .find(|shared| lock(&shared.job).id == *id)
+            .cloned()
+            .ok_or_else(|| ReciprocalError::not_found(ResourceKind::Job, id.clone()))
+    }
+
+    /// Every job whose result hasn't been collected yet, oldest first.
+    pub fn list(&self) -> Vec<Job> {
+        lock(&self.jobs)
+            .iter()
+            .map(|shared| shared.snapshot())
+            .collect()
+    }
+
+    /// Asks a running job to stop. The work notices at its next step, so
+    /// the job is still running when this returns.
+    pub fn cancel(&self, id: &JobId) -> Result<Job> {
+        let shared = self.find(id)?;
+        shared.cancelled.store(true, Ordering::Relaxed);
+        Ok(shared.snapshot())
+    }
+
+    /// Removes a finished job and returns its serialized result, or the
+    /// error it failed with.
+    pub fn take_result(&self, id: &JobId) -> Result<Value> {
+        let shared = self.find(id)?;
+        if shared.is_running() {
+            return Err(ReciprocalError::internal(format!(
+                "Job {} is still running",
+                id
+            )));
+        }
+        lock(&self.jobs).retain(|other| !Arc::ptr_eq(other, &shared));
+        let outcome = lock(&shared.outcome).take();
+        outcome.unwrap_or_else(|| Err(ReciprocalError::not_found(ResourceKind::Job, id.clone())))
+    }
+}
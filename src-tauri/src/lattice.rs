@@ -0,0 +1,125 @@
+//! Unit cell geometry: lattice parameters, lattice vectors and distances.
+
+/// Ångström per bohr radius, the length unit of Wien2k files.
+pub const BOHR_IN_ANGSTROM: f64 = 0.529_177_210_903;
+
+/// Lattice parameters, lengths in whatever unit the caller uses and
+/// angles in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub alpha: f64,
+    pub beta: f64,
+    pub gamma: f64,
+}
+
+impl Cell {
+    /// Lattice vectors as rows, with a along x and b in the xy plane.
+    pub fn vectors(&self) -> [[f64; 3]; 3] {
+        let [alpha, beta, gamma] = [self.alpha, self.beta, self.gamma].map(f64::to_radians);
+        let cx = self.c * beta.cos();
+        let cy = self.c * (alpha.cos() - beta.cos() * gamma.cos()) / gamma.sin();
+        let cz = (self.c * self.c - cx * cx - cy * cy).max(0.0).sqrt();
+        [
+            [self.a, 0.0, 0.0],
+            [self.b * gamma.cos(), self.b * gamma.sin(), 0.0],
+            [cx, cy, cz],
+        ]
+    }
+
+    /// Lattice parameters of the cell spanned by `vectors` (rows).
+    pub fn from_vectors(vectors: &[[f64; 3]; 3]) -> Self {
+        let length = |v: &[f64; 3]| dot(v, v).sqrt();
+        let angle = |u: &[f64; 3], v: &[f64; 3]| {
+            (dot(u, v) / (length(u) * length(v)))
+                .clamp(-1.0, 1.0)
+                .acos()
+                .to_degrees()
+        };
+        let [a, b, c] = vectors;
+        Cell {
+            a: length(a),
+            b: length(b),
+            c: length(c),
+            alpha: angle(b, c),
+            beta: angle(a, c),
+            gamma: angle(a, b),
+        }
+    }
+
+    pub fn volume(&self) -> f64 {
+        let [a, b, c] = self.vectors();
+        dot(&a, &cross(&b, &c)).abs()
+    }
+
+    pub fn scaled(&self, factor: f64) -> Self {
+        Cell {
+            a: self.a * factor,
+            b: self.b * factor,
+            c: self.c * factor,
+            ..*self
+        }
+    }
+
+    /// Cartesian position of fractional coordinates `frac`.
+    pub fn cartesian(&self, frac: [f64; 3]) -> [f64; 3] {
+        let v = self.vectors();
+        std::array::from_fn(|k| (0..3).map(|i| frac[i] * v[i][k]).sum())
+    }
+
+    /// Shortest distance between two fractional positions, over the
+    /// neighbouring periodic images.
+    pub fn distance(&self, p: [f64; 3], q: [f64; 3]) -> f64 {
+        let mut shortest = f64::INFINITY;
+        for i in -1..=1 {
+            for j in -1..=1 {
+                for k in -1..=1 {
+                    let image = [q[0] + i as f64, q[1] + j as f64, q[2] + k as f64];
+                    let d: [f64; 3] = std::array::from_fn(|n| p[n] - image[n]);
+                    let r = self.cartesian(d);
+                    shortest = shortest.min(dot(&r, &r).sqrt());
+                }
+            }
+        }
+        shortest
+    }
+}
+
+pub fn dot(u: &[f64; 3], v: &[f64; 3]) -> f64 {
+    u[0] * v[0] + u[1] * v[1] + u[2] * v[2]
+}
+
+pub fn cross(u: &[f64; 3], v: &[f64; 3]) -> [f64; 3] {
+    [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ]
+}
+
+/// Inverse of a 3×3 matrix, if it has one.
+pub fn invert(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let [a, b, c] = m;
+    let det = dot(a, &cross(b, c));
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    // Columns of the inverse are the cross products of the rows.
+    let columns = [cross(b, c), cross(c, a), cross(a, b)];
+    Some(std::array::from_fn(|i| {
+        std::array::from_fn(|j| columns[j][i] / det)
+    }))
+}
+
+/// Fractional coordinate wrapped into [0, 1), with values a hair below 1
+/// (from rounding in the file) taken as 0.
+pub fn wrap(x: f64) -> f64 {
+    let wrapped = x.rem_euclid(1.0);
+    if 1.0 - wrapped < 1e-6 {
+        0.0
+    } else {
+        wrapped
+    }
+}
@@ -1,634 +1,786 @@
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
-use tauri::Manager;
-use uuid::Uuid;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Project {
-    pub id: String,
-    pub name: String,
-    pub formula: String,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
-    #[serde(default)]
-    pub last_opened_at: Option<DateTime<Utc>>,
-    #[serde(default)]
-    pub has_cif: bool,
-    pub cif_filename: Option<String>,
-}
-
-fn get_projects_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    let app_data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    let projects_dir = app_data_dir.join("projects");
-
-    if !projects_dir.exists() {
-        fs::create_dir_all(&projects_dir)
-            .map_err(|e| format!("Failed to create projects directory: {}", e))?;
-    }
-
-    Ok(projects_dir)
+pub mod archive;
+pub mod atomic;
+pub mod band_grid;
+pub mod blobs;
+pub mod bxsf;
+pub mod checksum;
+pub mod cif;
+pub mod elements;
+pub mod error;
+pub mod frmsf;
+pub mod ids;
+pub mod jobs;
+pub mod lattice;
+pub mod links;
+pub mod listing;
+pub mod migrations;
+pub mod poscar;
+pub mod provenance;
+pub mod settings;
+pub mod store;
+pub mod structure_file;
+pub mod symmetry;
+pub mod trash;
+pub mod wien2k;
+
+pub use store::{
+    BandPathPoint, BandStructureData, BandStructureInfo, Eigenvalues, FermiSurfaceFiles,
+    FermiSurfaceInfo, FermiSurfaceKind, ImportOptions, Project, ProjectStore, RepairReport, Spin,
+    StructureInfo,
+};
+
+use band_grid::{GridColour, GridFormat};
+use cif::{BlockSummary, CrystalData};
+use error::{ReciprocalError, Result};
+use ids::{DatasetId, JobId, ProjectId, TrashId};
+use jobs::{Job, JobKind, JobRegistry, Progress};
+use links::LinkCheck;
+use listing::Listing;
+use provenance::{Parameters, Provenance};
+use serde::Serialize;
+use settings::LibrarySettings;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager, State};
+use trash::TrashEntry;
+use wien2k::Wien2kStruct;
+
+/// Event carrying a `Job` whenever its state or progress changes.
+const JOB_EVENT: &str = "job-progress";
+
+/// Runs `work` on a worker thread as a job whose updates are emitted as
+/// `JOB_EVENT`, and returns the job straight away.
+fn spawn_job<T: Serialize>(
+    app: &AppHandle,
+    jobs: &JobRegistry,
+    kind: JobKind,
+    label: impl Into<String>,
+    work: impl FnOnce(&Progress) -> Result<T> + Send + 'static,
+) -> Job {
+    let emitter = app.clone();
+    let (job, progress) = jobs.start(kind, label, move |job| {
+        let _ = emitter.emit(JOB_EVENT, job);
+    });
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = work(&progress);
+        progress.finish(result);
+    });
+    job
 }
 
-fn get_project_dir(app: &tauri::AppHandle, project_id: &str) -> Result<PathBuf, String> {
-    let projects_dir = get_projects_dir(app)?;
-    let project_dir = projects_dir.join(project_id);
-    if !project_dir.exists() {
-        return Err(format!("Project with id {} not found", project_id));
-    }
-    Ok(project_dir)
+#[tauri::command]
+fn load_projects(store: State<'_, ProjectStore>) -> Result<Listing<Project>> {
+    store.load_projects()
 }
 
 #[tauri::command]
-fn load_projects(app: tauri::AppHandle) -> Result<Vec<Project>, String> {
-    let projects_dir = get_projects_dir(&app)?;
-    let mut projects = Vec::new();
-
-    let entries = fs::read_dir(&projects_dir)
-        .map_err(|e| format!("Failed to read projects directory: {}", e))?;
-
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-        let path = entry.path();
-
-        // Each project is a directory containing project.json
-        if path.is_dir() {
-            let project_file = path.join("project.json");
-            if project_file.exists() {
-                let content = fs::read_to_string(&project_file)
-                    .map_err(|e| format!("Failed to read project file: {}", e))?;
-
-                let project: Project = serde_json::from_str(&content)
-                    .map_err(|e| format!("Failed to parse project file: {}", e))?;
-
-                projects.push(project);
-            }
-        }
-    }
-
-    // Sort by last_opened_at descending (most recently opened first), fall back to created_at
-    projects.sort_by(|a, b| {
-        let a_time = a.last_opened_at.unwrap_or(a.created_at);
-        let b_time = b.last_opened_at.unwrap_or(b.created_at);
-        b_time.cmp(&a_time)
-    });
-
-    Ok(projects)
+fn create_project(
+    store: State<'_, ProjectStore>,
+    name: String,
+    formula: String,
+) -> Result<Project> {
+    store.create_project(name, formula)
 }
 
+/// Creates a project from a CIF, Wien2k case.struct or POSCAR file.
 #[tauri::command]
-fn create_project(app: tauri::AppHandle, name: String, formula: String) -> Result<Project, String> {
-    let projects_dir = get_projects_dir(&app)?;
-
-    let now = Utc::now();
-    let project = Project {
-        id: Uuid::new_v4().to_string(),
-        name,
-        formula,
-        created_at: now,
-        updated_at: now,
-        last_opened_at: Some(now),
-        has_cif: false,
-        cif_filename: None,
-    };
-
-    // Create project directory
-    let project_dir = projects_dir.join(&project.id);
-    fs::create_dir_all(&project_dir)
-        .map_err(|e| format!("Failed to create project directory: {}", e))?;
-
-    // Save project.json inside the directory
-    let project_file = project_dir.join("project.json");
-    let content = serde_json::to_string_pretty(&project)
-        .map_err(|e| format!("Failed to serialize project: {}", e))?;
-
-    fs::write(&project_file, content)
-        .map_err(|e| format!("Failed to write project file: {}", e))?;
-
-    Ok(project)
+fn create_project_from_structure(store: State<'_, ProjectStore>, path: String) -> Result<Project> {
+    store.create_project_from_structure(Path::new(&path))
 }
 
 #[tauri::command]
-fn update_project(app: tauri::AppHandle, project: Project) -> Result<Project, String> {
-    let projects_dir = get_projects_dir(&app)?;
-    let project_dir = projects_dir.join(&project.id);
-    let project_file = project_dir.join("project.json");
-
-    if !project_file.exists() {
-        return Err(format!("Project with id {} not found", project.id));
-    }
-
-    let mut updated_project = project;
-    updated_project.updated_at = Utc::now();
-
-    let content = serde_json::to_string_pretty(&updated_project)
-        .map_err(|e| format!("Failed to serialize project: {}", e))?;
-
-    fs::write(&project_file, content)
-        .map_err(|e| format!("Failed to write project file: {}", e))?;
-
-    Ok(updated_project)
+fn update_project(store: State<'_, ProjectStore>, project: Project) -> Result<Project> {
+    store.update_project(project)
 }
 
 #[tauri::command]
-fn mark_project_opened(app: tauri::AppHandle, project_id: String) -> Result<Project, String> {
-    let projects_dir = get_projects_dir(&app)?;
-    let project_dir = projects_dir.join(&project_id);
-    let project_file = project_dir.join("project.json");
-
-    if !project_file.exists() {
-        return Err(format!("Project with id {} not found", project_id));
-    }
-
-    // Read existing project
-    let content = fs::read_to_string(&project_file)
-        .map_err(|e| format!("Failed to read project file: {}", e))?;
-    let mut project: Project = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse project file: {}", e))?;
-
-    // Update last_opened_at
-    project.last_opened_at = Some(Utc::now());
-
-    // Save updated project
-    let updated_content = serde_json::to_string_pretty(&project)
-        .map_err(|e| format!("Failed to serialize project: {}", e))?;
-    fs::write(&project_file, updated_content)
-        .map_err(|e| format!("Failed to write project file: {}", e))?;
-
-    Ok(project)
+fn mark_project_opened(store: State<'_, ProjectStore>, project_id: String) -> Result<Project> {
+    store.mark_project_opened(&ProjectId::parse(&project_id)?)
 }
 
 #[tauri::command]
-fn delete_project(app: tauri::AppHandle, id: String) -> Result<(), String> {
-    let projects_dir = get_projects_dir(&app)?;
-    let project_dir = projects_dir.join(&id);
-
-    if !project_dir.exists() {
-        return Err(format!("Project with id {} not found", id));
-    }
-
-    fs::remove_dir_all(&project_dir)
-        .map_err(|e| format!("Failed to delete project directory: {}", e))?;
-
-    Ok(())
+fn delete_project(store: State<'_, ProjectStore>, id: String) -> Result<TrashEntry> {
+    store.delete_project(&ProjectId::parse(&id)?)
 }
 
+/// Imports a CIF file. `blocks` names the data blocks to import, the first
+/// as the project's structure; without it the whole file is imported.
 #[tauri::command]
 fn import_cif_file(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
     source_path: String,
     original_filename: String,
-) -> Result<Project, String> {
-    let project_dir = get_project_dir(&app, &project_id)?;
-    let project_file = project_dir.join("project.json");
-
-    // Read existing project
-    let content = fs::read_to_string(&project_file)
-        .map_err(|e| format!("Failed to read project file: {}", e))?;
-    let mut project: Project = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse project file: {}", e))?;
-
-    // Copy CIF file to project directory as structure.cif
-    let dest_path = project_dir.join("structure.cif");
-    fs::copy(&source_path, &dest_path)
-        .map_err(|e| format!("Failed to copy CIF file: {}", e))?;
-
-    // Update project metadata
-    project.has_cif = true;
-    project.cif_filename = Some(original_filename);
-    project.updated_at = Utc::now();
-
-    // Save updated project
-    let updated_content = serde_json::to_string_pretty(&project)
-        .map_err(|e| format!("Failed to serialize project: {}", e))?;
-    fs::write(&project_file, updated_content)
-        .map_err(|e| format!("Failed to write project file: {}", e))?;
-
-    Ok(project)
+    blocks: Option<Vec<String>>,
+) -> Result<Project> {
+    store.import_cif_file(
+        &ProjectId::parse(&project_id)?,
+        Path::new(&source_path),
+        original_filename,
+        &blocks.unwrap_or_default(),
+    )
 }
 
 #[tauri::command]
-fn read_cif_file(app: tauri::AppHandle, project_id: String) -> Result<String, String> {
-    let project_dir = get_project_dir(&app, &project_id)?;
-    let cif_path = project_dir.join("structure.cif");
+fn import_poscar(
+    store: State<'_, ProjectStore>,
+    project_id: String,
+    source_path: String,
+    original_filename: String,
+) -> Result<Project> {
+    store.import_poscar(
+        &ProjectId::parse(&project_id)?,
+        Path::new(&source_path),
+        original_filename,
+    )
+}
 
-    if !cif_path.exists() {
-        return Err("CIF file not found".to_string());
-    }
+#[tauri::command]
+fn export_poscar(store: State<'_, ProjectStore>, project_id: String, dest: String) -> Result<()> {
+    store.export_poscar(&ProjectId::parse(&project_id)?, Path::new(&dest))
+}
 
-    fs::read_to_string(&cif_path)
-        .map_err(|e| format!("Failed to read CIF file: {}", e))
+#[tauri::command]
+fn list_cif_blocks(path: String) -> Result<Vec<BlockSummary>> {
+    cif::read_blocks(Path::new(&path))
 }
 
 #[tauri::command]
-fn save_crystal_data(
-    app: tauri::AppHandle,
+fn list_structures(
+    store: State<'_, ProjectStore>,
     project_id: String,
-    crystal_data_json: String,
-) -> Result<(), String> {
-    let project_dir = get_project_dir(&app, &project_id)?;
-    let data_path = project_dir.join("cif_data.json");
+) -> Result<Listing<StructureInfo>> {
+    store.list_structures(&ProjectId::parse(&project_id)?)
+}
 
-    fs::write(&data_path, crystal_data_json)
-        .map_err(|e| format!("Failed to save crystal data: {}", e))?;
+#[tauri::command]
+fn load_structure(
+    store: State<'_, ProjectStore>,
+    project_id: String,
+    structure_id: String,
+) -> Result<CrystalData> {
+    store.load_structure(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&structure_id)?,
+    )
+}
 
-    Ok(())
+#[tauri::command]
+fn delete_structure(
+    store: State<'_, ProjectStore>,
+    project_id: String,
+    structure_id: String,
+) -> Result<TrashEntry> {
+    store.delete_structure(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&structure_id)?,
+    )
 }
 
 #[tauri::command]
-fn load_crystal_data(app: tauri::AppHandle, project_id: String) -> Result<Option<String>, String> {
-    let project_dir = get_project_dir(&app, &project_id)?;
-    let data_path = project_dir.join("cif_data.json");
+fn read_cif_file(store: State<'_, ProjectStore>, project_id: String) -> Result<String> {
+    store.read_cif_file(&ProjectId::parse(&project_id)?)
+}
 
-    if !data_path.exists() {
-        return Ok(None);
+/// Parses a project's structure.cif, or the CIF file at `path` if given.
+#[tauri::command]
+fn parse_cif(
+    store: State<'_, ProjectStore>,
+    project_id: Option<String>,
+    path: Option<String>,
+) -> Result<CrystalData> {
+    match (path, project_id) {
+        (Some(path), _) => cif::read_crystal_data(Path::new(&path)),
+        (None, Some(project_id)) => store.parse_cif_file(&ProjectId::parse(&project_id)?),
+        (None, None) => Err(ReciprocalError::internal(
+            "parse_cif needs a project or a path",
+        )),
     }
+}
 
-    let content = fs::read_to_string(&data_path)
-        .map_err(|e| format!("Failed to read crystal data: {}", e))?;
+#[tauri::command]
+fn export_cif(store: State<'_, ProjectStore>, project_id: String, dest: String) -> Result<()> {
+    store.export_cif(&ProjectId::parse(&project_id)?, Path::new(&dest))
+}
 
-    Ok(Some(content))
+#[tauri::command]
+fn parse_struct(path: String) -> Result<Wien2kStruct> {
+    wien2k::read_struct(Path::new(&path))
 }
 
-// ============ Band Structure Commands ============
+/// Writes a Wien2k case.struct generated from the project's structure.
+#[tauri::command]
+fn export_struct(store: State<'_, ProjectStore>, project_id: String, dest: String) -> Result<()> {
+    store.export_struct(&ProjectId::parse(&project_id)?, Path::new(&dest))
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BandStructureInfo {
-    pub id: String,
-    pub name: String,
-    pub created_at: DateTime<Utc>,
-    pub qtl_filename: String,
-    pub klist_filename: String,
+#[tauri::command]
+fn save_crystal_data(
+    store: State<'_, ProjectStore>,
+    project_id: String,
+    crystal_data_json: String,
+) -> Result<()> {
+    store.save_crystal_data(&ProjectId::parse(&project_id)?, &crystal_data_json)
 }
 
-fn get_band_structures_dir(app: &tauri::AppHandle, project_id: &str) -> Result<PathBuf, String> {
-    let project_dir = get_project_dir(app, project_id)?;
-    let band_dir = project_dir.join("band_structures");
+#[tauri::command]
+fn load_crystal_data(store: State<'_, ProjectStore>, project_id: String) -> Result<Option<String>> {
+    store.load_crystal_data(&ProjectId::parse(&project_id)?)
+}
 
-    if !band_dir.exists() {
-        fs::create_dir_all(&band_dir)
-            .map_err(|e| format!("Failed to create band_structures directory: {}", e))?;
-    }
+/// Starts a job whose result is the `ArchiveManifest`.
+#[tauri::command]
+fn export_project_archive(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+    project_id: String,
+    dest: String,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::ExportArchive,
+        dest.clone(),
+        move |progress| store.export_project_archive(&project_id, Path::new(&dest), progress),
+    ))
+}
 
-    Ok(band_dir)
+/// Starts a job whose result is the imported `Project`.
+#[tauri::command]
+fn import_project_archive(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+    path: String,
+) -> Result<Job> {
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::ImportArchive,
+        path.clone(),
+        move |progress| store.import_project_archive(Path::new(&path), progress),
+    ))
 }
 
+// ============ Band Structure Commands ============
+
+/// Starts a job whose result is the new `BandStructureInfo`.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 fn import_band_structure(
-    app: tauri::AppHandle,
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
     project_id: String,
     name: String,
     qtl_source_path: String,
     qtl_filename: String,
     klist_source_path: String,
     klist_filename: String,
-) -> Result<BandStructureInfo, String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-
-    let id = Uuid::new_v4().to_string();
-    let band_path = band_dir.join(&id);
-    fs::create_dir_all(&band_path)
-        .map_err(|e| format!("Failed to create band structure directory: {}", e))?;
-
-    // Copy .qtl file
-    let qtl_dest = band_path.join("data.qtl");
-    fs::copy(&qtl_source_path, &qtl_dest)
-        .map_err(|e| format!("Failed to copy QTL file: {}", e))?;
-
-    // Copy .klist_band file
-    let klist_dest = band_path.join("data.klist_band");
-    fs::copy(&klist_source_path, &klist_dest)
-        .map_err(|e| format!("Failed to copy klist_band file: {}", e))?;
-
-    let info = BandStructureInfo {
-        id,
-        name,
-        created_at: Utc::now(),
-        qtl_filename,
-        klist_filename,
-    };
-
-    // Save metadata
-    let info_path = band_path.join("info.json");
-    let content = serde_json::to_string_pretty(&info)
-        .map_err(|e| format!("Failed to serialize band structure info: {}", e))?;
-    fs::write(&info_path, content)
-        .map_err(|e| format!("Failed to write band structure info: {}", e))?;
+    parameters: Option<Parameters>,
+    link: Option<bool>,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::ImportBandStructure,
+        name.clone(),
+        move |progress| {
+            store.import_band_structure(
+                &project_id,
+                name,
+                Path::new(&qtl_source_path),
+                qtl_filename,
+                Path::new(&klist_source_path),
+                klist_filename,
+                ImportOptions {
+                    parameters: parameters.unwrap_or_default(),
+                    link: link.unwrap_or(false),
+                },
+                progress,
+            )
+        },
+    ))
+}
 
-    Ok(info)
+/// Starts a job whose result is the new `BandStructureInfo`, holding the
+/// up and down channels of a spin-polarized run.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn import_spin_band_structure(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+    project_id: String,
+    name: String,
+    qtl_up_source_path: String,
+    qtl_up_filename: String,
+    qtl_dn_source_path: String,
+    qtl_dn_filename: String,
+    klist_source_path: String,
+    klist_filename: String,
+    parameters: Option<Parameters>,
+    link: Option<bool>,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::ImportBandStructure,
+        name.clone(),
+        move |progress| {
+            store.import_spin_band_structure(
+                &project_id,
+                name,
+                Path::new(&qtl_up_source_path),
+                qtl_up_filename,
+                Path::new(&qtl_dn_source_path),
+                qtl_dn_filename,
+                Path::new(&klist_source_path),
+                klist_filename,
+                ImportOptions {
+                    parameters: parameters.unwrap_or_default(),
+                    link: link.unwrap_or(false),
+                },
+                progress,
+            )
+        },
+    ))
 }
 
 #[tauri::command]
 fn list_band_structures(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
-) -> Result<Vec<BandStructureInfo>, String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-    let mut results = Vec::new();
-
-    if let Ok(entries) = fs::read_dir(&band_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                let info_path = path.join("info.json");
-                if info_path.exists() {
-                    if let Ok(content) = fs::read_to_string(&info_path) {
-                        if let Ok(info) = serde_json::from_str::<BandStructureInfo>(&content) {
-                            results.push(info);
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Sort by created_at descending
-    results.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-
-    Ok(results)
+) -> Result<Listing<BandStructureInfo>> {
+    store.list_band_structures(&ProjectId::parse(&project_id)?)
 }
 
+/// Starts a job whose result is the parsed `BandStructureData`.
 #[tauri::command]
-fn load_band_structure_files(
-    app: tauri::AppHandle,
+fn load_band_structure_data(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
     project_id: String,
     band_structure_id: String,
-) -> Result<(String, String), String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-    let band_path = band_dir.join(&band_structure_id);
-
-    if !band_path.exists() {
-        return Err(format!("Band structure {} not found", band_structure_id));
-    }
-
-    let qtl_content = fs::read_to_string(band_path.join("data.qtl"))
-        .map_err(|e| format!("Failed to read QTL file: {}", e))?;
-
-    let klist_content = fs::read_to_string(band_path.join("data.klist_band"))
-        .map_err(|e| format!("Failed to read klist_band file: {}", e))?;
-
-    Ok((qtl_content, klist_content))
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let band_structure_id = DatasetId::parse(&band_structure_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::LoadBandStructure,
+        band_structure_id.to_string(),
+        move |progress| store.load_band_structure_data(&project_id, &band_structure_id, progress),
+    ))
 }
 
 #[tauri::command]
 fn delete_band_structure(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
     band_structure_id: String,
-) -> Result<(), String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-    let band_path = band_dir.join(&band_structure_id);
-
-    if !band_path.exists() {
-        return Err(format!("Band structure {} not found", band_structure_id));
-    }
-
-    fs::remove_dir_all(&band_path)
-        .map_err(|e| format!("Failed to delete band structure: {}", e))?;
-
-    Ok(())
+) -> Result<TrashEntry> {
+    store.delete_band_structure(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&band_structure_id)?,
+    )
 }
 
 #[tauri::command]
 fn update_band_structure_labels(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
     band_structure_id: String,
     labels_json: String,
-) -> Result<(), String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-    let band_path = band_dir.join(&band_structure_id);
-
-    if !band_path.exists() {
-        return Err(format!("Band structure {} not found", band_structure_id));
-    }
-
-    let labels_path = band_path.join("labels.json");
-    fs::write(&labels_path, labels_json)
-        .map_err(|e| format!("Failed to save labels: {}", e))?;
-
-    Ok(())
+) -> Result<()> {
+    store.update_band_structure_labels(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&band_structure_id)?,
+        &labels_json,
+    )
 }
 
 #[tauri::command]
 fn load_band_structure_labels(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
     band_structure_id: String,
-) -> Result<Option<String>, String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-    let band_path = band_dir.join(&band_structure_id);
-    let labels_path = band_path.join("labels.json");
-
-    if !labels_path.exists() {
-        return Ok(None);
-    }
-
-    let content = fs::read_to_string(&labels_path)
-        .map_err(|e| format!("Failed to read labels: {}", e))?;
-
-    Ok(Some(content))
+) -> Result<Option<String>> {
+    store.load_band_structure_labels(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&band_structure_id)?,
+    )
 }
 
 #[tauri::command]
 fn update_band_structure_atom_names(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
     band_structure_id: String,
     atom_names_json: String,
-) -> Result<(), String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-    let band_path = band_dir.join(&band_structure_id);
-
-    if !band_path.exists() {
-        return Err(format!("Band structure {} not found", band_structure_id));
-    }
-
-    let names_path = band_path.join("atom_names.json");
-    fs::write(&names_path, atom_names_json)
-        .map_err(|e| format!("Failed to save atom names: {}", e))?;
-
-    Ok(())
+) -> Result<()> {
+    store.update_band_structure_atom_names(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&band_structure_id)?,
+        &atom_names_json,
+    )
 }
 
 #[tauri::command]
 fn load_band_structure_atom_names(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
     band_structure_id: String,
-) -> Result<Option<String>, String> {
-    let band_dir = get_band_structures_dir(&app, &project_id)?;
-    let band_path = band_dir.join(&band_structure_id);
-    let names_path = band_path.join("atom_names.json");
-
-    if !names_path.exists() {
-        return Ok(None);
-    }
-
-    let content = fs::read_to_string(&names_path)
-        .map_err(|e| format!("Failed to read atom names: {}", e))?;
-
-    Ok(Some(content))
+) -> Result<Option<String>> {
+    store.load_band_structure_atom_names(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&band_structure_id)?,
+    )
 }
 
 // ============ Fermi Surface Commands ============
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FermiSurfaceInfo {
-    pub id: String,
-    pub name: String,
-    pub created_at: DateTime<Utc>,
-    pub case_name: String,
-}
-
-fn get_fermi_surfaces_dir(app: &tauri::AppHandle, project_id: &str) -> Result<PathBuf, String> {
-    let project_dir = get_project_dir(app, project_id)?;
-    let fermi_dir = project_dir.join("fermi_surfaces");
-
-    if !fermi_dir.exists() {
-        fs::create_dir_all(&fermi_dir)
-            .map_err(|e| format!("Failed to create fermi_surfaces directory: {}", e))?;
-    }
-
-    Ok(fermi_dir)
-}
-
+/// Starts a job whose result is the new `FermiSurfaceInfo`. Eigenvalues
+/// come from `output1_source_path`, or else from `energy_source_paths`
+/// checked against `klist_source_path`. A single energy path also brings
+/// in the k-parallel splits beside it. For a spin-polarized run those are
+/// the up channel's, and the `_dn_` paths give the down channel's.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 fn import_fermi_surface(
-    app: tauri::AppHandle,
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
     project_id: String,
     name: String,
-    output1_source_path: String,
+    output1_source_path: Option<String>,
+    energy_source_paths: Option<Vec<String>>,
+    klist_source_path: Option<String>,
+    output1_dn_source_path: Option<String>,
+    energy_dn_source_paths: Option<Vec<String>>,
     output2_source_path: String,
     outputkgen_source_path: String,
     struct_source_path: String,
     case_name: String,
-) -> Result<FermiSurfaceInfo, String> {
-    let fermi_dir = get_fermi_surfaces_dir(&app, &project_id)?;
-
-    let id = Uuid::new_v4().to_string();
-    let fermi_path = fermi_dir.join(&id);
-    fs::create_dir_all(&fermi_path)
-        .map_err(|e| format!("Failed to create fermi surface directory: {}", e))?;
-
-    // Copy output1 file
-    let output1_dest = fermi_path.join("data.output1");
-    fs::copy(&output1_source_path, &output1_dest)
-        .map_err(|e| format!("Failed to copy output1 file: {}", e))?;
-
-    // Copy output2 file
-    let output2_dest = fermi_path.join("data.output2");
-    fs::copy(&output2_source_path, &output2_dest)
-        .map_err(|e| format!("Failed to copy output2 file: {}", e))?;
-
-    // Copy outputkgen file
-    let outputkgen_dest = fermi_path.join("data.outputkgen");
-    fs::copy(&outputkgen_source_path, &outputkgen_dest)
-        .map_err(|e| format!("Failed to copy outputkgen file: {}", e))?;
-
-    // Copy .struct file
-    let struct_dest = fermi_path.join("data.struct");
-    fs::copy(&struct_source_path, &struct_dest)
-        .map_err(|e| format!("Failed to copy struct file: {}", e))?;
-
-    let info = FermiSurfaceInfo {
-        id,
-        name,
-        created_at: Utc::now(),
-        case_name,
+    parameters: Option<Parameters>,
+    link: Option<bool>,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let energy_files = |paths: Option<Vec<String>>| -> Vec<PathBuf> {
+        match paths.as_deref() {
+            Some([path]) => wien2k::energy_splits(Path::new(path)),
+            Some(paths) => paths.iter().map(PathBuf::from).collect(),
+            None => Vec::new(),
+        }
     };
+    let energy = energy_files(energy_source_paths);
+    let energy_dn = energy_files(energy_dn_source_paths);
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::ImportFermiSurface,
+        name.clone(),
+        move |progress| {
+            let eigenvalues = match (&output1_source_path, &klist_source_path) {
+                (Some(output1), _) => Eigenvalues::Output1(Path::new(output1)),
+                (None, Some(klist)) if !energy.is_empty() => Eigenvalues::Energy {
+                    files: &energy,
+                    klist: Path::new(klist),
+                },
+                _ => {
+                    return Err(ReciprocalError::internal(
+                        "A Fermi surface needs an output1 file, or energy files and a klist",
+                    ))
+                }
+            };
+            // The down channel of a spin-polarized run, on the same klist.
+            let down = match (&output1_dn_source_path, &klist_source_path) {
+                (Some(output1), _) => Some(Eigenvalues::Output1(Path::new(output1))),
+                (None, Some(klist)) if !energy_dn.is_empty() => Some(Eigenvalues::Energy {
+                    files: &energy_dn,
+                    klist: Path::new(klist),
+                }),
+                _ => None,
+            };
+            store.import_fermi_surface(
+                &project_id,
+                name,
+                FermiSurfaceFiles {
+                    eigenvalues,
+                    down,
+                    output2: Path::new(&output2_source_path),
+                    outputkgen: Path::new(&outputkgen_source_path),
+                    struct_file: Path::new(&struct_source_path),
+                },
+                case_name,
+                ImportOptions {
+                    parameters: parameters.unwrap_or_default(),
+                    link: link.unwrap_or(false),
+                },
+                progress,
+            )
+        },
+    ))
+}
 
-    // Save metadata
-    let info_path = fermi_path.join("info.json");
-    let content = serde_json::to_string_pretty(&info)
-        .map_err(|e| format!("Failed to serialize fermi surface info: {}", e))?;
-    fs::write(&info_path, content)
-        .map_err(|e| format!("Failed to write fermi surface info: {}", e))?;
-
-    Ok(info)
+/// Starts a job whose result is the new `FermiSurfaceInfo`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn import_bxsf_fermi_surface(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+    project_id: String,
+    name: String,
+    bxsf_source_path: String,
+    parameters: Option<Parameters>,
+    link: Option<bool>,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::ImportFermiSurface,
+        name.clone(),
+        move |progress| {
+            store.import_bxsf_fermi_surface(
+                &project_id,
+                name,
+                Path::new(&bxsf_source_path),
+                ImportOptions {
+                    parameters: parameters.unwrap_or_default(),
+                    link: link.unwrap_or(false),
+                },
+                progress,
+            )
+        },
+    ))
 }
 
 #[tauri::command]
 fn list_fermi_surfaces(
-    app: tauri::AppHandle,
+    store: State<'_, ProjectStore>,
     project_id: String,
-) -> Result<Vec<FermiSurfaceInfo>, String> {
-    let fermi_dir = get_fermi_surfaces_dir(&app, &project_id)?;
-    let mut results = Vec::new();
-
-    if let Ok(entries) = fs::read_dir(&fermi_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                let info_path = path.join("info.json");
-                if info_path.exists() {
-                    if let Ok(content) = fs::read_to_string(&info_path) {
-                        if let Ok(info) = serde_json::from_str::<FermiSurfaceInfo>(&content) {
-                            results.push(info);
-                        }
-                    }
-                }
-            }
-        }
-    }
+) -> Result<Listing<FermiSurfaceInfo>> {
+    store.list_fermi_surfaces(&ProjectId::parse(&project_id)?)
+}
+
+/// Starts a job whose result is the (output1, output2, outputkgen, struct)
+/// contents.
+#[tauri::command]
+fn load_fermi_surface_files(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+    project_id: String,
+    fermi_surface_id: String,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let fermi_surface_id = DatasetId::parse(&fermi_surface_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::LoadFermiSurface,
+        fermi_surface_id.to_string(),
+        move |progress| store.load_fermi_surface_files(&project_id, &fermi_surface_id, progress),
+    ))
+}
 
-    // Sort by created_at descending
-    results.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+/// Starts a job whose result is the Fermi surface's `BandGrid`.
+#[tauri::command]
+fn load_fermi_surface_grid(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+    project_id: String,
+    fermi_surface_id: String,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let fermi_surface_id = DatasetId::parse(&fermi_surface_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::LoadFermiSurface,
+        fermi_surface_id.to_string(),
+        move |progress| store.load_fermi_surface_grid(&project_id, &fermi_surface_id, progress),
+    ))
+}
 
-    Ok(results)
+/// Starts a job that writes a Fermi surface's band grid to `dest` as BXSF
+/// or FermiSurfer. `bands` defaults to those crossing the Fermi energy.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn export_fermi_surface_grid(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+    project_id: String,
+    fermi_surface_id: String,
+    dest: String,
+    format: GridFormat,
+    bands: Option<Vec<usize>>,
+    colour: Option<GridColour>,
+) -> Result<Job> {
+    let project_id = ProjectId::parse(&project_id)?;
+    let fermi_surface_id = DatasetId::parse(&fermi_surface_id)?;
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::ExportFermiSurface,
+        dest.clone(),
+        move |progress| {
+            store.export_fermi_surface_grid(
+                &project_id,
+                &fermi_surface_id,
+                Path::new(&dest),
+                format,
+                bands,
+                colour,
+                progress,
+            )
+        },
+    ))
 }
 
 #[tauri::command]
-fn load_fermi_surface_files(
-    app: tauri::AppHandle,
+fn delete_fermi_surface(
+    store: State<'_, ProjectStore>,
     project_id: String,
     fermi_surface_id: String,
-) -> Result<(String, String, String, String), String> {
-    let fermi_dir = get_fermi_surfaces_dir(&app, &project_id)?;
-    let fermi_path = fermi_dir.join(&fermi_surface_id);
+) -> Result<TrashEntry> {
+    store.delete_fermi_surface(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&fermi_surface_id)?,
+    )
+}
 
-    if !fermi_path.exists() {
-        return Err(format!("Fermi surface {} not found", fermi_surface_id));
-    }
+#[tauri::command]
+fn get_dataset_provenance(
+    store: State<'_, ProjectStore>,
+    project_id: String,
+    dataset_id: String,
+) -> Result<Option<Provenance>> {
+    store.dataset_provenance(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&dataset_id)?,
+    )
+}
 
-    let output1_content = fs::read_to_string(fermi_path.join("data.output1"))
-        .map_err(|e| format!("Failed to read output1 file: {}", e))?;
+#[tauri::command]
+fn check_dataset_links(
+    store: State<'_, ProjectStore>,
+    project_id: String,
+    dataset_id: String,
+) -> Result<Vec<LinkCheck>> {
+    store.check_dataset_links(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&dataset_id)?,
+    )
+}
 
-    let output2_content = fs::read_to_string(fermi_path.join("data.output2"))
-        .map_err(|e| format!("Failed to read output2 file: {}", e))?;
+#[tauri::command]
+fn relink_dataset(
+    store: State<'_, ProjectStore>,
+    project_id: String,
+    dataset_id: String,
+    directory: String,
+    accept_changes: Option<bool>,
+) -> Result<Vec<LinkCheck>> {
+    store.relink_dataset(
+        &ProjectId::parse(&project_id)?,
+        &DatasetId::parse(&dataset_id)?,
+        Path::new(&directory),
+        accept_changes.unwrap_or(false),
+    )
+}
 
-    let outputkgen_content = fs::read_to_string(fermi_path.join("data.outputkgen"))
-        .map_err(|e| format!("Failed to read outputkgen file: {}", e))?;
+// ============ Trash ============
 
-    let struct_content = fs::read_to_string(fermi_path.join("data.struct"))
-        .map_err(|e| format!("Failed to read struct file: {}", e))?;
+#[tauri::command]
+fn list_trash(store: State<'_, ProjectStore>) -> Result<Listing<TrashEntry>> {
+    store.list_trash()
+}
 
-    Ok((output1_content, output2_content, outputkgen_content, struct_content))
+#[tauri::command]
+fn restore_from_trash(store: State<'_, ProjectStore>, trash_id: String) -> Result<TrashEntry> {
+    store.restore_from_trash(&TrashId::parse(&trash_id)?)
 }
 
 #[tauri::command]
-fn delete_fermi_surface(
-    app: tauri::AppHandle,
-    project_id: String,
-    fermi_surface_id: String,
-) -> Result<(), String> {
-    let fermi_dir = get_fermi_surfaces_dir(&app, &project_id)?;
-    let fermi_path = fermi_dir.join(&fermi_surface_id);
+fn empty_trash(store: State<'_, ProjectStore>) -> Result<usize> {
+    store.empty_trash()
+}
 
-    if !fermi_path.exists() {
-        return Err(format!("Fermi surface {} not found", fermi_surface_id));
-    }
+// ============ Jobs ============
+
+#[tauri::command]
+fn list_jobs(jobs: State<'_, JobRegistry>) -> Vec<Job> {
+    jobs.list()
+}
+
+#[tauri::command]
+fn cancel_job(jobs: State<'_, JobRegistry>, job_id: String) -> Result<Job> {
+    jobs.cancel(&JobId::parse(&job_id)?)
+}
+
+/// Collects a finished job's result, removing it from `list_jobs`. Fails
+/// with the job's own error if it failed or was cancelled.
+#[tauri::command]
+fn job_result(jobs: State<'_, JobRegistry>, job_id: String) -> Result<serde_json::Value> {
+    jobs.take_result(&JobId::parse(&job_id)?)
+}
 
-    fs::remove_dir_all(&fermi_path)
-        .map_err(|e| format!("Failed to delete fermi surface: {}", e))?;
+// ============ Settings ============
 
-    Ok(())
+#[tauri::command]
+fn get_settings(store: State<'_, ProjectStore>) -> Result<LibrarySettings> {
+    store.settings()
+}
+
+#[tauri::command]
+fn update_settings(
+    store: State<'_, ProjectStore>,
+    settings: LibrarySettings,
+) -> Result<LibrarySettings> {
+    store.update_settings(&settings)
+}
+
+// ============ Integrity ============
+
+/// Starts a job whose result is the `VerifyReport`.
+#[tauri::command]
+fn verify_library(
+    app: AppHandle,
+    store: State<'_, ProjectStore>,
+    jobs: State<'_, JobRegistry>,
+) -> Result<Job> {
+    let store = store.inner().clone();
+    Ok(spawn_job(
+        &app,
+        &jobs,
+        JobKind::VerifyLibrary,
+        store.root().display().to_string(),
+        move |progress| store.verify_library(progress),
+    ))
+}
+
+#[tauri::command]
+fn repair_project(store: State<'_, ProjectStore>, project_id: String) -> Result<RepairReport> {
+    store.repair_project(&ProjectId::parse(&project_id)?)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -637,28 +789,70 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .manage(JobRegistry::new())
+        .setup(|app| {
+            let app_data_dir = app.path().app_data_dir().map_err(|e| {
+                ReciprocalError::internal(format!("Failed to get app data directory: {}", e))
+            })?;
+            let store = ProjectStore::new(app_data_dir);
+            if let Err(e) = store.purge_trash() {
+                eprintln!("Failed to purge expired trash: {}", e);
+            }
+            app.manage(store);
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             load_projects,
             create_project,
+            create_project_from_structure,
             update_project,
             mark_project_opened,
             delete_project,
             import_cif_file,
+            import_poscar,
+            export_poscar,
+            list_cif_blocks,
+            list_structures,
+            load_structure,
+            delete_structure,
             read_cif_file,
+            parse_cif,
+            export_cif,
+            parse_struct,
+            export_struct,
             save_crystal_data,
             load_crystal_data,
+            export_project_archive,
+            import_project_archive,
             import_band_structure,
+            import_spin_band_structure,
             list_band_structures,
-            load_band_structure_files,
+            load_band_structure_data,
             delete_band_structure,
             update_band_structure_labels,
             load_band_structure_labels,
             update_band_structure_atom_names,
             load_band_structure_atom_names,
             import_fermi_surface,
+            import_bxsf_fermi_surface,
             list_fermi_surfaces,
             load_fermi_surface_files,
-            delete_fermi_surface
+            load_fermi_surface_grid,
+            export_fermi_surface_grid,
+            delete_fermi_surface,
+            get_dataset_provenance,
+            check_dataset_links,
+            relink_dataset,
+            repair_project,
+            verify_library,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            list_jobs,
+            cancel_job,
+            job_result,
+            get_settings,
+            update_settings
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,149 @@
+//! Dataset files referenced in place instead of copied into the library.
+//!
+//! Dense Wien2k meshes produce multi-gigabyte files that usually already sit
+//! on a shared file server, so a dataset can record their absolute paths in
+//! a `linked` map instead. Each link keeps the size, mtime and SHA-256 seen
+//! when it was made; a file is only re-hashed when its size or mtime no
+//! longer match. Archives carry linked paths, not contents.
+
+use crate::atomic;
+use crate::blobs::BlobRef;
+use crate::checksum;
+use crate::error::{IoOp, ReciprocalError, Result};
+use crate::jobs::Progress;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkedFile {
+    /// Absolute path of the file.
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified_at: Option<DateTime<Utc>>,
+    pub sha256: String,
+}
+
+/// Stored file name (e.g. `data.output1`) to linked file, as kept in
+/// dataset metadata.
+pub type LinkedFiles = BTreeMap<String, LinkedFile>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStatus {
+    Ok,
+    Missing,
+    /// The file's contents differ from when it was linked.
+    Changed,
+}
+
+/// Result of checking, or relinking, one linked file.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkCheck {
+    pub name: String,
+    pub path: PathBuf,
+    pub status: LinkStatus,
+}
+
+/// What `LinkedFile::check` found on disk.
+#[derive(Debug, Clone)]
+pub enum LinkState {
+    Unchanged,
+    /// Same contents under a new mtime; the record should be replaced with
+    /// this one so the file isn't re-hashed next time.
+    Touched(LinkedFile),
+    Missing,
+    Changed,
+}
+
+impl LinkState {
+    pub fn status(&self) -> LinkStatus {
+        match self {
+            LinkState::Unchanged | LinkState::Touched(_) => LinkStatus::Ok,
+            LinkState::Missing => LinkStatus::Missing,
+            LinkState::Changed => LinkStatus::Changed,
+        }
+    }
+}
+
+fn modified_at(metadata: &fs::Metadata) -> Option<DateTime<Utc>> {
+    metadata.modified().ok().map(DateTime::<Utc>::from)
+}
+
+impl LinkedFile {
+    /// Records the file at `path` as it is now.
+    pub fn capture(path: &Path, progress: &Progress) -> Result<Self> {
+        let path = fs::canonicalize(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+        let metadata = fs::metadata(&path).map_err(ReciprocalError::io(IoOp::Read, &path))?;
+        let (sha256, size) = checksum::sha256_file_with_progress(&path, progress)?;
+        Ok(LinkedFile {
+            path,
+            size,
+            modified_at: modified_at(&metadata),
+            sha256,
+        })
+    }
+
+    /// Compares the file on disk with this record.
+    pub fn check(&self) -> Result<LinkState> {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Ok(LinkState::Missing);
+        };
+        if metadata.len() != self.size {
+            return Ok(LinkState::Changed);
+        }
+        if modified_at(&metadata) == self.modified_at {
+            return Ok(LinkState::Unchanged);
+        }
+
+        let (sha256, size) = checksum::sha256_file(&self.path)?;
+        if sha256 != self.sha256 {
+            return Ok(LinkState::Changed);
+        }
+        Ok(LinkState::Touched(LinkedFile {
+            path: self.path.clone(),
+            size,
+            modified_at: modified_at(&metadata),
+            sha256,
+        }))
+    }
+
+    pub fn blob_ref(&self) -> BlobRef {
+        BlobRef {
+            sha256: self.sha256.clone(),
+            size: self.size,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DatasetLinks {
+    #[serde(default)]
+    linked: LinkedFiles,
+}
+
+/// The `linked` map of the dataset metadata at `info_path`.
+pub fn read(info_path: &Path) -> Result<LinkedFiles> {
+    atomic::read_json::<DatasetLinks>(info_path).map(|dataset| dataset.linked)
+}
+
+/// Replaces the `linked` map of the dataset metadata at `info_path`,
+/// leaving its other fields alone.
+pub fn write(info_path: &Path, linked: &LinkedFiles) -> Result<()> {
+    let mut info: Value = atomic::read_json(info_path)?;
+    let linked = serde_json::to_value(linked)
+        .map_err(|e| ReciprocalError::internal(format!("Failed to serialize links: {}", e)))?;
+    match info.as_object_mut() {
+        Some(object) => {
+            object.insert("linked".into(), linked);
+            atomic::write_json(info_path, &info)
+        }
+        None => Err(ReciprocalError::Corrupt {
+            path: info_path.to_path_buf(),
+            reason: "Dataset metadata is not an object".to_string(),
+        }),
+    }
+}
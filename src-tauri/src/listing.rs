@@ -0,0 +1,76 @@
+use crate::atomic;
+use crate::error::{IoOp, ReciprocalError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A library entry that could not be loaded, and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl Diagnostic {
+    pub fn new(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        Diagnostic {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Result of a listing command: every entry that loaded, plus a diagnostic
+/// for each one that didn't.
+#[derive(Debug, Clone, Serialize)]
+pub struct Listing<T> {
+    pub items: Vec<T>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Loads `metadata_file` from every subdirectory of `dir`.
+///
+/// Only failing to read `dir` itself is an error; a bad entry is reported
+/// as a diagnostic and the scan carries on.
+pub fn scan_metadata<T: DeserializeOwned>(dir: &Path, metadata_file: &str) -> Result<Listing<T>> {
+    let mut listing = Listing {
+        items: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    let entries = fs::read_dir(dir).map_err(ReciprocalError::io(IoOp::ReadDir, dir))?;
+
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                listing
+                    .diagnostics
+                    .push(Diagnostic::new(dir, e.to_string()));
+                continue;
+            }
+        };
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        let metadata_path = path.join(metadata_file);
+        if !metadata_path.exists() && !atomic::backup_path(&metadata_path).exists() {
+            listing
+                .diagnostics
+                .push(Diagnostic::new(&path, format!("Missing {}", metadata_file)));
+            continue;
+        }
+
+        match atomic::read_json::<T>(&metadata_path) {
+            Ok(item) => listing.items.push(item),
+            Err(e) => listing
+                .diagnostics
+                .push(Diagnostic::new(&metadata_path, e.to_string())),
+        }
+    }
+
+    Ok(listing)
+}
@@ -0,0 +1,231 @@
+//! Schema versions and upgrades for project directories.
+//!
+//! Every JSON document the backend owns carries a `schema_version`. The
+//! version in project.json is the version of the whole directory, including
+//! the documents the frontend stores opaquely (cif_data.json, labels.json,
+//! atom_names.json), so migrations are registered against it and may touch
+//! any file below the project directory.
+//!
+//! Before a project is upgraded its JSON documents are copied to
+//! `.backups/schema-v<N>-<timestamp>/`, and each applied migration is
+//! appended to `migrations.log`. Migrations must leave data files where they
+//! are; only the metadata is backed up.
+
+use crate::atomic;
+use crate::error::{IoOp, ReciprocalError, Result};
+use chrono::Utc;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Schema version written by this build.
+pub const CURRENT_SCHEMA_VERSION: u32 = 3;
+
+const BACKUPS_DIR: &str = ".backups";
+const LOG_FILE: &str = "migrations.log";
+
+struct Migration {
+    /// Version this migration upgrades from; it produces `from + 1`.
+    from: u32,
+    description: &'static str,
+    apply: fn(&Path) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "Add schema_version and fill defaults for last_opened_at and has_cif",
+        apply: migrate_v0_to_v1,
+    },
+    Migration {
+        from: 1,
+        description: "Allow dataset files to live in the library blob store",
+        apply: migrate_v1_to_v2,
+    },
+    Migration {
+        from: 2,
+        description:
+            "Record the kind and spin of datasets, for BXSF, case.energy and spin-polarized imports",
+        apply: migrate_v2_to_v3,
+    },
+];
+
+fn schema_version(document: &Value) -> u32 {
+    document
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32
+}
+
+/// Upgrades the project directory to `CURRENT_SCHEMA_VERSION`.
+///
+/// Does nothing if project.json is unreadable; the listing and repair
+/// commands are responsible for reporting that.
+pub fn migrate_project_dir(project_dir: &Path) -> Result<()> {
+    let project_file = project_dir.join("project.json");
+    let Ok(project) = atomic::read_json::<Value>(&project_file) else {
+        return Ok(());
+    };
+
+    let version = schema_version(&project);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(ReciprocalError::UnsupportedSchema {
+            path: project_file,
+            version,
+        });
+    }
+    if version == CURRENT_SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    backup_documents(project_dir, version)?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+        (migration.apply)(project_dir)?;
+        log_migration(project_dir, migration)?;
+    }
+
+    Ok(())
+}
+
+fn backup_documents(project_dir: &Path, version: u32) -> Result<()> {
+    let backup_dir = project_dir.join(BACKUPS_DIR).join(format!(
+        "schema-v{}-{}",
+        version,
+        Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+    copy_json_files(project_dir, project_dir, &backup_dir)
+}
+
+fn copy_json_files(root: &Path, dir: &Path, backup_dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(ReciprocalError::io(IoOp::ReadDir, dir))? {
+        let path = entry
+            .map_err(ReciprocalError::io(IoOp::ReadDir, dir))?
+            .path();
+
+        if path.is_dir() {
+            if path.file_name().is_some_and(|name| name == BACKUPS_DIR) {
+                continue;
+            }
+            copy_json_files(root, &path, backup_dir)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let dest = backup_dir.join(relative);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).map_err(ReciprocalError::io(IoOp::CreateDir, parent))?;
+            }
+            fs::copy(&path, &dest).map_err(ReciprocalError::io(IoOp::Copy, &path))?;
+        }
+    }
+    Ok(())
+}
+
+fn log_migration(project_dir: &Path, migration: &Migration) -> Result<()> {
+    let log_path = project_dir.join(LOG_FILE);
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(ReciprocalError::io(IoOp::Write, &log_path))?;
+    writeln!(
+        log,
+        "{} v{} -> v{}: {}",
+        Utc::now().to_rfc3339(),
+        migration.from,
+        migration.from + 1,
+        migration.description
+    )
+    .map_err(ReciprocalError::io(IoOp::Write, &log_path))
+}
+
+/// Rewrites the JSON document at `path` with `update`, if it exists and parses.
+fn update_document(
+    path: &Path,
+    update: impl FnOnce(&mut serde_json::Map<String, Value>),
+) -> Result<()> {
+    // Unreadable documents are left alone for the listing commands to report.
+    let Ok(mut document) = atomic::read_json::<Value>(path) else {
+        return Ok(());
+    };
+    if let Some(object) = document.as_object_mut() {
+        update(object);
+        atomic::write_json(path, &document)?;
+    }
+    Ok(())
+}
+
+fn dataset_info_files(project_dir: &Path, kind_dir: &str) -> Vec<PathBuf> {
+    fs::read_dir(project_dir.join(kind_dir))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path().join("info.json"))
+                .filter(|path| path.exists())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn migrate_v0_to_v1(project_dir: &Path) -> Result<()> {
+    let has_cif = project_dir.join("structure.cif").exists();
+
+    for kind_dir in ["band_structures", "fermi_surfaces"] {
+        for info_path in dataset_info_files(project_dir, kind_dir) {
+            update_document(&info_path, |info| {
+                info.insert("schema_version".into(), 1.into());
+            })?;
+        }
+    }
+
+    // project.json last, so an interrupted run is retried from the start.
+    update_document(&project_dir.join("project.json"), |project| {
+        project.entry("last_opened_at").or_insert(Value::Null);
+        project.entry("has_cif").or_insert(has_cif.into());
+        project.insert("schema_version".into(), 1.into());
+    })
+}
+
+/// Nothing to convert: datasets without a `files` map keep reading their
+/// files from the dataset directory. The bump stops older builds, which
+/// can't read blobs, from opening projects with newly imported datasets.
+fn migrate_v1_to_v2(project_dir: &Path) -> Result<()> {
+    for kind_dir in ["band_structures", "fermi_surfaces"] {
+        for info_path in dataset_info_files(project_dir, kind_dir) {
+            update_document(&info_path, |info| {
+                info.insert("schema_version".into(), 2.into());
+            })?;
+        }
+    }
+
+    update_document(&project_dir.join("project.json"), |project| {
+        project.insert("schema_version".into(), 2.into());
+    })
+}
+
+/// Writes out the defaults of the fields added for BXSF and `case.energy`
+/// Fermi surfaces and for spin-polarized datasets. The bump stops older
+/// builds, which would take those datasets for unpolarized Wien2k output1
+/// ones, from opening the project.
+fn migrate_v2_to_v3(project_dir: &Path) -> Result<()> {
+    for info_path in dataset_info_files(project_dir, "band_structures") {
+        update_document(&info_path, |info| {
+            info.entry("spin").or_insert("unpolarized".into());
+            info.entry("qtl_dn_filename").or_insert(Value::Null);
+            info.insert("schema_version".into(), 3.into());
+        })?;
+    }
+    for info_path in dataset_info_files(project_dir, "fermi_surfaces") {
+        update_document(&info_path, |info| {
+            info.entry("kind").or_insert("wien2k".into());
+            info.entry("energy_files").or_insert(0.into());
+            info.entry("spin").or_insert("unpolarized".into());
+            info.entry("energy_files_dn").or_insert(0.into());
+            info.insert("schema_version".into(), 3.into());
+        })?;
+    }
+
+    update_document(&project_dir.join("project.json"), |project| {
+        project.insert("schema_version".into(), 3.into());
+    })
+}
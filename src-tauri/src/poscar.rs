@@ -0,0 +1,303 @@
+//! VASP POSCAR and CONTCAR files.
+//!
+//! Both the VASP 5 layout, with a line of species names above the counts,
+//! and the older one without it (species then come from the comment line)
+//! are read. Positions may be direct or Cartesian. Files are written in the
+//! VASP 5 layout with direct coordinates and a scale factor of 1.
+
+use crate::cif::{AtomSite, CrystalData};
+use crate::elements;
+use crate::error::{IoOp, ReciprocalError, Result};
+use crate::lattice::{self, Cell};
+use crate::symmetry::{self, same_position};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Poscar {
+    pub comment: String,
+    /// Lattice vectors as rows, in Å with the scale factor applied.
+    pub lattice: [[f64; 3]; 3],
+    /// Species in file order, each with its number of atoms.
+    pub species: Vec<(String, usize)>,
+    /// Fractional positions, grouped by species in the same order.
+    pub positions: Vec<[f64; 3]>,
+    /// Which coordinates may relax, when the file has `Selective dynamics`.
+    pub selective_dynamics: Option<Vec<[bool; 3]>>,
+}
+
+struct Lines<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    line: usize,
+    file: &'a Path,
+}
+
+impl<'a> Lines<'a> {
+    fn error(&self, message: impl Into<String>) -> ReciprocalError {
+        ReciprocalError::Parse {
+            file: self.file.to_path_buf(),
+            line: Some(self.line),
+            message: message.into(),
+        }
+    }
+
+    fn next(&mut self, what: &str) -> Result<&'a str> {
+        let (i, line) = self
+            .lines
+            .next()
+            .ok_or_else(|| self.error(format!("File ends before the {}", what)))?;
+        self.line = i + 1;
+        Ok(line)
+    }
+
+    fn numbers<const N: usize>(&self, line: &str, what: &str) -> Result<[f64; N]> {
+        let values: Vec<f64> = line
+            .split_whitespace()
+            .take(N)
+            .map_while(|t| t.parse().ok())
+            .collect();
+        if values.len() < N {
+            return Err(self.error(format!("Expected {} numbers for {}", N, what)));
+        }
+        Ok(std::array::from_fn(|i| values[i]))
+    }
+}
+
+/// VASP's `T`/`F` relaxation flags.
+fn flag(text: Option<&str>) -> Option<bool> {
+    match text?.chars().next()? {
+        'T' | 't' => Some(true),
+        'F' | 'f' => Some(false),
+        _ => None,
+    }
+}
+
+impl Poscar {
+    pub fn parse(text: &str, file: &Path) -> Result<Self> {
+        let mut lines = Lines {
+            lines: text.lines().enumerate(),
+            line: 0,
+            file,
+        };
+
+        let comment = lines.next("comment")?.trim().to_string();
+        let scale_line = lines.next("scale factor")?;
+        let scale = lines.numbers::<1>(scale_line, "the scale factor")?[0];
+        let mut vectors = [[0.0; 3]; 3];
+        for row in &mut vectors {
+            let line = lines.next("lattice vectors")?;
+            *row = lines.numbers::<3>(line, "a lattice vector")?;
+        }
+        // A negative scale is the cell volume instead.
+        let factor = if scale < 0.0 {
+            let [a, b, c] = &vectors;
+            let volume = lattice::dot(a, &lattice::cross(b, c)).abs();
+            (-scale / volume).cbrt()
+        } else {
+            scale
+        };
+        let vectors = vectors.map(|row| row.map(|v| v * factor));
+
+        let mut line = lines.next("species")?;
+        let names: Vec<String> = if line
+            .split_whitespace()
+            .next()
+            .is_some_and(|t| t.parse::<usize>().is_err())
+        {
+            let names = line.split_whitespace().map(str::to_string).collect();
+            line = lines.next("atom counts")?;
+            names
+        } else {
+            comment.split_whitespace().map(str::to_string).collect()
+        };
+        let counts: Vec<usize> = line
+            .split_whitespace()
+            .map_while(|t| t.parse().ok())
+            .collect();
+        if counts.is_empty() {
+            return Err(lines.error("Expected the number of atoms of each species"));
+        }
+        if names.len() < counts.len() {
+            return Err(lines.error("The file doesn't name every species"));
+        }
+        let species: Vec<(String, usize)> = names.into_iter().zip(counts).collect();
+
+        let mut mode = lines.next("coordinate mode")?.trim();
+        let selective = mode.starts_with(['S', 's']);
+        if selective {
+            mode = lines.next("coordinate mode")?.trim();
+        }
+        let cartesian = mode.starts_with(['C', 'c', 'K', 'k']);
+        let to_fractional = lattice::invert(&vectors)
+            .ok_or_else(|| lines.error("The lattice vectors don't span a cell"))?;
+
+        let total: usize = species.iter().map(|(_, n)| n).sum();
+        let mut positions = Vec::with_capacity(total);
+        let mut flags = Vec::new();
+        for _ in 0..total {
+            let line = lines.next("atom positions")?;
+            let p = lines.numbers::<3>(line, "an atom position")?;
+            positions.push(if cartesian {
+                let p = p.map(|v| v * factor);
+                std::array::from_fn(|j| (0..3).map(|i| p[i] * to_fractional[i][j]).sum())
+            } else {
+                p
+            });
+            if selective {
+                let mut fields = line.split_whitespace().skip(3);
+                let mut next = || flag(fields.next());
+                match (next(), next(), next()) {
+                    (Some(x), Some(y), Some(z)) => flags.push([x, y, z]),
+                    _ => return Err(lines.error("Expected T or F flags after the position")),
+                }
+            }
+        }
+
+        Ok(Poscar {
+            comment,
+            lattice: vectors,
+            species,
+            positions,
+            selective_dynamics: selective.then_some(flags),
+        })
+    }
+
+    /// The structure as P1 sites, labelled by species and a running number
+    /// such as `O3`. Lengths in Å.
+    pub fn to_crystal_data(&self) -> CrystalData {
+        let mut data = CrystalData::from_cell(&Cell::from_vectors(&self.lattice));
+        let mut positions = self.positions.iter();
+        for (name, count) in &self.species {
+            // VASP 5.4 writes POTCAR names such as `Sr_sv` or `Fe/1a2b3c`.
+            let element = elements::element_of(name).unwrap_or(name);
+            for (n, p) in positions.by_ref().take(*count).enumerate() {
+                data.atom_sites.push(AtomSite {
+                    label: format!("{}{}", element, n + 1),
+                    type_symbol: element.to_string(),
+                    fract_x: p[0],
+                    fract_y: p[1],
+                    fract_z: p[2],
+                    wyckoff_symbol: None,
+                    symmetry_multiplicity: None,
+                    occupancy: 1.0,
+                });
+            }
+        }
+        data.space_group_hm = Some("P 1".to_string());
+        data.space_group_it_number = Some(1);
+        data.symmetry_operations = vec!["x,y,z".to_string()];
+        data
+    }
+
+    /// Every atom of the conventional cell of `data`, grouped by element.
+    /// VASP has no partial occupancy, so where sites share a position the
+    /// first one listed takes it. `source` names the file the data came
+    /// from, for errors.
+    pub fn from_crystal_data(data: &CrystalData, comment: &str, source: &Path) -> Result<Self> {
+        let error = |message: String| ReciprocalError::Parse {
+            file: source.to_path_buf(),
+            line: None,
+            message,
+        };
+        let cell = data.cell();
+        let parameters = [cell.a, cell.b, cell.c, cell.alpha, cell.beta, cell.gamma];
+        if parameters.iter().any(|v| !v.is_finite() || *v <= 0.0) {
+            return Err(error("The structure has no complete unit cell".into()));
+        }
+
+        let ops = symmetry::parse_operations(&data.symmetry_operations);
+        let mut occupied: Vec<[f64; 3]> = Vec::new();
+        let mut groups: Vec<(&str, Vec<[f64; 3]>)> = Vec::new();
+        for site in &data.atom_sites {
+            let position = [site.fract_x, site.fract_y, site.fract_z];
+            if position.iter().any(|v| !v.is_finite()) {
+                return Err(error(format!("Atom {} has no position", site.label)));
+            }
+            let element = elements::element_of(&site.type_symbol)
+                .or_else(|| elements::element_of(&site.label))
+                .ok_or_else(|| error(format!("Atom {} has no known element", site.label)))?;
+            let index = match groups.iter().position(|(e, _)| *e == element) {
+                Some(index) => index,
+                None => {
+                    groups.push((element, Vec::new()));
+                    groups.len() - 1
+                }
+            };
+            for p in symmetry::orbit(position, &ops) {
+                if !occupied.iter().any(|&q| same_position(p, q)) {
+                    occupied.push(p);
+                    groups[index].1.push(p);
+                }
+            }
+        }
+        groups.retain(|(_, positions)| !positions.is_empty());
+        if groups.is_empty() {
+            return Err(error("The structure has no atom sites".into()));
+        }
+
+        Ok(Poscar {
+            comment: comment.to_string(),
+            lattice: cell.vectors(),
+            species: groups
+                .iter()
+                .map(|(element, positions)| (element.to_string(), positions.len()))
+                .collect(),
+            positions: groups.into_iter().flat_map(|(_, p)| p).collect(),
+            selective_dynamics: None,
+        })
+    }
+
+    /// The file in the VASP 5 layout.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let mut line = |text: String| {
+            out.push_str(&text);
+            out.push('\n');
+        };
+
+        // The comment must stay on one line.
+        line(self.comment.lines().next().unwrap_or("").to_string());
+        line(format!("{:19.14}", 1.0));
+        for row in &self.lattice {
+            // Leave out the rounding error of cos 90°.
+            let row = row.map(|v| if v.abs() < 1e-10 { 0.0 } else { v });
+            line(row.iter().map(|v| format!("{:22.16}", v)).collect());
+        }
+        line(
+            self.species
+                .iter()
+                .map(|(name, _)| format!("{:>5}", name))
+                .collect(),
+        );
+        line(
+            self.species
+                .iter()
+                .map(|(_, count)| format!("{:>5}", count))
+                .collect(),
+        );
+        if self.selective_dynamics.is_some() {
+            line("Selective dynamics".to_string());
+        }
+        line("Direct".to_string());
+        for (i, p) in self.positions.iter().enumerate() {
+            let mut text: String = p.iter().map(|v| format!("{:20.16}", v)).collect();
+            if let Some(flags) = self
+                .selective_dynamics
+                .as_ref()
+                .and_then(|flags| flags.get(i))
+            {
+                for &flag in flags {
+                    text.push_str(if flag { "   T" } else { "   F" });
+                }
+            }
+            line(text);
+        }
+        out
+    }
+}
+
+pub fn read_poscar(path: &Path) -> Result<Poscar> {
+    let text = fs::read_to_string(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+    Poscar::parse(&text, path)
+}
@@ -0,0 +1,76 @@
+//! Where an imported dataset came from.
+//!
+//! Captured once at import time and stored as provenance.json next to the
+//! dataset's info.json, so the listing commands don't have to carry it.
+
+use crate::blobs::BlobFiles;
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PROVENANCE_FILE: &str = "provenance.json";
+
+/// Free-form calculation parameters supplied by the user, such as the
+/// Wien2k version or RKmax.
+pub type Parameters = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceFile {
+    /// Name the file is stored under in the dataset, e.g. `data.qtl`.
+    pub name: String,
+    /// Absolute path it was imported from.
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified_at: Option<DateTime<Utc>>,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub imported_at: DateTime<Utc>,
+    pub host: Option<String>,
+    pub app_version: String,
+    pub sources: Vec<SourceFile>,
+    #[serde(default)]
+    pub parameters: Parameters,
+}
+
+impl Provenance {
+    /// Describes an import of `sources`, given as (stored name, source path),
+    /// whose contents are now in the blob store as `files`.
+    pub fn capture(sources: &[(&str, &Path)], files: &BlobFiles, parameters: Parameters) -> Self {
+        let sources = sources
+            .iter()
+            .filter_map(|(name, path)| {
+                let blob = files.get(*name)?;
+                let modified_at = fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(DateTime::<Utc>::from);
+                Some(SourceFile {
+                    name: name.to_string(),
+                    path: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+                    size: blob.size,
+                    modified_at,
+                    sha256: blob.sha256.clone(),
+                })
+            })
+            .collect();
+
+        Provenance {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            imported_at: Utc::now(),
+            host: hostname::get()
+                .ok()
+                .map(|host| host.to_string_lossy().into_owned()),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            sources,
+            parameters,
+        }
+    }
+}
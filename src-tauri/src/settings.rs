@@ -0,0 +1,45 @@
+use crate::atomic;
+use crate::error::Result;
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Library-wide settings, stored as settings.json in the app data directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibrarySettings {
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Days a deleted item stays in the trash before it is purged. `None`
+    /// keeps trashed items until the trash is emptied by hand.
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: Option<u32>,
+}
+
+fn default_trash_retention_days() -> Option<u32> {
+    Some(30)
+}
+
+impl Default for LibrarySettings {
+    fn default() -> Self {
+        LibrarySettings {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            trash_retention_days: default_trash_retention_days(),
+        }
+    }
+}
+
+pub fn load(app_data_dir: &Path) -> Result<LibrarySettings> {
+    let path = app_data_dir.join(SETTINGS_FILE);
+    if !path.exists() && !atomic::backup_path(&path).exists() {
+        return Ok(LibrarySettings::default());
+    }
+    atomic::read_json(&path)
+}
+
+pub fn save(app_data_dir: &Path, settings: &LibrarySettings) -> Result<()> {
+    let mut settings = settings.clone();
+    settings.schema_version = CURRENT_SCHEMA_VERSION;
+    atomic::write_json(&app_data_dir.join(SETTINGS_FILE), &settings)
+}
@@ -0,0 +1,207 @@
+//! The parsed form of a band structure, kept as `bands.cache` in its
+//! directory so the .qtl files are only read once.
+//!
+//! The file is little-endian: a magic number, the format version, the
+//! SHA-256 of the files it was parsed from, then the k-path and each
+//! channel. Strings and arrays are prefixed with their length. A cache that
+//! is stale or doesn't read is parsed again, never reported as an error.
+
+use super::band_structures::{BandPathPoint, BandStructureData};
+use crate::atomic;
+use crate::error::Result;
+use crate::wien2k::{Qtl, QtlAtom, QtlBand};
+use std::fs;
+use std::path::Path;
+
+pub(super) const CACHE_FILE: &str = "bands.cache";
+
+const MAGIC: &[u8; 4] = b"RBND";
+const FORMAT_VERSION: u32 = 1;
+
+/// The cached data, if the file holds it for `key`.
+pub(super) fn read(path: &Path, key: &str) -> Option<BandStructureData> {
+    let bytes = fs::read(path).ok()?;
+    let mut reader = Reader(&bytes);
+    if reader.take(4)? != MAGIC || reader.u32()? != FORMAT_VERSION || reader.string()? != key {
+        return None;
+    }
+
+    let path = reader.list(|r| {
+        let label = r.string()?;
+        Some(BandPathPoint {
+            label: (!label.is_empty()).then_some(label),
+            k: [r.f64()?, r.f64()?, r.f64()?],
+        })
+    })?;
+    let up = reader.qtl()?;
+    let down = match reader.u8()? {
+        0 => None,
+        _ => Some(reader.qtl()?),
+    };
+    reader
+        .0
+        .is_empty()
+        .then_some(BandStructureData { path, up, down })
+}
+
+pub(super) fn write(path: &Path, key: &str, data: &BandStructureData) -> Result<()> {
+    let mut writer = Writer(Vec::new());
+    writer.0.extend_from_slice(MAGIC);
+    writer.u32(FORMAT_VERSION);
+    writer.string(key);
+
+    writer.usize(data.path.len());
+    for point in &data.path {
+        writer.string(point.label.as_deref().unwrap_or(""));
+        point.k.iter().for_each(|&k| writer.f64(k));
+    }
+    writer.qtl(&data.up);
+    match &data.down {
+        Some(down) => {
+            writer.0.push(1);
+            writer.qtl(down);
+        }
+        None => writer.0.push(0),
+    }
+    atomic::write_atomic(path, &writer.0)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.0.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.usize(value.len());
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn floats(&mut self, values: &[f32]) {
+        self.usize(values.len());
+        self.0.reserve(values.len() * 4);
+        for value in values {
+            self.0.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn qtl(&mut self, qtl: &Qtl) {
+        self.string(&qtl.case_name);
+        qtl.lattice_constants.iter().for_each(|&a| self.f64(a));
+        self.f64(qtl.fermi_energy);
+        self.0.push(qtl.spin_polarized as u8);
+        self.0.push(qtl.spin_orbit as u8);
+
+        self.usize(qtl.atoms.len());
+        for atom in &qtl.atoms {
+            self.usize(atom.index);
+            self.usize(atom.multiplicity);
+            self.0.extend_from_slice(&atom.isplit.to_le_bytes());
+            self.usize(atom.orbital_labels.len());
+            atom.orbital_labels
+                .iter()
+                .for_each(|label| self.string(label));
+        }
+
+        self.usize(qtl.bands.len());
+        for band in &qtl.bands {
+            self.usize(band.index);
+            self.floats(&band.energies);
+            self.floats(&band.weights);
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn usize(&mut self) -> Option<usize> {
+        self.array().map(u64::from_le_bytes)?.try_into().ok()
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        self.array().map(f64::from_le_bytes)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.usize()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn floats(&mut self) -> Option<Vec<f32>> {
+        let len = self.usize()?;
+        let bytes = self.take(len.checked_mul(4)?)?;
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        )
+    }
+
+    /// `len` items read by `item`, without trusting `len` for the
+    /// allocation.
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let len = self.usize()?;
+        let mut items = Vec::with_capacity(len.min(self.0.len()));
+        for _ in 0..len {
+            items.push(item(self)?);
+        }
+        Some(items)
+    }
+
+    fn qtl(&mut self) -> Option<Qtl> {
+        Some(Qtl {
+            case_name: self.string()?,
+            lattice_constants: [self.f64()?, self.f64()?, self.f64()?],
+            fermi_energy: self.f64()?,
+            spin_polarized: self.u8()? != 0,
+            spin_orbit: self.u8()? != 0,
+            atoms: self.list(|r| {
+                Some(QtlAtom {
+                    index: r.usize()?,
+                    multiplicity: r.usize()?,
+                    isplit: r.array().map(i32::from_le_bytes)?,
+                    orbital_labels: r.list(Reader::string)?,
+                })
+            })?,
+            bands: self.list(|r| {
+                Some(QtlBand {
+                    index: r.usize()?,
+                    energies: r.floats()?,
+                    weights: r.floats()?,
+                })
+            })?,
+        })
+    }
+}
@@ -0,0 +1,472 @@
+use super::band_cache::{self, CACHE_FILE};
+use super::{ensure_dir, item_name, read_text, total_size, ImportOptions, ProjectStore, Spin};
+use crate::atomic;
+use crate::blobs::BlobFiles;
+use crate::checksum;
+use crate::error::{IoOp, ReciprocalError, ResourceKind, Result};
+use crate::ids::{DatasetId, ProjectId};
+use crate::jobs::Progress;
+use crate::links::LinkedFiles;
+use crate::listing::{self, Listing};
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use crate::trash::{self, TrashEntry};
+use crate::wien2k::{self, Qtl};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandStructureInfo {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub qtl_filename: String,
+    pub klist_filename: String,
+    /// `Polarized` band structures keep the up channel in data.qtl and the
+    /// down channel in data.qtl_dn.
+    #[serde(default)]
+    pub spin: Spin,
+    /// Original name of the down channel's file, for `Polarized` ones.
+    #[serde(default)]
+    pub qtl_dn_filename: Option<String>,
+    /// Blobs holding data.qtl and data.klist_band. Empty for band structures
+    /// imported before the blob store, which keep the files alongside.
+    #[serde(default)]
+    pub files: BlobFiles,
+    /// Files referenced in place rather than stored, for band structures
+    /// imported with `ImportOptions::link`.
+    #[serde(default)]
+    pub linked: LinkedFiles,
+}
+
+/// A point of the k-path from the .klist_band.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BandPathPoint {
+    /// Set for the named high-symmetry points.
+    pub label: Option<String>,
+    /// In units of the reciprocal lattice vectors.
+    pub k: [f64; 3],
+}
+
+/// A band structure as parsed from its files, one `Qtl` per channel with a
+/// point for each point of `path`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BandStructureData {
+    pub path: Vec<BandPathPoint>,
+    pub up: Qtl,
+    /// The down channel of `Polarized` band structures, with energies
+    /// relative to the up channel's Fermi energy.
+    pub down: Option<Qtl>,
+}
+
+/// Parses a band structure's .qtl files along the k-path of `klist`.
+fn parse_band_structure(
+    qtl: &Path,
+    qtl_dn: Option<&Path>,
+    klist: &Path,
+    progress: &Progress,
+) -> Result<BandStructureData> {
+    let mut paths = vec![qtl];
+    paths.extend(qtl_dn);
+    paths.push(klist);
+    progress.phase(
+        "Parsing band structure",
+        Some(total_size(&paths)),
+        Some(paths.len()),
+    );
+
+    let path: Vec<BandPathPoint> = wien2k::parse_klist(&read_text(klist, progress)?, klist)?
+        .into_iter()
+        .map(|point| BandPathPoint {
+            k: point.k(),
+            label: (!point.name.is_empty()).then_some(point.name),
+        })
+        .collect();
+    let read = |file: &Path| -> Result<Qtl> {
+        let qtl = wien2k::parse_qtl(&read_text(file, progress)?, file)?;
+        match qtl
+            .bands
+            .iter()
+            .find(|band| band.energies.len() != path.len())
+        {
+            Some(band) => Err(ReciprocalError::Parse {
+                file: file.to_path_buf(),
+                line: None,
+                message: format!(
+                    "Band {} has {} k-points, but the k-path has {}",
+                    band.index,
+                    band.energies.len(),
+                    path.len()
+                ),
+            }),
+            None => Ok(qtl),
+        }
+    };
+
+    let up = read(qtl)?;
+    let down = match qtl_dn {
+        Some(qtl_dn) => {
+            let mut down = read(qtl_dn)?;
+            down.shift(down.fermi_energy - up.fermi_energy, up.fermi_energy);
+            Some(down)
+        }
+        None => None,
+    };
+    Ok(BandStructureData { path, up, down })
+}
+
+/// Key of the cache parsed from `sources`: the SHA-256 of each, as recorded
+/// at import. Files kept alongside by old band structures are hashed.
+fn cache_key(info: &BandStructureInfo, sources: &[(&str, &Path)]) -> Result<String> {
+    let hashes = sources
+        .iter()
+        .map(
+            |&(name, path)| match (info.files.get(name), info.linked.get(name)) {
+                (Some(blob), _) => Ok(blob.sha256.clone()),
+                (None, Some(link)) => Ok(link.sha256.clone()),
+                (None, None) => checksum::sha256_file(path).map(|(sha256, _)| sha256),
+            },
+        )
+        .collect::<Result<Vec<_>>>()?;
+    Ok(hashes.join(","))
+}
+
+impl ProjectStore {
+    fn band_structures_dir(&self, project_id: &ProjectId) -> Result<PathBuf> {
+        ensure_dir(self.project_dir(project_id)?.join("band_structures"))
+    }
+
+    /// Directory of an existing band structure.
+    fn band_structure_dir(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+    ) -> Result<PathBuf> {
+        let band_path = self
+            .band_structures_dir(project_id)?
+            .join(band_structure_id);
+        if !band_path.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::BandStructure,
+                band_structure_id.clone(),
+            ));
+        }
+        Ok(band_path)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn import_band_structure(
+        &self,
+        project_id: &ProjectId,
+        name: String,
+        qtl_source_path: &Path,
+        qtl_filename: String,
+        klist_source_path: &Path,
+        klist_filename: String,
+        options: ImportOptions,
+        progress: &Progress,
+    ) -> Result<BandStructureInfo> {
+        let sources = [
+            ("data.qtl", qtl_source_path),
+            ("data.klist_band", klist_source_path),
+        ];
+        let data = parse_band_structure(qtl_source_path, None, klist_source_path, progress)?;
+        self.store_band_structure(
+            project_id,
+            &sources,
+            &data,
+            |files, linked| BandStructureInfo {
+                schema_version: CURRENT_SCHEMA_VERSION,
+                id: String::new(),
+                name,
+                created_at: Utc::now(),
+                qtl_filename,
+                klist_filename,
+                spin: Spin::Unpolarized,
+                qtl_dn_filename: None,
+                files,
+                linked,
+            },
+            options,
+            progress,
+        )
+    }
+
+    /// Imports the `case.qtlup` and `case.qtldn` of a spin-polarized run as
+    /// one band structure, both on the k-path of `klist_source_path`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn import_spin_band_structure(
+        &self,
+        project_id: &ProjectId,
+        name: String,
+        qtl_up_source_path: &Path,
+        qtl_up_filename: String,
+        qtl_dn_source_path: &Path,
+        qtl_dn_filename: String,
+        klist_source_path: &Path,
+        klist_filename: String,
+        options: ImportOptions,
+        progress: &Progress,
+    ) -> Result<BandStructureInfo> {
+        let sources = [
+            ("data.qtl", qtl_up_source_path),
+            ("data.qtl_dn", qtl_dn_source_path),
+            ("data.klist_band", klist_source_path),
+        ];
+        let data = parse_band_structure(
+            qtl_up_source_path,
+            Some(qtl_dn_source_path),
+            klist_source_path,
+            progress,
+        )?;
+        self.store_band_structure(
+            project_id,
+            &sources,
+            &data,
+            |files, linked| BandStructureInfo {
+                schema_version: CURRENT_SCHEMA_VERSION,
+                id: String::new(),
+                name,
+                created_at: Utc::now(),
+                qtl_filename: qtl_up_filename,
+                klist_filename,
+                spin: Spin::Polarized,
+                qtl_dn_filename: Some(qtl_dn_filename),
+                files,
+                linked,
+            },
+            options,
+            progress,
+        )
+    }
+
+    /// Stores `sources` as a new band structure described by `info`, which
+    /// is given the new ID, and caches `data` parsed from them.
+    fn store_band_structure(
+        &self,
+        project_id: &ProjectId,
+        sources: &[(&str, &Path)],
+        data: &BandStructureData,
+        info: impl FnOnce(BlobFiles, LinkedFiles) -> BandStructureInfo,
+        options: ImportOptions,
+        progress: &Progress,
+    ) -> Result<BandStructureInfo> {
+        let band_dir = self.band_structures_dir(project_id)?;
+        let (files, linked) = self.store_files(sources, options.link, progress)?;
+
+        let id = DatasetId::new().to_string();
+        let band_path = band_dir.join(&id);
+        fs::create_dir_all(&band_path).map_err(ReciprocalError::io(IoOp::CreateDir, &band_path))?;
+        self.write_provenance(&band_path, sources, &files, &linked, options.parameters)?;
+
+        let info = BandStructureInfo {
+            id,
+            ..info(files, linked)
+        };
+
+        // Save metadata
+        let info_path = band_path.join("info.json");
+        atomic::write_json(&info_path, &info)?;
+        band_cache::write(
+            &band_path.join(CACHE_FILE),
+            &cache_key(&info, sources)?,
+            data,
+        )?;
+
+        Ok(info)
+    }
+
+    pub fn list_band_structures(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Listing<BandStructureInfo>> {
+        let band_dir = self.band_structures_dir(project_id)?;
+        let mut listing = listing::scan_metadata::<BandStructureInfo>(&band_dir, "info.json")?;
+
+        // Sort by created_at descending
+        listing.items.sort_by_key(|info| Reverse(info.created_at));
+
+        Ok(listing)
+    }
+
+    pub fn band_structure_info(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+    ) -> Result<BandStructureInfo> {
+        let band_path = self.band_structure_dir(project_id, band_structure_id)?;
+        atomic::read_json(&band_path.join("info.json"))
+    }
+
+    /// Contents of the band structure's .qtl and .klist_band files.
+    pub fn load_band_structure_files(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+        progress: &Progress,
+    ) -> Result<(String, String)> {
+        let band_path = self.band_structure_dir(project_id, band_structure_id)?;
+        let info: BandStructureInfo = atomic::read_json(&band_path.join("info.json"))?;
+
+        let qtl_path = self.data_file(&band_path, &info.files, &info.linked, "data.qtl")?;
+        let klist_path =
+            self.data_file(&band_path, &info.files, &info.linked, "data.klist_band")?;
+        progress.phase(
+            "Reading files",
+            Some(total_size(&[&qtl_path, &klist_path])),
+            Some(2),
+        );
+
+        let qtl_content = read_text(&qtl_path, progress)?;
+        let klist_content = read_text(&klist_path, progress)?;
+        Ok((qtl_content, klist_content))
+    }
+
+    /// The parsed band structure, from its cache unless the files have
+    /// changed since it was written.
+    pub fn load_band_structure_data(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+        progress: &Progress,
+    ) -> Result<BandStructureData> {
+        let band_path = self.band_structure_dir(project_id, band_structure_id)?;
+        let info: BandStructureInfo = atomic::read_json(&band_path.join("info.json"))?;
+
+        let qtl_path = self.data_file(&band_path, &info.files, &info.linked, "data.qtl")?;
+        let qtl_dn_path = match info.spin {
+            Spin::Polarized => {
+                Some(self.data_file(&band_path, &info.files, &info.linked, "data.qtl_dn")?)
+            }
+            Spin::Unpolarized => None,
+        };
+        let klist_path =
+            self.data_file(&band_path, &info.files, &info.linked, "data.klist_band")?;
+
+        let mut sources = vec![("data.qtl", qtl_path.as_path())];
+        sources.extend(qtl_dn_path.as_deref().map(|path| ("data.qtl_dn", path)));
+        sources.push(("data.klist_band", klist_path.as_path()));
+        let key = cache_key(&info, &sources)?;
+        let cache_path = band_path.join(CACHE_FILE);
+        if let Some(data) = band_cache::read(&cache_path, &key) {
+            return Ok(data);
+        }
+
+        let data = parse_band_structure(&qtl_path, qtl_dn_path.as_deref(), &klist_path, progress)?;
+        band_cache::write(&cache_path, &key, &data)?;
+        Ok(data)
+    }
+
+    /// Contents of a spin-polarized band structure's .qtl, .qtl_dn and
+    /// .klist_band files.
+    pub fn load_spin_band_structure_files(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+        progress: &Progress,
+    ) -> Result<(String, String, String)> {
+        let band_path = self.band_structure_dir(project_id, band_structure_id)?;
+        let info: BandStructureInfo = atomic::read_json(&band_path.join("info.json"))?;
+        if info.spin != Spin::Polarized {
+            return Err(ReciprocalError::internal(format!(
+                "Band structure {} is not spin-polarized",
+                band_structure_id
+            )));
+        }
+
+        let up_path = self.data_file(&band_path, &info.files, &info.linked, "data.qtl")?;
+        let down_path = self.data_file(&band_path, &info.files, &info.linked, "data.qtl_dn")?;
+        let klist_path =
+            self.data_file(&band_path, &info.files, &info.linked, "data.klist_band")?;
+        progress.phase(
+            "Reading files",
+            Some(total_size(&[&up_path, &down_path, &klist_path])),
+            Some(3),
+        );
+
+        Ok((
+            read_text(&up_path, progress)?,
+            read_text(&down_path, progress)?,
+            read_text(&klist_path, progress)?,
+        ))
+    }
+
+    pub fn delete_band_structure(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+    ) -> Result<TrashEntry> {
+        let band_path = self.band_structure_dir(project_id, band_structure_id)?;
+
+        let name = item_name(&band_path.join("info.json"), band_structure_id.as_str());
+        trash::move_to_trash(
+            &self.trash_dir(),
+            &band_path,
+            ResourceKind::BandStructure,
+            band_structure_id.as_str(),
+            Some(project_id.as_str()),
+            name,
+        )
+    }
+
+    pub fn update_band_structure_labels(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+        labels_json: &str,
+    ) -> Result<()> {
+        let band_path = self.band_structure_dir(project_id, band_structure_id)?;
+        atomic::write_atomic(&band_path.join("labels.json"), labels_json.as_bytes())
+    }
+
+    pub fn load_band_structure_labels(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+    ) -> Result<Option<String>> {
+        let labels_path = self
+            .band_structures_dir(project_id)?
+            .join(band_structure_id)
+            .join("labels.json");
+
+        if !labels_path.exists() {
+            return Ok(None);
+        }
+
+        atomic::read_json_text(&labels_path).map(Some)
+    }
+
+    pub fn update_band_structure_atom_names(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+        atom_names_json: &str,
+    ) -> Result<()> {
+        let band_path = self.band_structure_dir(project_id, band_structure_id)?;
+        atomic::write_atomic(
+            &band_path.join("atom_names.json"),
+            atom_names_json.as_bytes(),
+        )
+    }
+
+    pub fn load_band_structure_atom_names(
+        &self,
+        project_id: &ProjectId,
+        band_structure_id: &DatasetId,
+    ) -> Result<Option<String>> {
+        let names_path = self
+            .band_structures_dir(project_id)?
+            .join(band_structure_id)
+            .join("atom_names.json");
+
+        if !names_path.exists() {
+            return Ok(None);
+        }
+
+        atomic::read_json_text(&names_path).map(Some)
+    }
+}
@@ -0,0 +1,547 @@
+use super::{ensure_dir, item_name, read_text, total_size, ImportOptions, ProjectStore, Spin};
+use crate::atomic;
+use crate::band_grid::{BandGrid, GridColour, GridFormat, SpinChannel};
+use crate::blobs::BlobFiles;
+use crate::bxsf;
+use crate::error::{IoOp, ReciprocalError, ResourceKind, Result};
+use crate::frmsf;
+use crate::ids::{DatasetId, ProjectId};
+use crate::jobs::Progress;
+use crate::links::LinkedFiles;
+use crate::listing::{self, Listing};
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use crate::trash::{self, TrashEntry};
+use crate::wien2k;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The files a Fermi surface was imported from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FermiSurfaceKind {
+    /// Wien2k output1, output2, outputkgen and struct files.
+    #[default]
+    Wien2k,
+    /// Wien2k `case.energy` files in place of output1, stored as
+    /// `data.energy_1..N` with the `data.klist` they were checked against.
+    Wien2kEnergy,
+    /// An XCrysDen band grid, `data.bxsf`.
+    Bxsf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FermiSurfaceInfo {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub case_name: String,
+    #[serde(default)]
+    pub kind: FermiSurfaceKind,
+    /// Number of `data.energy_N` files, for `Wien2kEnergy` Fermi surfaces.
+    #[serde(default)]
+    pub energy_files: usize,
+    /// `Polarized` Wien2k Fermi surfaces keep the down channel's
+    /// eigenvalues in `data.output1_dn` or `data.energy_dn_1..N`, beside
+    /// the up channel's in the usual files.
+    #[serde(default)]
+    pub spin: Spin,
+    /// Number of `data.energy_dn_N` files.
+    #[serde(default)]
+    pub energy_files_dn: usize,
+    /// Blobs holding the data.* files. Empty for Fermi surfaces imported
+    /// before the blob store, which keep the files alongside.
+    #[serde(default)]
+    pub files: BlobFiles,
+    /// Files referenced in place rather than stored, for Fermi surfaces
+    /// imported with `ImportOptions::link`.
+    #[serde(default)]
+    pub linked: LinkedFiles,
+}
+
+/// Where a Wien2k Fermi surface's eigenvalues are read from.
+#[derive(Debug, Clone, Copy)]
+pub enum Eigenvalues<'a> {
+    Output1(&'a Path),
+    /// `case.energy`, `energyso`, `energyup` or `energydn`, or the splits
+    /// of a k-parallel run in any order, with the `case.klist` they are
+    /// checked against.
+    Energy {
+        files: &'a [PathBuf],
+        klist: &'a Path,
+    },
+}
+
+/// The Wien2k files a Fermi surface is imported from.
+#[derive(Debug, Clone, Copy)]
+pub struct FermiSurfaceFiles<'a> {
+    pub eigenvalues: Eigenvalues<'a>,
+    /// The down channel of a spin-polarized run, with `eigenvalues` the up
+    /// channel. Both must be read from the same kind of file, and energy
+    /// files from the same k-list.
+    pub down: Option<Eigenvalues<'a>>,
+    pub output2: &'a Path,
+    pub outputkgen: &'a Path,
+    pub struct_file: &'a Path,
+}
+
+impl FermiSurfaceInfo {
+    /// Names of the data files the Fermi surface was imported with.
+    pub fn data_file_names(&self) -> Vec<String> {
+        if self.kind == FermiSurfaceKind::Bxsf {
+            return vec!["data.bxsf".to_string()];
+        }
+        let mut names = self.eigenvalue_file_names(SpinChannel::Up);
+        if self.kind == FermiSurfaceKind::Wien2kEnergy {
+            names.push("data.klist".to_string());
+        }
+        if self.spin == Spin::Polarized {
+            names.extend(self.eigenvalue_file_names(SpinChannel::Down));
+        }
+        names.extend(["data.output2", "data.outputkgen", "data.struct"].map(String::from));
+        names
+    }
+
+    /// Data files holding one channel's eigenvalues. The up channel is the
+    /// only one of a Fermi surface that isn't spin-polarized.
+    fn eigenvalue_file_names(&self, channel: SpinChannel) -> Vec<String> {
+        let (suffix, energy_files) = match channel {
+            SpinChannel::Up => ("", self.energy_files),
+            SpinChannel::Down => ("_dn", self.energy_files_dn),
+        };
+        match self.kind {
+            FermiSurfaceKind::Bxsf => Vec::new(),
+            FermiSurfaceKind::Wien2k => vec![format!("data.output1{}", suffix)],
+            FermiSurfaceKind::Wien2kEnergy => (1..=energy_files)
+                .map(|i| format!("data.energy{}_{}", suffix, i))
+                .collect(),
+        }
+    }
+}
+
+/// Data file names and the files they are stored from.
+type Sources<'a> = Vec<(String, &'a Path)>;
+
+/// The data files one channel's eigenvalues are stored as, named with
+/// `suffix` after the file type, and for energy files the k-list they were
+/// checked against.
+fn eigenvalue_sources<'a>(
+    eigenvalues: Eigenvalues<'a>,
+    suffix: &str,
+    progress: &Progress,
+) -> Result<(Sources<'a>, Option<Vec<wien2k::KListPoint>>)> {
+    match eigenvalues {
+        Eigenvalues::Output1(output1) => {
+            Ok((vec![(format!("data.output1{}", suffix), output1)], None))
+        }
+        Eigenvalues::Energy { files, klist } => {
+            if files.is_empty() {
+                return Err(ReciprocalError::internal("No energy files to import"));
+            }
+            progress.phase("Checking eigenvalues", None, None);
+            let mut energy: Vec<&Path> = files.iter().map(PathBuf::as_path).collect();
+            energy.sort_by_key(|path| wien2k::split_number(path));
+            let kpoints = wien2k::read_energy(files)?;
+            let klist_text =
+                fs::read_to_string(klist).map_err(ReciprocalError::io(IoOp::Read, klist))?;
+            let points = wien2k::parse_klist(&klist_text, klist)?;
+            let first = energy.first().copied().unwrap_or(klist);
+            wien2k::align_to_klist(kpoints, &points, first)?;
+
+            let sources = energy
+                .iter()
+                .enumerate()
+                .map(|(i, path)| (format!("data.energy{}_{}", suffix, i + 1), *path))
+                .collect();
+            Ok((sources, Some(points)))
+        }
+    }
+}
+
+impl ProjectStore {
+    fn fermi_surfaces_dir(&self, project_id: &ProjectId) -> Result<PathBuf> {
+        ensure_dir(self.project_dir(project_id)?.join("fermi_surfaces"))
+    }
+
+    /// Directory of an existing Fermi surface.
+    fn fermi_surface_dir(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+    ) -> Result<PathBuf> {
+        let fermi_path = self.fermi_surfaces_dir(project_id)?.join(fermi_surface_id);
+        if !fermi_path.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::FermiSurface,
+                fermi_surface_id.clone(),
+            ));
+        }
+        Ok(fermi_path)
+    }
+
+    pub fn import_fermi_surface(
+        &self,
+        project_id: &ProjectId,
+        name: String,
+        files: FermiSurfaceFiles<'_>,
+        case_name: String,
+        options: ImportOptions,
+        progress: &Progress,
+    ) -> Result<FermiSurfaceInfo> {
+        let fermi_dir = self.fermi_surfaces_dir(project_id)?;
+        let kind = match files.eigenvalues {
+            Eigenvalues::Output1(_) => FermiSurfaceKind::Wien2k,
+            Eigenvalues::Energy { .. } => FermiSurfaceKind::Wien2kEnergy,
+        };
+        let (mut sources, klist) = eigenvalue_sources(files.eigenvalues, "", progress)?;
+        let energy_files = if klist.is_some() { sources.len() } else { 0 };
+        if let Eigenvalues::Energy { klist, .. } = files.eigenvalues {
+            sources.push(("data.klist".to_string(), klist));
+        }
+
+        let (spin, energy_files_dn) = match files.down {
+            Some(down) => {
+                let (down_sources, down_klist) = match (files.eigenvalues, down) {
+                    (Eigenvalues::Output1(_), Eigenvalues::Output1(_))
+                    | (Eigenvalues::Energy { .. }, Eigenvalues::Energy { .. }) => {
+                        eigenvalue_sources(down, "_dn", progress)?
+                    }
+                    _ => {
+                        return Err(ReciprocalError::internal(
+                            "Both spin channels must be read from the same kind of file",
+                        ))
+                    }
+                };
+                if down_klist != klist {
+                    return Err(ReciprocalError::internal(
+                        "The spin channels' energy files are on different k-lists",
+                    ));
+                }
+                let count = if down_klist.is_some() {
+                    down_sources.len()
+                } else {
+                    0
+                };
+                sources.extend(down_sources);
+                (Spin::Polarized, count)
+            }
+            None => (Spin::Unpolarized, 0),
+        };
+        sources.extend([
+            ("data.output2".to_string(), files.output2),
+            ("data.outputkgen".to_string(), files.outputkgen),
+            ("data.struct".to_string(), files.struct_file),
+        ]);
+        let sources: Vec<(&str, &Path)> = sources
+            .iter()
+            .map(|(name, path)| (name.as_str(), *path))
+            .collect();
+        let (blobs, linked) = self.store_files(&sources, options.link, progress)?;
+
+        let id = DatasetId::new().to_string();
+        let fermi_path = fermi_dir.join(&id);
+        fs::create_dir_all(&fermi_path)
+            .map_err(ReciprocalError::io(IoOp::CreateDir, &fermi_path))?;
+        self.write_provenance(&fermi_path, &sources, &blobs, &linked, options.parameters)?;
+
+        let info = FermiSurfaceInfo {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            id,
+            name,
+            created_at: Utc::now(),
+            case_name,
+            kind,
+            energy_files,
+            spin,
+            energy_files_dn,
+            files: blobs,
+            linked,
+        };
+
+        // Save metadata
+        let info_path = fermi_path.join("info.json");
+        atomic::write_json(&info_path, &info)?;
+
+        Ok(info)
+    }
+
+    /// Imports a Fermi surface from an XCrysDen `.bxsf` band grid, which is
+    /// parsed first so that a file that can't be drawn is refused.
+    pub fn import_bxsf_fermi_surface(
+        &self,
+        project_id: &ProjectId,
+        name: String,
+        source: &Path,
+        options: ImportOptions,
+        progress: &Progress,
+    ) -> Result<FermiSurfaceInfo> {
+        let fermi_dir = self.fermi_surfaces_dir(project_id)?;
+        progress.phase("Checking band grid", None, None);
+        bxsf::read_bxsf(source)?;
+
+        let sources = [("data.bxsf", source)];
+        let (blobs, linked) = self.store_files(&sources, options.link, progress)?;
+
+        let id = DatasetId::new().to_string();
+        let fermi_path = fermi_dir.join(&id);
+        fs::create_dir_all(&fermi_path)
+            .map_err(ReciprocalError::io(IoOp::CreateDir, &fermi_path))?;
+        self.write_provenance(&fermi_path, &sources, &blobs, &linked, options.parameters)?;
+
+        let case_name = source
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "case".to_string());
+        let info = FermiSurfaceInfo {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            id,
+            name,
+            created_at: Utc::now(),
+            case_name,
+            kind: FermiSurfaceKind::Bxsf,
+            energy_files: 0,
+            spin: Spin::Unpolarized,
+            energy_files_dn: 0,
+            files: blobs,
+            linked,
+        };
+
+        atomic::write_json(&fermi_path.join("info.json"), &info)?;
+
+        Ok(info)
+    }
+
+    pub fn list_fermi_surfaces(&self, project_id: &ProjectId) -> Result<Listing<FermiSurfaceInfo>> {
+        let fermi_dir = self.fermi_surfaces_dir(project_id)?;
+        let mut listing = listing::scan_metadata::<FermiSurfaceInfo>(&fermi_dir, "info.json")?;
+
+        // Sort by created_at descending
+        listing.items.sort_by_key(|info| Reverse(info.created_at));
+
+        Ok(listing)
+    }
+
+    pub fn fermi_surface_info(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+    ) -> Result<FermiSurfaceInfo> {
+        let fermi_path = self.fermi_surface_dir(project_id, fermi_surface_id)?;
+        atomic::read_json(&fermi_path.join("info.json"))
+    }
+
+    /// Contents of the Fermi surface's output1, output2, outputkgen and
+    /// struct files, in that order.
+    pub fn load_fermi_surface_files(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+        progress: &Progress,
+    ) -> Result<(String, String, String, String)> {
+        let fermi_path = self.fermi_surface_dir(project_id, fermi_surface_id)?;
+        let info: FermiSurfaceInfo = atomic::read_json(&fermi_path.join("info.json"))?;
+        if info.kind != FermiSurfaceKind::Wien2k || info.spin == Spin::Polarized {
+            return Err(ReciprocalError::internal(format!(
+                "Fermi surface {} has no single set of Wien2k files; load its energy grid instead",
+                fermi_surface_id
+            )));
+        }
+        let mut texts = self.read_data_files(
+            &fermi_path,
+            &info,
+            &[
+                "data.output1",
+                "data.output2",
+                "data.outputkgen",
+                "data.struct",
+            ],
+            progress,
+        )?;
+        let structure = texts.pop().unwrap_or_default();
+        let outputkgen = texts.pop().unwrap_or_default();
+        let output2 = texts.pop().unwrap_or_default();
+        let output1 = texts.pop().unwrap_or_default();
+        Ok((output1, output2, outputkgen, structure))
+    }
+
+    /// Every data file of the Fermi surface, whatever its kind, as
+    /// (name, contents) pairs.
+    pub fn load_fermi_surface_data(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+        progress: &Progress,
+    ) -> Result<Vec<(String, String)>> {
+        let fermi_path = self.fermi_surface_dir(project_id, fermi_surface_id)?;
+        let info: FermiSurfaceInfo = atomic::read_json(&fermi_path.join("info.json"))?;
+        let names = info.data_file_names();
+        let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+        let texts = self.read_data_files(&fermi_path, &info, &name_refs, progress)?;
+        Ok(names.into_iter().zip(texts).collect())
+    }
+
+    /// Contents of the named data files of a Fermi surface, in order.
+    fn read_data_files(
+        &self,
+        fermi_path: &Path,
+        info: &FermiSurfaceInfo,
+        names: &[&str],
+        progress: &Progress,
+    ) -> Result<Vec<String>> {
+        let paths = names
+            .iter()
+            .map(|name| self.data_file(fermi_path, &info.files, &info.linked, name))
+            .collect::<Result<Vec<_>>>()?;
+        progress.phase("Reading files", Some(total_size(&paths)), Some(paths.len()));
+        paths.iter().map(|path| read_text(path, progress)).collect()
+    }
+
+    /// Path of a BXSF Fermi surface's band grid file.
+    fn fermi_surface_bxsf(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+    ) -> Result<PathBuf> {
+        let fermi_path = self.fermi_surface_dir(project_id, fermi_surface_id)?;
+        let info: FermiSurfaceInfo = atomic::read_json(&fermi_path.join("info.json"))?;
+        if info.kind != FermiSurfaceKind::Bxsf {
+            return Err(ReciprocalError::internal(format!(
+                "Fermi surface {} has no band grid file",
+                fermi_surface_id
+            )));
+        }
+        self.data_file(&fermi_path, &info.files, &info.linked, "data.bxsf")
+    }
+
+    /// The Fermi surface's band energies on its full k-mesh, in eV. Wien2k
+    /// eigenvalues are unfolded from the irreducible k-points.
+    pub fn load_fermi_surface_grid(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+        progress: &Progress,
+    ) -> Result<BandGrid> {
+        let info = self.fermi_surface_info(project_id, fermi_surface_id)?;
+        if info.kind == FermiSurfaceKind::Bxsf {
+            let path = self.fermi_surface_bxsf(project_id, fermi_surface_id)?;
+            progress.phase("Reading files", Some(total_size(&[&path])), Some(1));
+            let text = read_text(&path, progress)?;
+            progress.phase("Parsing band grid", None, None);
+            return bxsf::parse(&text, &path);
+        }
+
+        let fermi_path = self.fermi_surface_dir(project_id, fermi_surface_id)?;
+        let file = |name: &str| {
+            let name = name.trim_start_matches("data.");
+            PathBuf::from(format!("{}.{}", info.case_name, name))
+        };
+        // Everything but the struct file.
+        let mut names = info.data_file_names();
+        names.pop();
+        let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+        let texts = self.read_data_files(&fermi_path, &info, &name_refs, progress)?;
+        progress.phase("Building band grid", None, None);
+        let mut texts: HashMap<String, String> = names.into_iter().zip(texts).collect();
+        let mut take = |name: &str| texts.remove(name).unwrap_or_default();
+
+        let fermi_energy = wien2k::parse_fermi_energy(&take("data.output2"), &file("output2"))?;
+        let mesh = wien2k::parse_outputkgen(&take("data.outputkgen"), &file("outputkgen"))?;
+        let klist = match info.kind {
+            FermiSurfaceKind::Wien2kEnergy => {
+                Some(wien2k::parse_klist(&take("data.klist"), &file("klist"))?)
+            }
+            _ => None,
+        };
+
+        // Both channels share the one Fermi energy and mesh; their bands
+        // are tagged with the channel they belong to.
+        let channels = match info.spin {
+            Spin::Unpolarized => vec![SpinChannel::Up],
+            Spin::Polarized => vec![SpinChannel::Up, SpinChannel::Down],
+        };
+        let mut grid: Option<BandGrid> = None;
+        for channel in channels {
+            let names = info.eigenvalue_file_names(channel);
+            let first = file(names.first().map_or("output1", String::as_str));
+            let energies = match &klist {
+                Some(points) => {
+                    let mut kpoints = Vec::new();
+                    for name in &names {
+                        kpoints.extend(wien2k::parse_energy(&take(name), &file(name))?);
+                    }
+                    let kpoints = wien2k::align_to_klist(kpoints, points, &first)?;
+                    wien2k::eigenvalues(&kpoints)
+                }
+                None => wien2k::parse_output1(&take(&names[0])),
+            };
+            if energies.is_empty() {
+                return Err(ReciprocalError::Parse {
+                    file: first,
+                    line: None,
+                    message: "No eigenvalues found".to_string(),
+                });
+            }
+            let mut channel_grid = wien2k::band_grid(&energies, fermi_energy, &mesh);
+            if info.spin == Spin::Polarized {
+                channel_grid.spins = vec![channel; channel_grid.bands.len()];
+            }
+            match &mut grid {
+                Some(grid) => {
+                    grid.bands.extend(channel_grid.bands);
+                    grid.spins.extend(channel_grid.spins);
+                }
+                None => grid = Some(channel_grid),
+            }
+        }
+        grid.ok_or_else(|| ReciprocalError::internal("No spin channels to load"))
+    }
+
+    /// Writes the chosen bands of the Fermi surface's grid to `dest`, or the
+    /// bands that cross the Fermi energy when none are chosen.
+    #[allow(clippy::too_many_arguments)]
+    pub fn export_fermi_surface_grid(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+        dest: &Path,
+        format: GridFormat,
+        bands: Option<Vec<usize>>,
+        colour: Option<GridColour>,
+        progress: &Progress,
+    ) -> Result<()> {
+        let info = self.fermi_surface_info(project_id, fermi_surface_id)?;
+        let grid = self.load_fermi_surface_grid(project_id, fermi_surface_id, progress)?;
+        let bands = bands.unwrap_or_else(|| grid.crossing_bands());
+        grid.check_bands(&bands)?;
+        progress.check()?;
+        progress.phase("Writing band grid", None, None);
+        let text = match format {
+            GridFormat::Bxsf => bxsf::to_text(&grid, &bands, &info.name),
+            GridFormat::Frmsf => frmsf::to_text(&grid, &bands, colour),
+        };
+        atomic::write_atomic(dest, text.as_bytes())
+    }
+
+    pub fn delete_fermi_surface(
+        &self,
+        project_id: &ProjectId,
+        fermi_surface_id: &DatasetId,
+    ) -> Result<TrashEntry> {
+        let fermi_path = self.fermi_surface_dir(project_id, fermi_surface_id)?;
+
+        let name = item_name(&fermi_path.join("info.json"), fermi_surface_id.as_str());
+        trash::move_to_trash(
+            &self.trash_dir(),
+            &fermi_path,
+            ResourceKind::FermiSurface,
+            fermi_surface_id.as_str(),
+            Some(project_id.as_str()),
+            name,
+        )
+    }
+}
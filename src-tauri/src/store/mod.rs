@@ -0,0 +1,656 @@
+//! The on-disk project library.
+//!
+//! `ProjectStore` owns every path under the library root (the app data
+//! directory in the GUI). Nothing here depends on Tauri, so the same store
+//! can back the desktop app, other binaries and tests against a temp
+//! directory.
+
+mod band_cache;
+mod band_structures;
+mod fermi_surfaces;
+mod repair;
+mod structures;
+
+pub use band_structures::{BandPathPoint, BandStructureData, BandStructureInfo};
+pub use fermi_surfaces::{Eigenvalues, FermiSurfaceFiles, FermiSurfaceInfo, FermiSurfaceKind};
+pub use repair::RepairReport;
+pub use structures::StructureInfo;
+
+use crate::archive::{self, ArchiveManifest};
+use crate::atomic;
+use crate::blobs::{self, BlobFiles, VerifyReport};
+use crate::cif::{self, CrystalData};
+use crate::error::{IoOp, ReciprocalError, ResourceKind, Result};
+use crate::ids::{DatasetId, ProjectId, TrashId};
+use crate::jobs::Progress;
+use crate::links::{self, LinkCheck, LinkState, LinkStatus, LinkedFile, LinkedFiles};
+use crate::listing::{self, Diagnostic, Listing};
+use crate::migrations::{self, CURRENT_SCHEMA_VERSION};
+use crate::provenance::{Parameters, Provenance, PROVENANCE_FILE};
+use crate::settings::{self, LibrarySettings};
+use crate::trash::{self, TrashEntry};
+use crate::wien2k::Wien2kStruct;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub id: String,
+    pub name: String,
+    pub formula: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_opened_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub has_cif: bool,
+    pub cif_filename: Option<String>,
+}
+
+/// How an import stores its files.
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub parameters: Parameters,
+    /// Reference the source files where they are instead of copying them
+    /// into the library, for case directories too large to duplicate.
+    pub link: bool,
+}
+
+/// Whether a dataset holds one set of bands or one per spin channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin {
+    #[default]
+    Unpolarized,
+    /// Majority (up) and minority (down) channels, as Wien2k writes them
+    /// for a spin-polarized run with `-up` and `-dn`.
+    Polarized,
+}
+
+/// Bundle identifier from tauri.conf.json. Tauri's `app_data_dir` is the
+/// platform data directory joined with this.
+pub const APP_IDENTIFIER: &str = "com.jakescally.reciprocal";
+
+/// A project library rooted at a directory.
+#[derive(Debug, Clone)]
+pub struct ProjectStore {
+    root: PathBuf,
+}
+
+/// Display name for a trashed item, read from its metadata if possible.
+fn item_name(metadata_path: &Path, fallback: &str) -> String {
+    atomic::read_json::<serde_json::Value>(metadata_path)
+        .ok()
+        .and_then(|value| value.get("name")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Combined size of the files at `paths`, skipping any that can't be read.
+fn total_size<P: AsRef<Path>>(paths: &[P]) -> u64 {
+    paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Reads a data file as text, counting it towards `progress`.
+fn read_text(path: &Path, progress: &Progress) -> Result<String> {
+    progress.check()?;
+    let file = File::open(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+    let mut text = String::new();
+    progress
+        .reader(file)
+        .read_to_string(&mut text)
+        .map_err(ReciprocalError::io(IoOp::Read, path))?;
+    progress.file_done();
+    Ok(text)
+}
+
+fn ensure_dir(dir: PathBuf) -> Result<PathBuf> {
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(ReciprocalError::io(IoOp::CreateDir, &dir))?;
+    }
+    Ok(dir)
+}
+
+impl ProjectStore {
+    /// A store over the library at `root`. Directories are created lazily,
+    /// so `root` doesn't need to exist yet.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ProjectStore { root: root.into() }
+    }
+
+    /// The library the desktop app uses, if the platform has a data directory.
+    pub fn default_root() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn projects_dir(&self) -> Result<PathBuf> {
+        ensure_dir(self.root.join("projects"))
+    }
+
+    fn trash_dir(&self) -> PathBuf {
+        self.root.join("trash")
+    }
+
+    fn staging_dir(&self) -> PathBuf {
+        self.root.join("staging")
+    }
+
+    fn blobs_dir(&self) -> PathBuf {
+        self.root.join(blobs::BLOBS_DIR)
+    }
+
+    /// Path of a dataset's data file: its blob if the metadata references
+    /// one, the original file if it was linked in place, otherwise the copy
+    /// inside the dataset directory, which is where datasets imported before
+    /// the blob store keep their files.
+    fn data_file(
+        &self,
+        dataset_dir: &Path,
+        files: &BlobFiles,
+        linked: &LinkedFiles,
+        name: &str,
+    ) -> Result<PathBuf> {
+        if let Some(blob) = files.get(name) {
+            return blobs::blob_path(&self.blobs_dir(), &blob.sha256);
+        }
+        match linked.get(name) {
+            Some(link) => self.resolve_link(dataset_dir, name, link),
+            None => Ok(dataset_dir.join(name)),
+        }
+    }
+
+    /// Path of a linked file, if it is still the file that was imported.
+    fn resolve_link(&self, dataset_dir: &Path, name: &str, link: &LinkedFile) -> Result<PathBuf> {
+        let state = link.check()?;
+        match state {
+            LinkState::Unchanged => Ok(link.path.clone()),
+            LinkState::Touched(updated) => {
+                // Record the new mtime so the file isn't re-hashed on every load.
+                let info_path = dataset_dir.join("info.json");
+                let mut linked = links::read(&info_path)?;
+                let path = updated.path.clone();
+                linked.insert(name.to_string(), updated);
+                links::write(&info_path, &linked)?;
+                Ok(path)
+            }
+            LinkState::Missing | LinkState::Changed => Err(ReciprocalError::BrokenLink {
+                path: link.path.clone(),
+                name: name.to_string(),
+                status: state.status(),
+            }),
+        }
+    }
+
+    /// Stores each `(name, source)` file as a blob, or with `link` records
+    /// where it is instead.
+    fn store_files(
+        &self,
+        sources: &[(&str, &Path)],
+        link: bool,
+        progress: &Progress,
+    ) -> Result<(BlobFiles, LinkedFiles)> {
+        let phase = if link {
+            "Hashing files"
+        } else {
+            "Copying files"
+        };
+        let paths: Vec<&Path> = sources.iter().map(|(_, source)| *source).collect();
+        progress.phase(phase, Some(total_size(&paths)), Some(paths.len()));
+
+        let blobs_dir = self.blobs_dir();
+        let mut files = BlobFiles::new();
+        let mut linked = LinkedFiles::new();
+        for (name, source) in sources {
+            progress.check()?;
+            if link {
+                linked.insert(name.to_string(), LinkedFile::capture(source, progress)?);
+            } else {
+                files.insert(name.to_string(), blobs::put(&blobs_dir, source, progress)?);
+            }
+            progress.file_done();
+        }
+        Ok((files, linked))
+    }
+
+    /// Directory of an existing project, migrated to the current schema.
+    pub fn project_dir(&self, project_id: &ProjectId) -> Result<PathBuf> {
+        let project_dir = self.projects_dir()?.join(project_id);
+        if !project_dir.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::Project,
+                project_id.clone(),
+            ));
+        }
+        migrations::migrate_project_dir(&project_dir)?;
+        Ok(project_dir)
+    }
+
+    /// Records where a dataset's files were imported from. Written before
+    /// info.json, so a dataset that lists always has its provenance.
+    fn write_provenance(
+        &self,
+        dataset_dir: &Path,
+        sources: &[(&str, &Path)],
+        files: &BlobFiles,
+        linked: &LinkedFiles,
+        parameters: Parameters,
+    ) -> Result<()> {
+        let mut hashes = files.clone();
+        hashes.extend(
+            linked
+                .iter()
+                .map(|(name, link)| (name.clone(), link.blob_ref())),
+        );
+        let provenance = Provenance::capture(sources, &hashes, parameters);
+        atomic::write_json(&dataset_dir.join(PROVENANCE_FILE), &provenance)
+    }
+
+    /// Directory of a band structure or Fermi surface.
+    fn dataset_dir(&self, project_id: &ProjectId, dataset_id: &DatasetId) -> Result<PathBuf> {
+        let project_dir = self.project_dir(project_id)?;
+        ["band_structures", "fermi_surfaces"]
+            .iter()
+            .map(|kind_dir| project_dir.join(kind_dir).join(dataset_id))
+            .find(|dir| dir.exists())
+            .ok_or_else(|| ReciprocalError::not_found(ResourceKind::Dataset, dataset_id.clone()))
+    }
+
+    /// Import provenance of a band structure or Fermi surface. `None` for
+    /// datasets imported before provenance was recorded.
+    pub fn dataset_provenance(
+        &self,
+        project_id: &ProjectId,
+        dataset_id: &DatasetId,
+    ) -> Result<Option<Provenance>> {
+        let path = self
+            .dataset_dir(project_id, dataset_id)?
+            .join(PROVENANCE_FILE);
+        if !path.exists() && !atomic::backup_path(&path).exists() {
+            return Ok(None);
+        }
+        atomic::read_json(&path).map(Some)
+    }
+
+    // ============ Linked files ============
+
+    /// Checks each file a dataset links to against what was imported.
+    pub fn check_dataset_links(
+        &self,
+        project_id: &ProjectId,
+        dataset_id: &DatasetId,
+    ) -> Result<Vec<LinkCheck>> {
+        let info_path = self.dataset_dir(project_id, dataset_id)?.join("info.json");
+        let mut linked = links::read(&info_path)?;
+
+        let mut checks = Vec::new();
+        let mut touched = false;
+        for (name, link) in linked.iter_mut() {
+            let state = link.check()?;
+            let status = state.status();
+            if let LinkState::Touched(updated) = state {
+                *link = updated;
+                touched = true;
+            }
+            checks.push(LinkCheck {
+                name: name.clone(),
+                path: link.path.clone(),
+                status,
+            });
+        }
+
+        if touched {
+            links::write(&info_path, &linked)?;
+        }
+        Ok(checks)
+    }
+
+    /// Points a dataset's links at the files of the same names in
+    /// `directory`, e.g. after a case directory has moved. A file whose
+    /// contents differ from what was imported is left alone unless
+    /// `accept_changes` is set.
+    pub fn relink_dataset(
+        &self,
+        project_id: &ProjectId,
+        dataset_id: &DatasetId,
+        directory: &Path,
+        accept_changes: bool,
+    ) -> Result<Vec<LinkCheck>> {
+        let info_path = self.dataset_dir(project_id, dataset_id)?.join("info.json");
+        let mut linked = links::read(&info_path)?;
+
+        let mut checks = Vec::new();
+        for (name, link) in linked.iter_mut() {
+            let candidate = match link.path.file_name() {
+                Some(file_name) => directory.join(file_name),
+                None => continue,
+            };
+            let (path, status) = if !candidate.is_file() {
+                (candidate, LinkStatus::Missing)
+            } else {
+                let relinked = LinkedFile::capture(&candidate, &Progress::none())?;
+                let path = relinked.path.clone();
+                if relinked.sha256 == link.sha256 || accept_changes {
+                    *link = relinked;
+                    (path, LinkStatus::Ok)
+                } else {
+                    (path, LinkStatus::Changed)
+                }
+            };
+            checks.push(LinkCheck {
+                name: name.clone(),
+                path,
+                status,
+            });
+        }
+
+        links::write(&info_path, &linked)?;
+        Ok(checks)
+    }
+
+    // ============ Projects ============
+
+    pub fn load_projects(&self) -> Result<Listing<Project>> {
+        let projects_dir = self.projects_dir()?;
+
+        // Upgrade old projects first; one that can't be upgraded is still listed
+        // if its project.json parses, so the failure only shows as a diagnostic.
+        let mut migration_failures = Vec::new();
+        for entry in fs::read_dir(&projects_dir)
+            .map_err(ReciprocalError::io(IoOp::ReadDir, &projects_dir))?
+            .flatten()
+        {
+            let path = entry.path();
+            if path.is_dir() {
+                if let Err(e) = migrations::migrate_project_dir(&path) {
+                    migration_failures.push(Diagnostic::new(&path, e.to_string()));
+                }
+            }
+        }
+
+        // Each project is a directory containing project.json
+        let mut listing = listing::scan_metadata::<Project>(&projects_dir, "project.json")?;
+        listing.diagnostics.extend(migration_failures);
+
+        // Sort by last_opened_at descending (most recently opened first), fall back to created_at
+        listing
+            .items
+            .sort_by_key(|p| Reverse(p.last_opened_at.unwrap_or(p.created_at)));
+
+        Ok(listing)
+    }
+
+    pub fn load_project(&self, project_id: &ProjectId) -> Result<Project> {
+        let project_dir = self.project_dir(project_id)?;
+        atomic::read_json(&project_dir.join("project.json"))
+    }
+
+    pub fn create_project(&self, name: String, formula: String) -> Result<Project> {
+        let projects_dir = self.projects_dir()?;
+
+        let now = Utc::now();
+        let project = Project {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            id: ProjectId::new().to_string(),
+            name,
+            formula,
+            created_at: now,
+            updated_at: now,
+            last_opened_at: Some(now),
+            has_cif: false,
+            cif_filename: None,
+        };
+
+        // Create project directory
+        let project_dir = projects_dir.join(&project.id);
+        fs::create_dir_all(&project_dir)
+            .map_err(ReciprocalError::io(IoOp::CreateDir, &project_dir))?;
+
+        // Save project.json inside the directory
+        let project_file = project_dir.join("project.json");
+        atomic::write_json(&project_file, &project)?;
+
+        Ok(project)
+    }
+
+    pub fn update_project(&self, project: Project) -> Result<Project> {
+        let project_id = ProjectId::parse(&project.id)?;
+        let project_dir = self.project_dir(&project_id)?;
+        let project_file = project_dir.join("project.json");
+
+        if !project_file.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::Project,
+                project.id,
+            ));
+        }
+
+        // The frontend doesn't round-trip schema_version, so stamp it here.
+        let mut updated_project = project;
+        updated_project.schema_version = CURRENT_SCHEMA_VERSION;
+        updated_project.updated_at = Utc::now();
+
+        atomic::write_json(&project_file, &updated_project)?;
+
+        Ok(updated_project)
+    }
+
+    pub fn mark_project_opened(&self, project_id: &ProjectId) -> Result<Project> {
+        let project_dir = self.project_dir(project_id)?;
+        let project_file = project_dir.join("project.json");
+
+        if !project_file.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::Project,
+                project_id.clone(),
+            ));
+        }
+
+        // Read existing project
+        let mut project: Project = atomic::read_json(&project_file)?;
+
+        // Update last_opened_at
+        project.last_opened_at = Some(Utc::now());
+
+        // Save updated project
+        atomic::write_json(&project_file, &project)?;
+
+        Ok(project)
+    }
+
+    pub fn delete_project(&self, project_id: &ProjectId) -> Result<TrashEntry> {
+        let project_dir = self.projects_dir()?.join(project_id);
+
+        if !project_dir.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::Project,
+                project_id.clone(),
+            ));
+        }
+
+        let name = item_name(&project_dir.join("project.json"), project_id.as_str());
+        trash::move_to_trash(
+            &self.trash_dir(),
+            &project_dir,
+            ResourceKind::Project,
+            project_id.as_str(),
+            None,
+            name,
+        )
+    }
+
+    // ============ Crystal Structure ============
+
+    fn cif_path(&self, project_id: &ProjectId) -> Result<PathBuf> {
+        let cif_path = self.project_dir(project_id)?.join("structure.cif");
+
+        if !cif_path.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::File,
+                "structure.cif",
+            ));
+        }
+
+        Ok(cif_path)
+    }
+
+    pub fn read_cif_file(&self, project_id: &ProjectId) -> Result<String> {
+        let cif_path = self.cif_path(project_id)?;
+        fs::read_to_string(&cif_path).map_err(ReciprocalError::io(IoOp::Read, &cif_path))
+    }
+
+    /// Parses the project's structure.cif.
+    pub fn parse_cif_file(&self, project_id: &ProjectId) -> Result<CrystalData> {
+        cif::read_crystal_data(&self.cif_path(project_id)?)
+    }
+
+    /// The project's structure as last saved by the app, or as read from
+    /// structure.cif if it never was.
+    pub fn crystal_data(&self, project_id: &ProjectId) -> Result<CrystalData> {
+        let data_path = self.project_dir(project_id)?.join("cif_data.json");
+        if data_path.exists() {
+            return atomic::read_json(&data_path);
+        }
+        self.parse_cif_file(project_id)
+    }
+
+    /// Writes the project's structure to `dest` as a CIF file.
+    pub fn export_cif(&self, project_id: &ProjectId, dest: &Path) -> Result<()> {
+        let project = self.load_project(project_id)?;
+        let data = self.crystal_data(project_id)?;
+        atomic::write_atomic(dest, cif::write_cif(&data, &project.name).as_bytes())
+    }
+
+    /// Writes a Wien2k case.struct for the project's structure to `dest`.
+    pub fn export_struct(&self, project_id: &ProjectId, dest: &Path) -> Result<()> {
+        let project = self.load_project(project_id)?;
+        let data = self.crystal_data(project_id)?;
+        let source = self.cif_path(project_id)?;
+        let structure = Wien2kStruct::from_crystal_data(&data, &project.name, &source)?;
+        atomic::write_atomic(dest, structure.to_text().as_bytes())
+    }
+
+    pub fn save_crystal_data(&self, project_id: &ProjectId, crystal_data_json: &str) -> Result<()> {
+        let project_dir = self.project_dir(project_id)?;
+        let data_path = project_dir.join("cif_data.json");
+
+        atomic::write_atomic(&data_path, crystal_data_json.as_bytes())
+    }
+
+    pub fn load_crystal_data(&self, project_id: &ProjectId) -> Result<Option<String>> {
+        let project_dir = self.project_dir(project_id)?;
+        let data_path = project_dir.join("cif_data.json");
+
+        if !data_path.exists() {
+            return Ok(None);
+        }
+
+        atomic::read_json_text(&data_path).map(Some)
+    }
+
+    // ============ Archives ============
+
+    pub fn export_project_archive(
+        &self,
+        project_id: &ProjectId,
+        dest: &Path,
+        progress: &Progress,
+    ) -> Result<ArchiveManifest> {
+        let project_dir = self.project_dir(project_id)?;
+        archive::export_project(&project_dir, &self.blobs_dir(), dest, progress)
+    }
+
+    pub fn import_project_archive(&self, path: &Path, progress: &Progress) -> Result<Project> {
+        archive::import_project(
+            path,
+            &self.projects_dir()?,
+            &self.blobs_dir(),
+            &self.staging_dir(),
+            progress,
+        )
+    }
+
+    // ============ Trash ============
+
+    pub fn list_trash(&self) -> Result<Listing<TrashEntry>> {
+        trash::list(&self.trash_dir())
+    }
+
+    pub fn restore_from_trash(&self, trash_id: &TrashId) -> Result<TrashEntry> {
+        let projects_dir = self.projects_dir()?;
+        let entry = trash::restore(&self.trash_dir(), &projects_dir, trash_id)?;
+
+        // A project may have been trashed by an older build.
+        if entry.kind == ResourceKind::Project {
+            migrations::migrate_project_dir(&projects_dir.join(&entry.item_id))?;
+        }
+
+        Ok(entry)
+    }
+
+    pub fn empty_trash(&self) -> Result<usize> {
+        let removed = trash::empty(&self.trash_dir())?;
+        self.collect_garbage()?;
+        Ok(removed)
+    }
+
+    /// Purges trashed items older than the configured retention period.
+    pub fn purge_trash(&self) -> Result<usize> {
+        let removed = match self.settings()?.trash_retention_days {
+            Some(days) => trash::purge_expired(&self.trash_dir(), days)?,
+            None => 0,
+        };
+        if removed > 0 {
+            self.collect_garbage()?;
+        }
+        Ok(removed)
+    }
+
+    // ============ Blobs ============
+
+    fn blob_references(&self) -> Result<(Vec<blobs::Reference>, Vec<Diagnostic>)> {
+        blobs::find_references(&[&self.root.join("projects"), &self.trash_dir()])
+    }
+
+    /// Re-hashes every stored blob and checks every dataset's references.
+    pub fn verify_library(&self, progress: &Progress) -> Result<VerifyReport> {
+        let (references, unreadable) = self.blob_references()?;
+        blobs::verify(&self.blobs_dir(), &references, unreadable, progress)
+    }
+
+    /// Removes blobs no dataset refers to. Skipped while any dataset
+    /// metadata is unreadable, since its references can't be known.
+    pub fn collect_garbage(&self) -> Result<usize> {
+        let (references, unreadable) = self.blob_references()?;
+        if !unreadable.is_empty() {
+            return Ok(0);
+        }
+        blobs::collect_garbage(&self.blobs_dir(), &references)
+    }
+
+    // ============ Settings ============
+
+    pub fn settings(&self) -> Result<LibrarySettings> {
+        settings::load(&self.root)
+    }
+
+    pub fn update_settings(&self, settings: &LibrarySettings) -> Result<LibrarySettings> {
+        ensure_dir(self.root.clone())?;
+        settings::save(&self.root, settings)?;
+        // Apply a shortened retention period straight away.
+        self.purge_trash()?;
+        self.settings()
+    }
+}
@@ -0,0 +1,401 @@
+use super::band_cache::CACHE_FILE;
+use super::{
+    BandStructureInfo, FermiSurfaceInfo, FermiSurfaceKind, Project, ProjectStore, Spin,
+    StructureInfo,
+};
+use crate::atomic;
+use crate::blobs::{self, BlobFiles, BlobRef};
+use crate::error::{IoOp, ReciprocalError, Result};
+use crate::ids::ProjectId;
+use crate::links::{LinkedFile, LinkedFiles};
+use crate::listing::Diagnostic;
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use crate::provenance::{Provenance, PROVENANCE_FILE};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairReport {
+    pub project: Project,
+    /// Metadata files that were rewritten, and why.
+    pub repaired: Vec<Diagnostic>,
+    /// Datasets whose data files are gone, so their metadata can't be rebuilt.
+    pub unrecoverable: Vec<Diagnostic>,
+}
+
+fn modified_at(path: &Path) -> DateTime<Utc> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now())
+}
+
+/// Rebuilds project.json from what is left in the project directory, taking
+/// the name and formula from cif_data.json when it is still readable.
+fn rebuild_project(project_dir: &Path, project_id: &str, has_cif: bool) -> Project {
+    let crystal_data =
+        atomic::read_json::<serde_json::Value>(&project_dir.join("cif_data.json")).ok();
+    let field = |key: &str| {
+        crystal_data
+            .as_ref()
+            .and_then(|data| data.get(key))
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    };
+
+    let formula = field("formula_sum")
+        .or_else(|| field("formula_structural"))
+        .unwrap_or_default();
+    let name = field("chemical_name_common")
+        .or_else(|| (!formula.is_empty()).then(|| formula.clone()))
+        .unwrap_or_else(|| "Recovered project".to_string());
+    let created_at = modified_at(project_dir);
+
+    Project {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        id: project_id.to_string(),
+        name,
+        formula,
+        created_at,
+        updated_at: Utc::now(),
+        last_opened_at: None,
+        has_cif,
+        cif_filename: has_cif.then(|| "structure.cif".to_string()),
+    }
+}
+
+/// What is left of a dataset whose info.json is unreadable.
+struct Remains {
+    id: String,
+    created_at: DateTime<Utc>,
+    /// Stored names of the data files that are still there, e.g. `data.qtl`.
+    names: BTreeSet<String>,
+    files: BlobFiles,
+    linked: LinkedFiles,
+    /// File name each data file was imported from, when it was recorded.
+    originals: BTreeMap<String, String>,
+}
+
+impl Remains {
+    fn has(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    /// The name `name` was imported under, or `name` itself if that wasn't
+    /// recorded.
+    fn original(&self, name: &str) -> String {
+        self.originals
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+/// The data files of a dataset, from its provenance.json: each
+/// is in the blob store, or still at the path it was imported from, where
+/// it is linked again. `Err` names the first file that is neither.
+fn remains_from_provenance(
+    id: String,
+    provenance: Provenance,
+    blobs_dir: &Path,
+) -> std::result::Result<Remains, String> {
+    let mut remains = Remains {
+        id,
+        created_at: provenance.imported_at,
+        names: BTreeSet::new(),
+        files: BlobFiles::new(),
+        linked: LinkedFiles::new(),
+        originals: BTreeMap::new(),
+    };
+    for source in provenance.sources {
+        if let Some(original) = source.path.file_name() {
+            let original = original.to_string_lossy().into_owned();
+            remains.originals.insert(source.name.clone(), original);
+        }
+        let blob = BlobRef {
+            sha256: source.sha256,
+            size: source.size,
+        };
+        if blobs::blob_path(blobs_dir, &blob.sha256).is_ok_and(|blob| blob.exists()) {
+            remains.files.insert(source.name.clone(), blob);
+        } else if fs::metadata(&source.path).is_ok_and(|m| m.len() == blob.size) {
+            let link = LinkedFile {
+                path: source.path,
+                size: blob.size,
+                modified_at: source.modified_at,
+                sha256: blob.sha256,
+            };
+            remains.linked.insert(source.name.clone(), link);
+        } else {
+            return Err(source.name);
+        }
+        remains.names.insert(source.name);
+    }
+    Ok(remains)
+}
+
+/// The data files kept in the directory of a dataset imported before the
+/// blob store, which has no provenance.json.
+fn remains_in_dir(path: &Path, id: String) -> Result<Remains> {
+    let mut names = BTreeSet::new();
+    let mut created_at = None;
+    for entry in fs::read_dir(path).map_err(ReciprocalError::io(IoOp::ReadDir, path))? {
+        let entry = entry.map_err(ReciprocalError::io(IoOp::ReadDir, path))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with("info.json") || name == CACHE_FILE || !entry.path().is_file() {
+            continue;
+        }
+        let modified = modified_at(&entry.path());
+        created_at = Some(created_at.map_or(modified, |at: DateTime<Utc>| at.min(modified)));
+        names.insert(name);
+    }
+    Ok(Remains {
+        id,
+        created_at: created_at.unwrap_or_else(|| modified_at(path)),
+        names,
+        files: BlobFiles::new(),
+        linked: LinkedFiles::new(),
+        originals: BTreeMap::new(),
+    })
+}
+
+/// Rewrites info.json for every dataset under `dir` whose metadata is
+/// unreadable, from what `rebuild` makes of its remaining data files.
+/// `rebuild` gives the name of a required file that is gone instead.
+fn repair_datasets<T: Serialize + DeserializeOwned>(
+    dir: &Path,
+    blobs_dir: &Path,
+    rebuild: impl Fn(Remains) -> std::result::Result<T, String>,
+    repaired: &mut Vec<Diagnostic>,
+    unrecoverable: &mut Vec<Diagnostic>,
+) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir).map_err(ReciprocalError::io(IoOp::ReadDir, dir))? {
+        let path = entry
+            .map_err(ReciprocalError::io(IoOp::ReadDir, dir))?
+            .path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let info_path = path.join("info.json");
+        let error = match atomic::read_json::<T>(&info_path) {
+            Ok(_) => continue,
+            Err(e) => e,
+        };
+
+        let id = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let provenance_path = path.join(PROVENANCE_FILE);
+        let remains = if provenance_path.exists() || atomic::backup_path(&provenance_path).exists()
+        {
+            match atomic::read_json::<Provenance>(&provenance_path) {
+                Ok(provenance) => remains_from_provenance(id, provenance, blobs_dir),
+                Err(e) => {
+                    unrecoverable.push(Diagnostic::new(&provenance_path, e.to_string()));
+                    continue;
+                }
+            }
+        } else {
+            Ok(remains_in_dir(&path, id)?)
+        };
+
+        match remains.and_then(&rebuild) {
+            Ok(info) => {
+                atomic::write_json(&info_path, &info)?;
+                repaired.push(Diagnostic::new(
+                    &info_path,
+                    format!("Rebuilt dataset metadata ({})", error),
+                ));
+            }
+            Err(missing) => {
+                unrecoverable.push(Diagnostic::new(&path, format!("Missing {}", missing)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The first of `names` that `remains` doesn't have.
+fn first_missing(remains: &Remains, names: &[impl AsRef<str>]) -> std::result::Result<(), String> {
+    match names.iter().find(|name| !remains.has(name.as_ref())) {
+        Some(name) => Err(name.as_ref().to_string()),
+        None => Ok(()),
+    }
+}
+
+/// A band structure is spin-polarized if it has a `data.qtl_dn`.
+fn rebuild_band_structure(remains: Remains) -> std::result::Result<BandStructureInfo, String> {
+    first_missing(&remains, &["data.qtl", "data.klist_band"])?;
+    let spin = if remains.has("data.qtl_dn") {
+        Spin::Polarized
+    } else {
+        Spin::Unpolarized
+    };
+    Ok(BandStructureInfo {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        name: "Recovered band structure".to_string(),
+        created_at: remains.created_at,
+        qtl_filename: remains.original("data.qtl"),
+        klist_filename: remains.original("data.klist_band"),
+        spin,
+        qtl_dn_filename: (spin == Spin::Polarized).then(|| remains.original("data.qtl_dn")),
+        id: remains.id,
+        files: remains.files,
+        linked: remains.linked,
+    })
+}
+
+/// The kind and spin of a Fermi surface follow from its data files: a
+/// `data.bxsf`, `data.energy_N` files in place of `data.output1`, and `_dn`
+/// files for the down channel. Every file that kind needs must be there.
+fn rebuild_fermi_surface(remains: Remains) -> std::result::Result<FermiSurfaceInfo, String> {
+    let count = |prefix: &str| {
+        (1..)
+            .take_while(|i| remains.has(&format!("{}{}", prefix, i)))
+            .count()
+    };
+    let kind = if remains.has("data.bxsf") {
+        FermiSurfaceKind::Bxsf
+    } else if remains.has("data.energy_1") {
+        FermiSurfaceKind::Wien2kEnergy
+    } else {
+        FermiSurfaceKind::Wien2k
+    };
+    let spin = if kind != FermiSurfaceKind::Bxsf
+        && (remains.has("data.output1_dn") || remains.has("data.energy_dn_1"))
+    {
+        Spin::Polarized
+    } else {
+        Spin::Unpolarized
+    };
+    let (energy_files, energy_files_dn) = match kind {
+        FermiSurfaceKind::Wien2kEnergy => (count("data.energy_"), count("data.energy_dn_")),
+        _ => (0, 0),
+    };
+
+    let named_after = match kind {
+        FermiSurfaceKind::Bxsf => "data.bxsf",
+        _ => "data.struct",
+    };
+    let case_name = remains
+        .originals
+        .get(named_after)
+        .and_then(|original| Path::new(original).file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "case".to_string());
+
+    let mut info = FermiSurfaceInfo {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        id: String::new(),
+        name: "Recovered Fermi surface".to_string(),
+        created_at: remains.created_at,
+        case_name,
+        kind,
+        energy_files,
+        spin,
+        energy_files_dn,
+        files: Default::default(),
+        linked: Default::default(),
+    };
+    first_missing(&remains, &info.data_file_names())?;
+    info.id = remains.id;
+    info.files = remains.files;
+    info.linked = remains.linked;
+    Ok(info)
+}
+
+impl ProjectStore {
+    /// Rebuilds unreadable project and dataset metadata from the files that
+    /// are still on disk. A dataset's data files are found through its
+    /// provenance.json, or in its directory if it predates the blob store.
+    pub fn repair_project(&self, project_id: &ProjectId) -> Result<RepairReport> {
+        let project_dir = self.project_dir(project_id)?;
+        let project_file = project_dir.join("project.json");
+        let has_cif = project_dir.join("structure.cif").exists();
+
+        let mut repaired = Vec::new();
+        let mut unrecoverable = Vec::new();
+
+        let project = match atomic::read_json::<Project>(&project_file) {
+            Ok(mut project) => {
+                if project.has_cif != has_cif {
+                    project.has_cif = has_cif;
+                    project.cif_filename = if has_cif {
+                        project
+                            .cif_filename
+                            .or_else(|| Some("structure.cif".to_string()))
+                    } else {
+                        None
+                    };
+                    atomic::write_json(&project_file, &project)?;
+                    repaired.push(Diagnostic::new(
+                        &project_file,
+                        "Updated has_cif to match structure.cif",
+                    ));
+                }
+                project
+            }
+            Err(e) => {
+                let project = rebuild_project(&project_dir, project_id.as_str(), has_cif);
+                atomic::write_json(&project_file, &project)?;
+                repaired.push(Diagnostic::new(
+                    &project_file,
+                    format!("Rebuilt project metadata ({})", e),
+                ));
+                project
+            }
+        };
+
+        let blobs_dir = self.blobs_dir();
+        repair_datasets(
+            &project_dir.join("band_structures"),
+            &blobs_dir,
+            rebuild_band_structure,
+            &mut repaired,
+            &mut unrecoverable,
+        )?;
+
+        repair_datasets(
+            &project_dir.join("fermi_surfaces"),
+            &blobs_dir,
+            rebuild_fermi_surface,
+            &mut repaired,
+            &mut unrecoverable,
+        )?;
+
+        repair_datasets(
+            &project_dir.join("structures"),
+            &blobs_dir,
+            |remains| {
+                first_missing(&remains, &["structure.cif"])?;
+                Ok(StructureInfo {
+                    schema_version: CURRENT_SCHEMA_VERSION,
+                    name: "Recovered structure".to_string(),
+                    formula: String::new(),
+                    created_at: remains.created_at,
+                    cif_filename: "structure.cif".to_string(),
+                    id: remains.id,
+                })
+            },
+            &mut repaired,
+            &mut unrecoverable,
+        )?;
+
+        Ok(RepairReport {
+            project,
+            repaired,
+            unrecoverable,
+        })
+    }
+}
@@ -0,0 +1,242 @@
+use super::{ensure_dir, item_name, Project, ProjectStore};
+use crate::atomic;
+use crate::cif::{self, CrystalData, DataBlock};
+use crate::error::{IoOp, ReciprocalError, ResourceKind, Result};
+use crate::ids::{DatasetId, ProjectId};
+use crate::listing::{self, Listing};
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use crate::poscar::{self, Poscar};
+use crate::structure_file::{self, StructureFormat};
+use crate::trash::{self, TrashEntry};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A structure kept alongside the project's own structure.cif, imported
+/// from another data block of a multi-block CIF file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructureInfo {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub id: String,
+    /// The data block name, which is how refinement programs and
+    /// databases tell phases and temperatures apart.
+    pub name: String,
+    pub formula: String,
+    pub created_at: DateTime<Utc>,
+    pub cif_filename: String,
+}
+
+impl ProjectStore {
+    fn structures_dir(&self, project_id: &ProjectId) -> Result<PathBuf> {
+        ensure_dir(self.project_dir(project_id)?.join("structures"))
+    }
+
+    /// Directory of an existing structure.
+    fn structure_dir(&self, project_id: &ProjectId, structure_id: &DatasetId) -> Result<PathBuf> {
+        let structure_path = self.structures_dir(project_id)?.join(structure_id);
+        if !structure_path.exists() {
+            return Err(ReciprocalError::not_found(
+                ResourceKind::Structure,
+                structure_id.clone(),
+            ));
+        }
+        Ok(structure_path)
+    }
+
+    /// Stores `block` of the CIF `text` as a structure of its own.
+    fn add_structure(
+        &self,
+        project_id: &ProjectId,
+        text: &str,
+        block: &DataBlock,
+        cif_filename: &str,
+    ) -> Result<StructureInfo> {
+        let data = CrystalData::from_block(block);
+        let id = DatasetId::new().to_string();
+        let structure_path = self.structures_dir(project_id)?.join(&id);
+        fs::create_dir_all(&structure_path)
+            .map_err(ReciprocalError::io(IoOp::CreateDir, &structure_path))?;
+        atomic::write_atomic(
+            &structure_path.join("structure.cif"),
+            text[block.source.clone()].as_bytes(),
+        )?;
+
+        let info = StructureInfo {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            id,
+            name: block.name.clone(),
+            formula: data.formula_sum.unwrap_or_default(),
+            created_at: Utc::now(),
+            cif_filename: cif_filename.to_string(),
+        };
+        atomic::write_json(&structure_path.join("info.json"), &info)?;
+        Ok(info)
+    }
+
+    /// Imports the named data blocks of the CIF file at `source_path`. The
+    /// first becomes the project's structure.cif and the others are kept as
+    /// separate structures; with no names the whole file is copied as is.
+    pub fn import_cif_file(
+        &self,
+        project_id: &ProjectId,
+        source_path: &Path,
+        original_filename: String,
+        blocks: &[String],
+    ) -> Result<Project> {
+        let project_dir = self.project_dir(project_id)?;
+        let project_file = project_dir.join("project.json");
+
+        // Read existing project
+        let mut project: Project = atomic::read_json(&project_file)?;
+
+        let dest_path = project_dir.join("structure.cif");
+        if blocks.is_empty() {
+            // Copy CIF file to project directory as structure.cif
+            fs::copy(source_path, &dest_path)
+                .map_err(ReciprocalError::io(IoOp::Copy, source_path))?;
+        } else {
+            let text = cif::read_text(source_path)?;
+            let file = cif::parse(&text, source_path)?;
+            let chosen = blocks
+                .iter()
+                .map(|name| cif::structure_block(&file, name, source_path))
+                .collect::<Result<Vec<_>>>()?;
+
+            for (i, block) in chosen.into_iter().enumerate() {
+                if i == 0 {
+                    atomic::write_atomic(&dest_path, text[block.source.clone()].as_bytes())?;
+                } else {
+                    self.add_structure(project_id, &text, block, &original_filename)?;
+                }
+            }
+        }
+
+        // Update project metadata
+        project.has_cif = true;
+        project.cif_filename = Some(original_filename);
+        project.updated_at = Utc::now();
+
+        // Save updated project
+        atomic::write_json(&project_file, &project)?;
+
+        Ok(project)
+    }
+
+    /// Makes the structure in a POSCAR or CONTCAR file the project's
+    /// structure, stored as a CIF written from it.
+    pub fn import_poscar(
+        &self,
+        project_id: &ProjectId,
+        source_path: &Path,
+        original_filename: String,
+    ) -> Result<Project> {
+        let data = poscar::read_poscar(source_path)?.to_crystal_data();
+        let mut project = self.load_project(project_id)?;
+        let text = cif::write_cif(&data, &project.name);
+        atomic::write_atomic(&self.cif_path(project_id)?, text.as_bytes())?;
+        self.set_structure(&mut project, &data, original_filename)?;
+        Ok(project)
+    }
+
+    /// Writes the project's structure to `dest` as a POSCAR, with every
+    /// atom of the conventional cell.
+    pub fn export_poscar(&self, project_id: &ProjectId, dest: &Path) -> Result<()> {
+        let project = self.load_project(project_id)?;
+        let data = self.crystal_data(project_id)?;
+        let source = self.cif_path(project_id)?;
+        let poscar = Poscar::from_crystal_data(&data, &project.name, &source)?;
+        atomic::write_atomic(dest, poscar.to_text().as_bytes())
+    }
+
+    /// Creates a project from a CIF, Wien2k struct or POSCAR file, named
+    /// after the file's title or else the formula. Other formats are
+    /// stored as a CIF written from the parsed structure, so the project
+    /// reads the same whatever the source was.
+    pub fn create_project_from_structure(&self, source_path: &Path) -> Result<Project> {
+        let source = structure_file::read(source_path)?;
+        let formula = structure_file::formula(&source.data);
+        let original_filename = source_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match source.title {
+            Some(title) => title,
+            None if !formula.is_empty() => formula.clone(),
+            None => source_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+
+        let mut project = self.create_project(name, formula)?;
+        let project_dir = self.project_dir(&ProjectId::parse(&project.id)?)?;
+        let dest_path = project_dir.join("structure.cif");
+        if source.format == StructureFormat::Cif {
+            fs::copy(source_path, &dest_path)
+                .map_err(ReciprocalError::io(IoOp::Copy, source_path))?;
+        } else {
+            let text = cif::write_cif(&source.data, &project.name);
+            atomic::write_atomic(&dest_path, text.as_bytes())?;
+        }
+        self.set_structure(&mut project, &source.data, original_filename)?;
+
+        Ok(project)
+    }
+
+    /// Records a newly written structure.cif on the project, saving `data`
+    /// as the crystal data the frontend shows.
+    fn set_structure(
+        &self,
+        project: &mut Project,
+        data: &CrystalData,
+        original_filename: String,
+    ) -> Result<()> {
+        let project_dir = self.project_dir(&ProjectId::parse(&project.id)?)?;
+        atomic::write_json(&project_dir.join("cif_data.json"), data)?;
+
+        project.has_cif = true;
+        project.cif_filename = Some(original_filename);
+        project.updated_at = Utc::now();
+        atomic::write_json(&project_dir.join("project.json"), project)
+    }
+
+    pub fn list_structures(&self, project_id: &ProjectId) -> Result<Listing<StructureInfo>> {
+        let structures_dir = self.structures_dir(project_id)?;
+        let mut listing = listing::scan_metadata::<StructureInfo>(&structures_dir, "info.json")?;
+
+        // Keep the order the blocks had in the file
+        listing.items.sort_by_key(|info| info.created_at);
+
+        Ok(listing)
+    }
+
+    /// Parses one of the project's additional structures.
+    pub fn load_structure(
+        &self,
+        project_id: &ProjectId,
+        structure_id: &DatasetId,
+    ) -> Result<CrystalData> {
+        let structure_path = self.structure_dir(project_id, structure_id)?;
+        cif::read_crystal_data(&structure_path.join("structure.cif"))
+    }
+
+    pub fn delete_structure(
+        &self,
+        project_id: &ProjectId,
+        structure_id: &DatasetId,
+    ) -> Result<TrashEntry> {
+        let structure_path = self.structure_dir(project_id, structure_id)?;
+
+        let name = item_name(&structure_path.join("info.json"), structure_id.as_str());
+        trash::move_to_trash(
+            &self.trash_dir(),
+            &structure_path,
+            ResourceKind::Structure,
+            structure_id.as_str(),
+            Some(project_id.as_str()),
+            name,
+        )
+    }
+}
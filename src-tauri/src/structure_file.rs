@@ -0,0 +1,165 @@
+//! Crystal structures from any of the file formats Reciprocal reads: CIF,
+//! Wien2k `case.struct` and VASP POSCAR.
+
+use crate::cif::{self, CrystalData};
+use crate::elements;
+use crate::error::{ReciprocalError, Result};
+use crate::poscar::Poscar;
+use crate::symmetry;
+use crate::wien2k::Wien2kStruct;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StructureFormat {
+    Cif,
+    Wien2kStruct,
+    Poscar,
+}
+
+/// A structure read from a file, with the title the file gave it.
+#[derive(Debug, Clone)]
+pub struct StructureFile {
+    pub format: StructureFormat,
+    pub title: Option<String>,
+    pub data: CrystalData,
+}
+
+/// The format of the file at `path`, from its name where that is
+/// conventional and otherwise from `text`.
+pub fn detect(path: &Path, text: &str) -> Option<StructureFormat> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    if name.ends_with(".cif") {
+        return Some(StructureFormat::Cif);
+    }
+    if name.ends_with(".struct") {
+        return Some(StructureFormat::Wien2kStruct);
+    }
+    if name.starts_with("poscar") || name.starts_with("contcar") || name.ends_with(".vasp") {
+        return Some(StructureFormat::Poscar);
+    }
+
+    if text
+        .lines()
+        .any(|line| line.trim_start().to_ascii_lowercase().starts_with("data_"))
+    {
+        Some(StructureFormat::Cif)
+    } else if text
+        .lines()
+        .nth(1)
+        .is_some_and(|line| line.contains("LATTICE,NONEQUIV.ATOMS"))
+    {
+        Some(StructureFormat::Wien2kStruct)
+    } else if Poscar::parse(text, path).is_ok() {
+        Some(StructureFormat::Poscar)
+    } else {
+        None
+    }
+}
+
+/// Reads the structure in the file at `path`, whichever format it is in.
+pub fn read(path: &Path) -> Result<StructureFile> {
+    let text = cif::read_text(path)?;
+    let format = detect(path, &text).ok_or_else(|| ReciprocalError::Parse {
+        file: path.to_path_buf(),
+        line: None,
+        message: "Not a CIF, Wien2k struct or POSCAR file".to_string(),
+    })?;
+    let non_empty = |title: &str| {
+        let title = title.trim();
+        (!title.is_empty()).then(|| title.to_string())
+    };
+    Ok(match format {
+        StructureFormat::Cif => {
+            let data = cif::parse_crystal_data(&text, path)?;
+            StructureFile {
+                format,
+                title: data.chemical_name_common.clone(),
+                data,
+            }
+        }
+        StructureFormat::Wien2kStruct => {
+            let structure = Wien2kStruct::parse(&text, path)?;
+            StructureFile {
+                format,
+                title: non_empty(&structure.title),
+                data: structure.to_crystal_data(),
+            }
+        }
+        StructureFormat::Poscar => {
+            let poscar = Poscar::parse(&text, path)?;
+            StructureFile {
+                format,
+                title: non_empty(&poscar.comment),
+                data: poscar.to_crystal_data(),
+            }
+        }
+    })
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The formula of the cell's contents in lowest terms, elements in the
+/// order their sites are listed and counts in `<sub>` tags as the frontend
+/// stores project formulas, e.g. `Sr<sub>2</sub>RuO<sub>4</sub>`. Partial
+/// occupancies give fractional counts.
+pub fn formula(data: &CrystalData) -> String {
+    let ops = symmetry::parse_operations(&data.symmetry_operations);
+    let mut counts: Vec<(&str, f64)> = Vec::new();
+    for site in &data.atom_sites {
+        let Some(element) =
+            elements::element_of(&site.type_symbol).or_else(|| elements::element_of(&site.label))
+        else {
+            continue;
+        };
+        let position = [site.fract_x, site.fract_y, site.fract_z];
+        let multiplicity = if position.iter().all(|v| v.is_finite()) {
+            symmetry::orbit(position, &ops).len()
+        } else {
+            1
+        };
+        let occupancy = if site.occupancy.is_finite() {
+            site.occupancy
+        } else {
+            1.0
+        };
+        let amount = multiplicity as f64 * occupancy;
+        match counts.iter_mut().find(|(e, _)| *e == element) {
+            Some((_, count)) => *count += amount,
+            None => counts.push((element, amount)),
+        }
+    }
+
+    let whole = counts
+        .iter()
+        .all(|(_, n)| (n - n.round()).abs() < 1e-6 && n.round() >= 1.0);
+    let divisor = if whole {
+        counts
+            .iter()
+            .fold(0, |g, (_, n)| gcd(g, n.round() as u64))
+            .max(1) as f64
+    } else {
+        1.0
+    };
+    counts
+        .iter()
+        .map(|(element, n)| {
+            let n = n / divisor;
+            if (n - 1.0).abs() < 1e-6 {
+                element.to_string()
+            } else {
+                format!("{}<sub>{}</sub>", element, (n * 100.0).round() / 100.0)
+            }
+        })
+        .collect()
+}
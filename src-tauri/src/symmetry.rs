@@ -0,0 +1,179 @@
+//! Space group operations acting on fractional coordinates.
+
+use crate::lattice::wrap;
+use serde::{Deserialize, Serialize};
+
+/// Fractional coordinates closer than this along every axis are the same
+/// position.
+const POSITION_TOLERANCE: f64 = 1e-4;
+
+/// x' = R·x + t in fractional coordinates. Serializes like the frontend's
+/// `SymmetryMatrix`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SymmetryOperation {
+    pub rotation: [[i32; 3]; 3],
+    pub translation: [f64; 3],
+}
+
+/// Reads a number written as a decimal or a fraction such as `1/2`.
+fn fraction(text: &str) -> Option<f64> {
+    match text.split_once('/') {
+        Some((n, d)) => Some(n.parse::<f64>().ok()? / d.parse::<f64>().ok()?),
+        None => text.parse().ok(),
+    }
+}
+
+/// One component of a Jones-faithful operation, e.g. `-x+1/2` or `x-y`.
+fn component(text: &str) -> Option<([i32; 3], f64)> {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if text.is_empty() {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let (mut row, mut translation) = ([0; 3], 0.0);
+    let mut i = 0;
+    while i < bytes.len() {
+        let sign = match bytes[i] {
+            b'+' => {
+                i += 1;
+                1.0
+            }
+            b'-' => {
+                i += 1;
+                -1.0
+            }
+            _ => 1.0,
+        };
+        let start = i;
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.' || bytes[i] == b'/')
+        {
+            i += 1;
+        }
+        let coefficient = (i > start).then(|| fraction(&text[start..i]));
+        if bytes.get(i) == Some(&b'*') {
+            i += 1;
+        }
+        match bytes.get(i) {
+            Some(axis @ (b'x' | b'y' | b'z')) => {
+                let coefficient = coefficient.unwrap_or(Some(1.0))?;
+                row[(axis - b'x') as usize] += (sign * coefficient).round() as i32;
+                i += 1;
+            }
+            _ => translation += sign * coefficient??,
+        }
+    }
+    Some((row, translation))
+}
+
+/// `t` as a fraction with a small denominator where it is one.
+fn format_translation(t: f64) -> String {
+    for denominator in [1, 2, 3, 4, 6, 8, 12] {
+        let numerator = t * denominator as f64;
+        if (numerator - numerator.round()).abs() < 1e-6 {
+            let numerator = numerator.round() as i64;
+            return if denominator == 1 {
+                numerator.to_string()
+            } else {
+                format!("{}/{}", numerator, denominator)
+            };
+        }
+    }
+    t.to_string()
+}
+
+impl SymmetryOperation {
+    pub fn identity() -> Self {
+        SymmetryOperation {
+            rotation: [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            translation: [0.0; 3],
+        }
+    }
+
+    /// Parses an operation written like `-x+1/2, y, z-1/4`, as in CIF.
+    pub fn parse_xyz(text: &str) -> Option<Self> {
+        let parts: Vec<_> = text.split(',').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        let mut op = SymmetryOperation::identity();
+        for (n, part) in parts.iter().enumerate() {
+            let (row, t) = component(part)?;
+            op.rotation[n] = row;
+            op.translation[n] = t;
+        }
+        Some(op)
+    }
+
+    /// The operation written like `-x+1/2,y,z`.
+    pub fn to_xyz(&self) -> String {
+        let rows = (0..3).map(|n| {
+            let mut out = String::new();
+            for (axis, name) in ['x', 'y', 'z'].iter().enumerate() {
+                match self.rotation[n][axis] {
+                    0 => {}
+                    1 => out.push_str(&format!("+{}", name)),
+                    -1 => out.push_str(&format!("-{}", name)),
+                    k => out.push_str(&format!("{:+}{}", k, name)),
+                }
+            }
+            let t = self.translation[n];
+            if t.abs() > 1e-9 {
+                let t = format_translation(t);
+                if !t.starts_with('-') {
+                    out.push('+');
+                }
+                out.push_str(&t);
+            }
+            out.trim_start_matches('+').to_string()
+        });
+        rows.collect::<Vec<_>>().join(",")
+    }
+
+    pub fn apply(&self, p: [f64; 3]) -> [f64; 3] {
+        std::array::from_fn(|n| {
+            (0..3)
+                .map(|k| self.rotation[n][k] as f64 * p[k])
+                .sum::<f64>()
+                + self.translation[n]
+        })
+    }
+}
+
+/// The operations among `xyz` that parse, or just the identity if none
+/// do, so a structure without symmetry is taken as P1.
+pub fn parse_operations(xyz: &[String]) -> Vec<SymmetryOperation> {
+    let ops: Vec<_> = xyz
+        .iter()
+        .filter_map(|op| SymmetryOperation::parse_xyz(op))
+        .collect();
+    if ops.is_empty() {
+        vec![SymmetryOperation::identity()]
+    } else {
+        ops
+    }
+}
+
+/// Whether two fractional positions coincide, allowing for periodicity.
+pub fn same_position(p: [f64; 3], q: [f64; 3]) -> bool {
+    (0..3).all(|n| {
+        let d = (p[n] - q[n]).rem_euclid(1.0);
+        d.min(1.0 - d) < POSITION_TOLERANCE
+    })
+}
+
+/// The distinct positions `operations` take `position` to, wrapped into
+/// the unit cell.
+pub fn orbit(position: [f64; 3], operations: &[SymmetryOperation]) -> Vec<[f64; 3]> {
+    let mut positions: Vec<[f64; 3]> = Vec::new();
+    for op in operations {
+        let p = op.apply(position).map(wrap);
+        if !positions.iter().any(|&q| same_position(p, q)) {
+            positions.push(p);
+        }
+    }
+    positions
+}
@@ -0,0 +1,188 @@
+//! Per-library trash for deleted projects and datasets.
+//!
+//! Deleting moves the item's directory to `trash/<trash id>/item` and records
+//! what it was in `trash/<trash id>/trash.json`. Items are restored by moving
+//! them back, and purged once they are older than the retention period.
+
+use crate::atomic;
+use crate::error::{IoOp, ReciprocalError, ResourceKind, Result};
+use crate::ids::{DatasetId, ProjectId, TrashId};
+use crate::listing::{self, Listing};
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const ENTRY_FILE: &str = "trash.json";
+const ITEM_DIR: &str = "item";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub id: String,
+    pub kind: ResourceKind,
+    /// ID of the deleted project or dataset.
+    pub item_id: String,
+    /// Owning project, for datasets.
+    pub project_id: Option<String>,
+    pub name: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Where an item of `kind` lives in the library. The IDs come from
+/// trash.json, so they are validated like any other caller-supplied ID.
+fn library_path(
+    projects_dir: &Path,
+    kind: ResourceKind,
+    item_id: &str,
+    project_id: Option<&str>,
+) -> Result<PathBuf> {
+    let dataset_dir = match kind {
+        ResourceKind::Project => return Ok(projects_dir.join(ProjectId::parse(item_id)?)),
+        ResourceKind::BandStructure => "band_structures",
+        ResourceKind::FermiSurface => "fermi_surfaces",
+        ResourceKind::Structure => "structures",
+        ResourceKind::Dataset
+        | ResourceKind::File
+        | ResourceKind::TrashEntry
+        | ResourceKind::Job => {
+            return Err(ReciprocalError::internal(format!(
+                "{} can't be restored from the trash",
+                kind
+            )))
+        }
+    };
+    let project_id = project_id
+        .ok_or_else(|| ReciprocalError::internal(format!("Trashed {} has no project", kind)))?;
+    Ok(projects_dir
+        .join(ProjectId::parse(project_id)?)
+        .join(dataset_dir)
+        .join(DatasetId::parse(item_id)?))
+}
+
+/// Moves `item_path` into the trash and returns the new trash entry.
+pub fn move_to_trash(
+    trash_dir: &Path,
+    item_path: &Path,
+    kind: ResourceKind,
+    item_id: &str,
+    project_id: Option<&str>,
+    name: String,
+) -> Result<TrashEntry> {
+    let entry = TrashEntry {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        id: Uuid::new_v4().to_string(),
+        kind,
+        item_id: item_id.to_string(),
+        project_id: project_id.map(str::to_string),
+        name,
+        deleted_at: Utc::now(),
+    };
+
+    let entry_dir = trash_dir.join(&entry.id);
+    fs::create_dir_all(&entry_dir).map_err(ReciprocalError::io(IoOp::CreateDir, &entry_dir))?;
+    atomic::write_json(&entry_dir.join(ENTRY_FILE), &entry)?;
+
+    let dest = entry_dir.join(ITEM_DIR);
+    if let Err(e) = fs::rename(item_path, &dest) {
+        let _ = fs::remove_dir_all(&entry_dir);
+        return Err(ReciprocalError::io(IoOp::Delete, item_path)(e));
+    }
+
+    Ok(entry)
+}
+
+pub fn list(trash_dir: &Path) -> Result<Listing<TrashEntry>> {
+    if !trash_dir.exists() {
+        return Ok(Listing {
+            items: Vec::new(),
+            diagnostics: Vec::new(),
+        });
+    }
+    let mut listing = listing::scan_metadata::<TrashEntry>(trash_dir, ENTRY_FILE)?;
+    listing.items.sort_by_key(|entry| Reverse(entry.deleted_at));
+    Ok(listing)
+}
+
+/// Moves a trashed item back to where it was deleted from.
+pub fn restore(trash_dir: &Path, projects_dir: &Path, trash_id: &TrashId) -> Result<TrashEntry> {
+    let entry_dir = trash_dir.join(trash_id);
+    let entry_file = entry_dir.join(ENTRY_FILE);
+    if !entry_file.exists() {
+        return Err(ReciprocalError::not_found(
+            ResourceKind::TrashEntry,
+            trash_id.clone(),
+        ));
+    }
+    let entry: TrashEntry = atomic::read_json(&entry_file)?;
+
+    let dest = library_path(
+        projects_dir,
+        entry.kind,
+        &entry.item_id,
+        entry.project_id.as_deref(),
+    )?;
+    if dest.exists() {
+        return Err(ReciprocalError::AlreadyExists {
+            kind: entry.kind,
+            id: entry.item_id,
+        });
+    }
+    if entry.kind != ResourceKind::Project {
+        if let Some(project_id) = &entry.project_id {
+            if !projects_dir.join(project_id).exists() {
+                return Err(ReciprocalError::not_found(
+                    ResourceKind::Project,
+                    project_id,
+                ));
+            }
+        }
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(ReciprocalError::io(IoOp::CreateDir, parent))?;
+    }
+
+    let item = entry_dir.join(ITEM_DIR);
+    fs::rename(&item, &dest).map_err(ReciprocalError::io(IoOp::Write, &dest))?;
+    fs::remove_dir_all(&entry_dir).map_err(ReciprocalError::io(IoOp::Delete, &entry_dir))?;
+
+    Ok(entry)
+}
+
+/// Permanently deletes everything in the trash. Returns how many items
+/// were removed.
+pub fn empty(trash_dir: &Path) -> Result<usize> {
+    if !trash_dir.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in fs::read_dir(trash_dir).map_err(ReciprocalError::io(IoOp::ReadDir, trash_dir))? {
+        let path = entry
+            .map_err(ReciprocalError::io(IoOp::ReadDir, trash_dir))?
+            .path();
+        if path.is_dir() {
+            fs::remove_dir_all(&path).map_err(ReciprocalError::io(IoOp::Delete, &path))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Permanently deletes items deleted more than `retention_days` ago.
+/// Returns how many items were removed.
+pub fn purge_expired(trash_dir: &Path, retention_days: u32) -> Result<usize> {
+    let cutoff = Utc::now() - Duration::days(i64::from(retention_days));
+    let mut removed = 0;
+    for entry in list(trash_dir)?.items {
+        if entry.deleted_at < cutoff {
+            let path = trash_dir.join(&entry.id);
+            fs::remove_dir_all(&path).map_err(ReciprocalError::io(IoOp::Delete, &path))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,372 @@
+//! Converting between a CIF structure and a `case.struct`: building one in
+//! place of Wien2k's `cif2struct`, and reading one back for the structure
+//! viewer.
+//!
+//! The centring is read off the pure translations among the CIF's
+//! symmetry operations rather than the space group symbol, so a file that
+//! lists every atom in P1 stays primitive. Atom parameters are the usual
+//! starting values; RMTs are meant to be refined with `setrmt` before
+//! production runs.
+
+use super::structure::{LatticeType, StructAtom, Wien2kStruct};
+use crate::cif::{AtomSite, CrystalData};
+use crate::elements;
+use crate::error::{ReciprocalError, Result};
+use crate::lattice::{wrap, Cell, BOHR_IN_ANGSTROM};
+use crate::symmetry::{self, same_position, SymmetryOperation};
+use std::path::Path;
+
+const NPT: u32 = 781;
+const ISPLIT: i32 = 8;
+/// Largest RMT handed out, in bohr.
+const MAX_RMT: f64 = 2.5;
+/// Fraction of the half nearest-neighbour distance used for RMT, leaving
+/// room between the spheres.
+const RMT_FILL: f64 = 0.97;
+/// Atoms closer than this (bohr) are partial occupancies of one site and
+/// don't limit each other's spheres.
+const SAME_SITE: f64 = 0.1;
+
+const IDENTITY: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Hexagonal → rhombohedral (obverse) coordinates, and back.
+const HEX_TO_RHOMB: [[f64; 3]; 3] = [[1.0, 0.0, 1.0], [-1.0, 1.0, 1.0], [0.0, -1.0, 1.0]];
+const RHOMB_TO_HEX: [[f64; 3]; 3] = [
+    [2.0 / 3.0, -1.0 / 3.0, -1.0 / 3.0],
+    [1.0 / 3.0, 1.0 / 3.0, -2.0 / 3.0],
+    [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0],
+];
+const RHOMBOHEDRAL_CENTRING: [[f64; 3]; 2] = [
+    [2.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0],
+    [1.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0],
+];
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+fn shifted(p: [f64; 3], t: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|n| wrap(p[n] + t[n]))
+}
+
+fn multiply(m: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| (0..3).map(|k| m[i][k] * v[k]).sum())
+}
+
+/// Whether the operations include `x + t` for each of `translations`.
+fn has_translations(ops: &[SymmetryOperation], translations: &[[f64; 3]]) -> bool {
+    let identity = SymmetryOperation::identity().rotation;
+    translations.iter().all(|&t| {
+        ops.iter()
+            .any(|op| op.rotation == identity && same_position(op.translation, t))
+    })
+}
+
+fn lattice_type(ops: &[SymmetryOperation], cell: &Cell) -> LatticeType {
+    let hexagonal = close(cell.a, cell.b)
+        && close(cell.alpha, 90.0)
+        && close(cell.beta, 90.0)
+        && close(cell.gamma, 120.0);
+    if hexagonal && has_translations(ops, &RHOMBOHEDRAL_CENTRING) {
+        return LatticeType::R;
+    }
+    for lattice in [
+        LatticeType::F,
+        LatticeType::B,
+        LatticeType::Cxy,
+        LatticeType::Cyz,
+        LatticeType::Cxz,
+    ] {
+        if has_translations(ops, lattice.centring()) {
+            return lattice;
+        }
+    }
+    if hexagonal {
+        LatticeType::H
+    } else {
+        LatticeType::P
+    }
+}
+
+/// The translations that leave the conventional cell unchanged, including
+/// zero.
+fn centring(lattice: LatticeType) -> Vec<[f64; 3]> {
+    let mut translations = vec![[0.0; 3]];
+    match lattice {
+        LatticeType::R => translations.extend(RHOMBOHEDRAL_CENTRING),
+        _ => translations.extend_from_slice(lattice.centring()),
+    }
+    translations
+}
+
+/// `positions` with those a centring translation away from an earlier one
+/// dropped.
+fn primitive_positions(positions: Vec<[f64; 3]>, centring: &[[f64; 3]]) -> Vec<[f64; 3]> {
+    let mut kept: Vec<[f64; 3]> = Vec::new();
+    for p in positions {
+        let repeated = kept
+            .iter()
+            .any(|&q| centring.iter().any(|&t| same_position(shifted(q, t), p)));
+        if !repeated {
+            kept.push(p);
+        }
+    }
+    kept
+}
+
+/// One operation per coset of the centring translations, with
+/// translations wrapped into the cell.
+fn primitive_operations(
+    ops: &[SymmetryOperation],
+    centring: &[[f64; 3]],
+) -> Vec<SymmetryOperation> {
+    let mut kept: Vec<SymmetryOperation> = Vec::new();
+    for op in ops {
+        let op = SymmetryOperation {
+            rotation: op.rotation,
+            translation: op.translation.map(wrap),
+        };
+        let repeated = kept.iter().any(|k| {
+            k.rotation == op.rotation
+                && centring
+                    .iter()
+                    .any(|&t| same_position(shifted(k.translation, t), op.translation))
+        });
+        if !repeated {
+            kept.push(op);
+        }
+    }
+    kept
+}
+
+/// `op` in the coordinates `basis` takes positions to, given `inverse`
+/// which takes them back: R' = B·R·B⁻¹, t' = B·t.
+fn change_basis(
+    op: &SymmetryOperation,
+    basis: &[[f64; 3]; 3],
+    inverse: &[[f64; 3]; 3],
+) -> SymmetryOperation {
+    let r = op.rotation.map(|row| row.map(f64::from));
+    let rotation = std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            let mut sum = 0.0;
+            for k in 0..3 {
+                for l in 0..3 {
+                    sum += basis[i][k] * r[k][l] * inverse[l][j];
+                }
+            }
+            sum.round() as i32
+        })
+    });
+    SymmetryOperation {
+        rotation,
+        translation: multiply(basis, op.translation).map(wrap),
+    }
+}
+
+/// Half the distance to the nearest other atom, shrunk a little and
+/// rounded down to two decimals, in bohr.
+fn rmt(cell: &Cell, position: [f64; 3], all: &[[f64; 3]]) -> f64 {
+    let nearest = all
+        .iter()
+        .map(|&q| cell.distance(position, q))
+        .filter(|&d| d > SAME_SITE)
+        .fold(f64::INFINITY, f64::min);
+    let radius = (RMT_FILL * nearest / 2.0 * 100.0).floor() / 100.0;
+    radius.min(MAX_RMT)
+}
+
+/// A Wien2k atom name: the CIF label if it starts with the element, so
+/// inequivalent sites stay apart, otherwise the element symbol.
+fn atom_name(label: &str, element: &str) -> String {
+    let name = if label.starts_with(element) && !label.contains(char::is_whitespace) {
+        label
+    } else {
+        element
+    };
+    name.chars().take(10).collect()
+}
+
+impl Wien2kStruct {
+    /// Builds the struct file for `data`. `source` names the file the data
+    /// came from, for errors.
+    pub fn from_crystal_data(data: &CrystalData, title: &str, source: &Path) -> Result<Self> {
+        let error = |message: String| ReciprocalError::Parse {
+            file: source.to_path_buf(),
+            line: None,
+            message,
+        };
+        let cell = data.cell();
+        let parameters = [cell.a, cell.b, cell.c, cell.alpha, cell.beta, cell.gamma];
+        if parameters.iter().any(|v| !v.is_finite() || *v <= 0.0) {
+            return Err(error("The structure has no complete unit cell".into()));
+        }
+        if data.atom_sites.is_empty() {
+            return Err(error("The structure has no atom sites".into()));
+        }
+        let bohr = cell.scaled(1.0 / BOHR_IN_ANGSTROM);
+
+        let ops = symmetry::parse_operations(&data.symmetry_operations);
+        let lattice = lattice_type(&ops, &cell);
+        let centring = centring(lattice);
+
+        let mut sites = Vec::with_capacity(data.atom_sites.len());
+        for site in &data.atom_sites {
+            let position = [site.fract_x, site.fract_y, site.fract_z];
+            if position.iter().any(|v| !v.is_finite()) {
+                return Err(error(format!("Atom {} has no position", site.label)));
+            }
+            let element = elements::element_of(&site.type_symbol)
+                .or_else(|| elements::element_of(&site.label))
+                .ok_or_else(|| error(format!("Atom {} has no known element", site.label)))?;
+            let positions = primitive_positions(symmetry::orbit(position, &ops), &centring);
+            sites.push((site, element, positions));
+        }
+
+        // Every atom of the conventional cell, for nearest neighbours.
+        let all: Vec<[f64; 3]> = sites
+            .iter()
+            .flat_map(|(_, _, positions)| positions)
+            .flat_map(|&p| centring.iter().map(move |&t| shifted(p, t)))
+            .collect();
+
+        let atoms = sites
+            .into_iter()
+            .map(|(site, element, positions)| {
+                let z = elements::atomic_number(element).unwrap_or(0);
+                let rmt = rmt(&bohr, positions[0], &all);
+                let positions = if lattice == LatticeType::R {
+                    positions
+                        .into_iter()
+                        .map(|p| multiply(&HEX_TO_RHOMB, p).map(wrap))
+                        .collect()
+                } else {
+                    positions
+                };
+                StructAtom {
+                    name: atom_name(&site.label, element),
+                    positions,
+                    isplit: ISPLIT,
+                    npt: NPT,
+                    r0: if z <= 36 { 0.0001 } else { 0.00005 },
+                    rmt,
+                    z: f64::from(z),
+                    local_rotation: IDENTITY,
+                }
+            })
+            .collect();
+
+        let mut symmetry_operations = primitive_operations(&ops, &centring);
+        if lattice == LatticeType::R {
+            symmetry_operations = symmetry_operations
+                .iter()
+                .map(|op| change_basis(op, &HEX_TO_RHOMB, &RHOMB_TO_HEX))
+                .collect();
+        }
+
+        Ok(Wien2kStruct {
+            title: title.to_string(),
+            lattice,
+            space_group_number: data.space_group_it_number,
+            space_group_symbol: data
+                .space_group_hm
+                .as_ref()
+                .map(|hm| hm.split_whitespace().collect()),
+            relativistic: true,
+            cell: [bohr.a, bohr.b, bohr.c, bohr.alpha, bohr.beta, bohr.gamma],
+            atoms,
+            symmetry_operations,
+        })
+    }
+
+    /// The structure as the frontend shows it, lengths in Å. Each
+    /// non-equivalent atom becomes one site, unless the file's operations
+    /// don't generate its listed positions, in which case every position is
+    /// a site of its own.
+    pub fn to_crystal_data(&self) -> CrystalData {
+        let rhombohedral = self.lattice == LatticeType::R;
+        let hexagonal = |p: [f64; 3]| {
+            if rhombohedral {
+                multiply(&RHOMB_TO_HEX, p).map(wrap)
+            } else {
+                p.map(wrap)
+            }
+        };
+        let centring = centring(self.lattice);
+
+        let mut ops: Vec<SymmetryOperation> = Vec::new();
+        let listed = if self.symmetry_operations.is_empty() {
+            vec![SymmetryOperation::identity()]
+        } else {
+            self.symmetry_operations.clone()
+        };
+        for op in &listed {
+            let op = if rhombohedral {
+                change_basis(op, &RHOMB_TO_HEX, &HEX_TO_RHOMB)
+            } else {
+                op.clone()
+            };
+            for &t in &centring {
+                let translation = shifted(op.translation, t);
+                let full = SymmetryOperation {
+                    rotation: op.rotation,
+                    translation,
+                };
+                if !ops.iter().any(|o| {
+                    o.rotation == full.rotation && same_position(o.translation, full.translation)
+                }) {
+                    ops.push(full);
+                }
+            }
+        }
+
+        let mut data = CrystalData::from_cell(&self.cell().scaled(BOHR_IN_ANGSTROM));
+        let mut labels: Vec<String> = Vec::new();
+        let mut label = |name: &str| {
+            let mut candidate = name.to_string();
+            let mut n = 1;
+            while labels.contains(&candidate) {
+                n += 1;
+                candidate = format!("{}_{}", name, n);
+            }
+            labels.push(candidate.clone());
+            candidate
+        };
+        for atom in &self.atoms {
+            let type_symbol = elements::symbol(atom.z.round() as u32)
+                .or_else(|| elements::element_of(&atom.name))
+                .unwrap_or("X")
+                .to_string();
+            let positions: Vec<[f64; 3]> = atom.positions.iter().map(|&p| hexagonal(p)).collect();
+            let Some(&first) = positions.first() else {
+                continue;
+            };
+            let orbit = symmetry::orbit(first, &ops);
+            let generated = positions
+                .iter()
+                .all(|&p| orbit.iter().any(|&q| same_position(p, q)));
+            let sites = if generated {
+                &positions[..1]
+            } else {
+                &positions[..]
+            };
+            for &p in sites {
+                data.atom_sites.push(AtomSite {
+                    label: label(&atom.name),
+                    type_symbol: type_symbol.clone(),
+                    fract_x: p[0],
+                    fract_y: p[1],
+                    fract_z: p[2],
+                    wyckoff_symbol: None,
+                    symmetry_multiplicity: generated.then_some(orbit.len() as u32),
+                    occupancy: 1.0,
+                });
+            }
+        }
+
+        data.space_group_hm = self.space_group_symbol.clone();
+        data.space_group_it_number = self.space_group_number;
+        data.symmetry_operations = ops.iter().map(SymmetryOperation::to_xyz).collect();
+        data
+    }
+}
@@ -0,0 +1,296 @@
+//! Eigenvalue files written by LAPW1 and LAPWSO: `case.energy`,
+//! `case.energyso`, their spin-polarized `up`/`dn` forms, and the
+//! `case.energy_1..N` splits of a k-parallel run. Also `case.klist`, which
+//! the merged eigenvalues are checked against.
+
+use super::fermi::RY_TO_EV;
+use crate::error::{IoOp, ReciprocalError, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The eigenvalues at one k-point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyKPoint {
+    /// In units of the reciprocal lattice vectors, as LAPW1 writes it.
+    pub k: [f64; 3],
+    pub name: String,
+    pub weight: f64,
+    /// eV, lowest first.
+    pub energies: Vec<f64>,
+}
+
+/// A point of `case.klist`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KListPoint {
+    pub name: String,
+    /// Integer coordinates, divided by `divisor` to give `k`.
+    pub coordinates: [i64; 3],
+    pub divisor: i64,
+    pub weight: f64,
+}
+
+impl KListPoint {
+    pub fn k(&self) -> [f64; 3] {
+        self.coordinates.map(|c| c as f64 / self.divisor as f64)
+    }
+}
+
+fn parse_error(file: &Path, line: Option<usize>, message: impl Into<String>) -> ReciprocalError {
+    ReciprocalError::Parse {
+        file: file.to_path_buf(),
+        line,
+        message: message.into(),
+    }
+}
+
+fn field<T: std::str::FromStr>(line: &str, start: usize, end: usize) -> Option<T> {
+    line.get(start..end.min(line.len()))?.trim().parse().ok()
+}
+
+/// Reads a k-point line, written as `(3e19.12,a10,2i6,f5.1)`: k, name,
+/// number of plane waves, number of eigenvalues and weight. Files that
+/// were reformatted are read as whitespace-separated fields.
+fn kpoint_header(line: &str) -> Option<([f64; 3], String, usize, f64)> {
+    let fixed = || {
+        let k = [
+            field(line, 0, 19)?,
+            field(line, 19, 38)?,
+            field(line, 38, 57)?,
+        ];
+        let name = line.get(57..67)?.trim().to_string();
+        // The number of plane waves, unused but there on a k-point line.
+        let _: usize = field(line, 67, 73)?;
+        Some((k, name, field(line, 73, 79)?, field(line, 79, 84)?))
+    };
+    fixed().or_else(|| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != 6 && tokens.len() != 7 {
+            return None;
+        }
+        let k = [
+            tokens[0].parse().ok()?,
+            tokens[1].parse().ok()?,
+            tokens[2].parse().ok()?,
+        ];
+        let name = if tokens.len() == 7 { tokens[3] } else { "" };
+        let [plane_waves, bands, weight] = tokens[tokens.len() - 3..] else {
+            return None;
+        };
+        let _: usize = plane_waves.parse().ok()?;
+        Some((
+            k,
+            name.to_string(),
+            bands.parse().ok()?,
+            weight.parse().ok()?,
+        ))
+    })
+}
+
+/// Reads one eigenvalue file. The linearization energies at the top, two
+/// lines per inequivalent atom, are skipped.
+pub fn parse_energy(text: &str, file: &Path) -> Result<Vec<EnergyKPoint>> {
+    let mut lines = text.lines().enumerate().peekable();
+    while lines
+        .peek()
+        .is_some_and(|(_, line)| kpoint_header(line).is_none())
+    {
+        lines.next();
+    }
+
+    let mut kpoints = Vec::new();
+    while let Some((i, line)) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (k, name, bands, weight) = kpoint_header(line)
+            .ok_or_else(|| parse_error(file, Some(i + 1), "Expected a k-point line"))?;
+        let mut energies = Vec::with_capacity(bands);
+        for _ in 0..bands {
+            let (i, line) = lines.next().ok_or_else(|| {
+                parse_error(
+                    file,
+                    None,
+                    format!("File ends inside k-point {}", kpoints.len() + 1),
+                )
+            })?;
+            let mut tokens = line.split_whitespace();
+            let energy = tokens
+                .next()
+                .and_then(|t| t.parse::<usize>().ok())
+                .and(tokens.next())
+                .and_then(|t| t.parse::<f64>().ok())
+                .ok_or_else(|| {
+                    parse_error(file, Some(i + 1), "Expected a band number and energy")
+                })?;
+            energies.push(energy * RY_TO_EV);
+        }
+        kpoints.push(EnergyKPoint {
+            k,
+            name,
+            weight,
+            energies,
+        });
+    }
+    Ok(kpoints)
+}
+
+/// The `N` of a k-parallel split such as `case.energy_3`.
+pub fn split_number(path: &Path) -> Option<usize> {
+    let name = path.file_name()?.to_str()?;
+    let (_, number) = name.rsplit_once('_')?;
+    number.parse().ok()
+}
+
+/// The eigenvalue files a path stands for. Given one split of a k-parallel
+/// run, or a `case.energy` that is missing or empty as it is after one,
+/// returns every `case.energy_N` beside it in split order. Otherwise, or if
+/// there are no splits, returns just `path`.
+pub fn energy_splits(path: &Path) -> Vec<PathBuf> {
+    let whole = fs::metadata(path).is_ok_and(|m| m.len() > 0);
+    let name = path.file_name().and_then(|n| n.to_str());
+    let Some(name) = name.filter(|_| !whole || split_number(path).is_some()) else {
+        return vec![path.to_path_buf()];
+    };
+    let base = match split_number(path) {
+        Some(_) => name.rsplit_once('_').map_or(name, |(base, _)| base),
+        None => name,
+    };
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+    let entries = fs::read_dir(dir.unwrap_or(Path::new(".")));
+    let mut splits: Vec<(usize, PathBuf)> = entries
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let (prefix, number) = file_name.to_str()?.rsplit_once('_')?;
+            (prefix == base)
+                .then(|| number.parse().ok())
+                .flatten()
+                .map(|n| (n, entry.path()))
+        })
+        .collect();
+    if splits.is_empty() {
+        return vec![path.to_path_buf()];
+    }
+    splits.sort_by_key(|(n, _)| *n);
+    splits.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Reads eigenvalue files and joins them, splits in the order of their
+/// numbers whatever order they are given in.
+pub fn read_energy(paths: &[PathBuf]) -> Result<Vec<EnergyKPoint>> {
+    let mut paths: Vec<&PathBuf> = paths.iter().collect();
+    paths.sort_by_key(|path| split_number(path));
+    let mut kpoints = Vec::new();
+    for path in paths {
+        let text = fs::read_to_string(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+        kpoints.extend(parse_energy(&text, path)?);
+    }
+    Ok(kpoints)
+}
+
+/// Reads `case.klist` up to its `END` line, or to the end of files that
+/// leave it out.
+pub fn parse_klist(text: &str, file: &Path) -> Result<Vec<KListPoint>> {
+    let mut points = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with("END") {
+            return Ok(points);
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        // (A10,4I10,F5.1), with extra fields on the first line.
+        let fixed = || -> Option<KListPoint> {
+            Some(KListPoint {
+                name: line.get(..10)?.trim().to_string(),
+                coordinates: [
+                    field(line, 10, 20)?,
+                    field(line, 20, 30)?,
+                    field(line, 30, 40)?,
+                ],
+                divisor: field(line, 40, 50)?,
+                weight: field(line, 50, 55)?,
+            })
+        };
+        // Reformatted files may leave out the label. KGEN numbers the
+        // points, so an integer first field is only taken for a coordinate
+        // when the fifth is the weight rather than the divisor.
+        let free = || -> Option<KListPoint> {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let labeled = tokens.first()?.parse::<i64>().is_err()
+                || tokens.get(4).is_some_and(|t| t.parse::<i64>().is_ok());
+            let (name, fields) = if labeled {
+                (tokens[0], &tokens[1..])
+            } else {
+                ("", &tokens[..])
+            };
+            let ints: Vec<i64> = fields
+                .get(..4)?
+                .iter()
+                .map(|t| t.parse().ok())
+                .collect::<Option<_>>()?;
+            Some(KListPoint {
+                name: name.to_string(),
+                coordinates: [ints[0], ints[1], ints[2]],
+                divisor: ints[3],
+                weight: fields.get(4)?.parse().ok()?,
+            })
+        };
+        let point = fixed()
+            .or_else(free)
+            .filter(|p| p.divisor != 0)
+            .ok_or_else(|| parse_error(file, Some(i + 1), "Expected a k-point"))?;
+        points.push(point);
+    }
+    Ok(points)
+}
+
+/// Puts merged eigenvalues in `klist` order, checking that every k-point of
+/// the list has eigenvalues and nothing else does. `file` names the
+/// eigenvalue files in errors.
+pub fn align_to_klist(
+    kpoints: Vec<EnergyKPoint>,
+    klist: &[KListPoint],
+    file: &Path,
+) -> Result<Vec<EnergyKPoint>> {
+    if kpoints.len() != klist.len() {
+        return Err(parse_error(
+            file,
+            None,
+            format!(
+                "Eigenvalues for {} k-points, but the k-list has {}",
+                kpoints.len(),
+                klist.len()
+            ),
+        ));
+    }
+    let key = |k: [f64; 3]| k.map(|v| (v * 1e6).round() as i64);
+    let mut by_k: HashMap<[i64; 3], EnergyKPoint> =
+        kpoints.into_iter().map(|kp| (key(kp.k), kp)).collect();
+    klist
+        .iter()
+        .enumerate()
+        .map(|(i, point)| {
+            by_k.remove(&key(point.k())).ok_or_else(|| {
+                parse_error(
+                    file,
+                    None,
+                    format!("No eigenvalues for k-point {} of the k-list", i + 1),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Eigenvalues per k-point, cut to the number of bands every k-point has,
+/// as `band_grid` takes them.
+pub fn eigenvalues(kpoints: &[EnergyKPoint]) -> Vec<Vec<f64>> {
+    let bands = kpoints.iter().map(|k| k.energies.len()).min().unwrap_or(0);
+    kpoints
+        .iter()
+        .map(|k| k.energies[..bands].to_vec())
+        .collect()
+}
@@ -0,0 +1,237 @@
+//! Band energies on the full k-mesh from `case.output1`, `case.output2` and
+//! `case.outputkgen`, unfolded the way XCrysDen's `wn_readbands` and
+//! `wn_readbakgen` do. The frontend's `buildWien2kBandGrid` does the same
+//! for drawing.
+
+use crate::band_grid::BandGrid;
+use crate::error::{ReciprocalError, Result};
+use std::path::Path;
+
+/// Electronvolts per rydberg.
+pub const RY_TO_EV: f64 = 13.605_693_122_994;
+
+fn parse_error(file: &Path, line: Option<usize>, message: impl Into<String>) -> ReciprocalError {
+    ReciprocalError::Parse {
+        file: file.to_path_buf(),
+        line,
+        message: message.into(),
+    }
+}
+
+/// The numbers in a line of Fortran output, including ones printed without
+/// a space between them such as `-0.51234567-0.41234567`.
+fn numbers(line: &str) -> Vec<f64> {
+    let bytes = line.as_bytes();
+    let mut values = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        if matches!(bytes[i], b'-' | b'+') {
+            i += 1;
+        }
+        let digits = i;
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+            i += 1;
+        }
+        if !bytes[digits..i].iter().any(u8::is_ascii_digit) {
+            i = start + 1;
+            continue;
+        }
+        if i < bytes.len() && matches!(bytes[i], b'E' | b'e') {
+            let mut j = i + 1;
+            if j < bytes.len() && matches!(bytes[j], b'-' | b'+') {
+                j += 1;
+            }
+            if j < bytes.len() && bytes[j].is_ascii_digit() {
+                while j < bytes.len() && bytes[j].is_ascii_digit() {
+                    j += 1;
+                }
+                i = j;
+            }
+        }
+        if let Ok(value) = line[start..i].parse() {
+            values.push(value);
+        }
+    }
+    values
+}
+
+/// Eigenvalues in eV at each irreducible k-point, in file order, cut to the
+/// number of bands every k-point has.
+pub fn parse_output1(text: &str) -> Vec<Vec<f64>> {
+    let mut energies: Vec<Vec<f64>> = Vec::new();
+    let mut current: Option<Vec<f64>> = None;
+    let mut in_energies = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("K=") {
+            energies.extend(current.take().filter(|k| !k.is_empty()));
+            current = Some(Vec::new());
+            in_energies = false;
+        } else if line.contains("EIGENVALUES ARE") {
+            in_energies = true;
+        } else if line.contains("EIGENVALUES BELOW") {
+            in_energies = false;
+        } else if in_energies {
+            if let Some(current) = &mut current {
+                current.extend(numbers(line).into_iter().map(|e| e * RY_TO_EV));
+            }
+        }
+    }
+    energies.extend(current.filter(|k| !k.is_empty()));
+
+    let bands = energies.iter().map(Vec::len).min().unwrap_or(0);
+    for k in &mut energies {
+        k.truncate(bands);
+    }
+    energies
+}
+
+/// The Fermi energy in eV from the last `:FER` line.
+pub fn parse_fermi_energy(text: &str, file: &Path) -> Result<f64> {
+    text.lines()
+        .rev()
+        .filter(|line| line.to_ascii_uppercase().contains(":FER"))
+        .filter_map(|line| {
+            let (_, value) = line.split_once('=')?;
+            value.split_whitespace().next()?.parse::<f64>().ok()
+        })
+        .next()
+        .map(|ry| ry * RY_TO_EV)
+        .ok_or_else(|| parse_error(file, None, "No :FER line with the Fermi energy"))
+}
+
+/// The k-mesh listed in `case.outputkgen`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KMesh {
+    /// Points along each axis, both ends included.
+    pub dims: [usize; 3],
+    /// G1, G2 and G3 as rows.
+    pub reciprocal_vectors: [[f64; 3]; 3],
+    /// Grid coordinates of each point and the 1-based number of the point
+    /// it is equivalent to.
+    pub points: Vec<([usize; 3], usize)>,
+}
+
+pub fn parse_outputkgen(text: &str, file: &Path) -> Result<KMesh> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut reciprocal_vectors = [[0.0; 3]; 3];
+    if let Some(i) = lines.iter().position(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        fields.windows(3).any(|w| w == ["G1", "G2", "G3"])
+    }) {
+        for (row, line) in reciprocal_vectors.iter_mut().zip(&lines[i + 1..]) {
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .filter_map(|t| t.parse().ok())
+                .collect();
+            if values.len() == 3 {
+                *row = [values[0], values[1], values[2]];
+            }
+        }
+    }
+
+    let (division_line, division) = lines
+        .iter()
+        .enumerate()
+        .find(|(_, line)| line.contains("DIVISION OF RECIPROCAL LATTICE VECTORS"))
+        .ok_or_else(|| parse_error(file, None, "No division of the reciprocal lattice vectors"))?;
+    let divisions: Vec<usize> = division
+        .split_whitespace()
+        .filter_map(|t| t.parse().ok())
+        .collect();
+    let dims = match divisions[..] {
+        [.., x, y, z] if x > 0 && y > 0 && z > 0 => [x + 1, y + 1, z + 1],
+        _ => {
+            return Err(parse_error(
+                file,
+                Some(division_line + 1),
+                "Expected three divisions of the reciprocal lattice vectors",
+            ))
+        }
+    };
+
+    let header = lines
+        .iter()
+        .position(|line| {
+            line.contains("point") && line.contains("coordinates") && line.contains("relation")
+        })
+        .or_else(|| {
+            lines
+                .iter()
+                .position(|line| line.trim_start().starts_with("point"))
+        });
+    let total = dims.iter().product();
+    let mut points = Vec::with_capacity(total);
+    for line in &lines[header.map_or(0, |h| h + 1)..] {
+        if points.len() == total {
+            break;
+        }
+        let values: Vec<usize> = line
+            .split_whitespace()
+            .map_while(|t| t.parse().ok())
+            .collect();
+        if let [_, x, y, z, relation, ..] = values[..] {
+            if x < dims[0] && y < dims[1] && z < dims[2] && (1..=total).contains(&relation) {
+                points.push(([x, y, z], relation));
+            }
+        }
+    }
+    if points.len() < total {
+        return Err(parse_error(
+            file,
+            None,
+            format!("Expected {} k-points, found {}", total, points.len()),
+        ));
+    }
+
+    Ok(KMesh {
+        dims,
+        reciprocal_vectors,
+        points,
+    })
+}
+
+/// Spreads the irreducible k-point energies over the whole mesh.
+pub fn band_grid(energies: &[Vec<f64>], fermi_energy: f64, mesh: &KMesh) -> BandGrid {
+    let [nx, ny, nz] = mesh.dims;
+    let bands = energies.first().map_or(0, Vec::len);
+
+    // Number each irreducible point in order; as in XCrysDen, a point whose
+    // relation is itself reducible takes that point's relation.
+    let relation = |n: usize| mesh.points[n - 1].1;
+    let mut irreducible = vec![0; mesh.points.len() + 1];
+    let mut count = 0;
+    for (n, number) in irreducible.iter_mut().enumerate().skip(1) {
+        *number = if relation(n) == n {
+            count += 1;
+            count
+        } else {
+            relation(relation(n))
+        };
+    }
+
+    let mut grid = BandGrid {
+        nx,
+        ny,
+        nz,
+        fermi_energy,
+        origin: [0.0; 3],
+        reciprocal_vectors: mesh.reciprocal_vectors,
+        bands: vec![vec![0.0; nx * ny * nz]; bands],
+        spins: Vec::new(),
+    };
+    for &([x, y, z], relation) in &mesh.points {
+        let Some(k) = irreducible[relation]
+            .checked_sub(1)
+            .and_then(|k| energies.get(k))
+        else {
+            continue;
+        };
+        let index = grid.index(x, y, z);
+        for (band, &energy) in grid.bands.iter_mut().zip(k) {
+            band[index] = energy as f32;
+        }
+    }
+    grid
+}
@@ -0,0 +1,32 @@
+//! Files of the Wien2k DFT code.
+//!
+//! `structure` reads and writes `case.struct`; `convert` builds one from a
+//! project's CIF to start a new calculation, and turns one back into the
+//! crystal structure the frontend shows. `fermi` unfolds the eigenvalues
+//! of an SCF run onto the full k-mesh for Fermi surfaces, read from
+//! `case.output1` or from the `case.energy` files `energy` reads. `qtl`
+//! reads the energies and band characters of a band structure's
+//! `case.qtl`.
+
+mod convert;
+mod energy;
+mod fermi;
+mod qtl;
+mod structure;
+
+pub use energy::{
+    align_to_klist, eigenvalues, energy_splits, parse_energy, parse_klist, read_energy,
+    split_number, EnergyKPoint, KListPoint,
+};
+pub use fermi::{band_grid, parse_fermi_energy, parse_output1, parse_outputkgen, KMesh, RY_TO_EV};
+pub use qtl::{parse_qtl, qtl_fermi_energy, Qtl, QtlAtom, QtlBand};
+pub use structure::{LatticeType, StructAtom, Wien2kStruct};
+
+use crate::error::{IoOp, ReciprocalError, Result};
+use std::fs;
+use std::path::Path;
+
+pub fn read_struct(path: &Path) -> Result<Wien2kStruct> {
+    let text = fs::read_to_string(path).map_err(ReciprocalError::io(IoOp::Read, path))?;
+    Wien2kStruct::parse(&text, path)
+}
@@ -0,0 +1,219 @@
+//! Band characters written by LAPW2 for a band structure run: `case.qtl`,
+//! or `case.qtlup` and `case.qtldn` for the two spin channels.
+
+use super::fermi::RY_TO_EV;
+use crate::error::{ReciprocalError, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// An inequivalent atom of the header, from its `JATOM` line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QtlAtom {
+    /// 1-based, as in `case.struct`.
+    pub index: usize,
+    pub multiplicity: usize,
+    pub isplit: i32,
+    /// Lowercased names of the characters on the atom's lines, `tot`
+    /// first.
+    pub orbital_labels: Vec<String>,
+}
+
+/// One band along the k-path.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QtlBand {
+    /// 1-based, as in the file.
+    pub index: usize,
+    /// eV relative to the Fermi energy, one per k-point.
+    pub energies: Vec<f32>,
+    /// `Qtl::weight_stride` characters per k-point: each atom's, in the
+    /// order of `Qtl::atoms`, then the interstitial's.
+    pub weights: Vec<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Qtl {
+    pub case_name: String,
+    /// Bohr.
+    pub lattice_constants: [f64; 3],
+    /// eV.
+    pub fermi_energy: f64,
+    pub spin_polarized: bool,
+    pub spin_orbit: bool,
+    pub atoms: Vec<QtlAtom>,
+    pub bands: Vec<QtlBand>,
+}
+
+impl Qtl {
+    /// Number of characters each atom has at a k-point, then 1 for the
+    /// interstitial.
+    pub fn weight_counts(&self) -> Vec<usize> {
+        self.atoms
+            .iter()
+            .map(|atom| atom.orbital_labels.len())
+            .chain([1])
+            .collect()
+    }
+
+    pub fn weight_stride(&self) -> usize {
+        self.weight_counts().iter().sum()
+    }
+
+    /// Moves every band by `shift` eV and refers it to `fermi_energy`.
+    pub fn shift(&mut self, shift: f64, fermi_energy: f64) {
+        for band in &mut self.bands {
+            for energy in &mut band.energies {
+                *energy += shift as f32;
+            }
+        }
+        self.fermi_energy = fermi_energy;
+    }
+}
+
+fn parse_error(file: &Path, line: Option<usize>, message: impl Into<String>) -> ReciprocalError {
+    ReciprocalError::Parse {
+        file: file.to_path_buf(),
+        line,
+        message: message.into(),
+    }
+}
+
+/// The text after `key` on `line`, if it has one.
+fn after<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.split_once(key).map(|(_, rest)| rest.trim_start())
+}
+
+/// The first whitespace-separated value after `key` on `line`.
+fn value<T: std::str::FromStr>(line: &str, key: &str) -> Option<T> {
+    after(line, key)?.split_whitespace().next()?.parse().ok()
+}
+
+/// The Fermi energy in eV from the `FERMI ENERGY=` field of the header.
+pub fn qtl_fermi_energy(text: &str, file: &Path) -> Result<f64> {
+    text.lines()
+        .take_while(|line| !line.trim_start().starts_with("BAND"))
+        .find_map(|line| value::<f64>(line, "FERMI ENERGY="))
+        .map(|ry| ry * RY_TO_EV)
+        .ok_or_else(|| parse_error(file, None, "No FERMI ENERGY in the header"))
+}
+
+/// Reads a `JATOM n MULT= m ISPLIT= s labels` line.
+fn jatom(line: &str) -> Option<QtlAtom> {
+    let labels = after(line, "ISPLIT=")?
+        .split_once(char::is_whitespace)
+        .map_or("", |(_, labels)| labels);
+    Some(QtlAtom {
+        index: value(line, "JATOM")?,
+        multiplicity: value(line, "MULT=")?,
+        isplit: value(line, "ISPLIT=")?,
+        orbital_labels: labels
+            .split(',')
+            .map(|label| label.trim().to_lowercase())
+            .filter(|label| !label.is_empty())
+            .collect(),
+    })
+}
+
+/// Reads a whole `case.qtl`. Energies are converted to eV relative to the
+/// header's Fermi energy. Each band lists its k-points in turn, one line
+/// per atom and one for the interstitial; a k-point ends where the atom
+/// index stops increasing.
+pub fn parse_qtl(text: &str, file: &Path) -> Result<Qtl> {
+    let fermi_energy = qtl_fermi_energy(text, file)?;
+    let lines: Vec<&str> = text.lines().collect();
+    let line = |i: usize| lines.get(i).copied().unwrap_or("");
+
+    let mut lattice_constants = [0.0; 3];
+    if let Some(rest) = after(line(2), "LATTICE CONST.=") {
+        for (constant, value) in lattice_constants.iter_mut().zip(rest.split_whitespace()) {
+            *constant = value.parse().unwrap_or(0.0);
+        }
+    }
+    let spin_polarized = value::<u32>(line(3), "SPIN=").is_some_and(|spin| spin > 1);
+    let spin_orbit = {
+        let tokens: Vec<&str> = line(3).split_whitespace().collect();
+        tokens
+            .windows(2)
+            .any(|pair| pair[0] == "SO" && pair[1].parse::<u32>().is_ok_and(|so| so > 0))
+    };
+
+    let mut atoms = Vec::new();
+    let mut start = 4;
+    while line(start).contains("JATOM") {
+        let atom = jatom(line(start))
+            .ok_or_else(|| parse_error(file, Some(start + 1), "Unreadable JATOM line"))?;
+        atoms.push(atom);
+        start += 1;
+    }
+
+    let mut qtl = Qtl {
+        case_name: line(0).trim().to_string(),
+        lattice_constants,
+        fermi_energy,
+        spin_polarized,
+        spin_orbit,
+        atoms,
+        bands: Vec::new(),
+    };
+    let counts = qtl.weight_counts();
+    let offsets: Vec<usize> = counts
+        .iter()
+        .scan(0, |offset, count| {
+            *offset += count;
+            Some(*offset - count)
+        })
+        .collect();
+    let stride = qtl.weight_stride();
+
+    let mut last_atom = 0;
+    for (i, text) in lines.iter().enumerate().skip(start) {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(rest) = trimmed.strip_prefix("BAND") {
+            let index = rest
+                .trim_start_matches(':')
+                .trim()
+                .parse()
+                .map_err(|_| parse_error(file, Some(i + 1), "Unreadable BAND line"))?;
+            qtl.bands.push(QtlBand {
+                index,
+                energies: Vec::new(),
+                weights: Vec::new(),
+            });
+            last_atom = 0;
+            continue;
+        }
+
+        let band = qtl
+            .bands
+            .last_mut()
+            .ok_or_else(|| parse_error(file, Some(i + 1), "Expected a BAND line"))?;
+        let mut tokens = trimmed.split_whitespace();
+        let (Some(energy), Some(atom)) = (
+            tokens.next().and_then(|t| t.parse::<f64>().ok()),
+            tokens.next().and_then(|t| t.parse::<usize>().ok()),
+        ) else {
+            return Err(parse_error(file, Some(i + 1), "Expected energy and atom"));
+        };
+        if atom <= last_atom || band.energies.is_empty() {
+            band.energies
+                .push((energy * RY_TO_EV - fermi_energy) as f32);
+            band.weights.resize(band.weights.len() + stride, 0.0);
+        }
+        last_atom = atom;
+
+        // Lines for atoms beyond the header's are not kept.
+        let Some(slot) = atom.checked_sub(1).filter(|&slot| slot < counts.len()) else {
+            continue;
+        };
+        let base = band.weights.len() - stride + offsets[slot];
+        for (n, token) in tokens.take(counts[slot]).enumerate() {
+            band.weights[base + n] = token
+                .parse()
+                .map_err(|_| parse_error(file, Some(i + 1), "Unreadable character"))?;
+        }
+    }
+
+    Ok(qtl)
+}
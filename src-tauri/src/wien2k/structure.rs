@@ -0,0 +1,400 @@
+//! Wien2k `case.struct` files.
+//!
+//! The format is fixed-column Fortran output. Fields are read from their
+//! columns where Wien2k puts them, falling back to whitespace-separated
+//! values for hand-edited files, and written back in Wien2k's own layout.
+
+use crate::error::{ReciprocalError, Result};
+use crate::lattice::Cell;
+use crate::symmetry::SymmetryOperation;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LatticeType {
+    /// Primitive, including triclinic and monoclinic cells.
+    P,
+    /// Hexagonal, γ = 120°.
+    H,
+    /// Rhombohedral, given by hexagonal axes but with positions and
+    /// operations in rhombohedral coordinates.
+    R,
+    F,
+    /// Body-centred.
+    B,
+    #[serde(rename = "CXY")]
+    Cxy,
+    #[serde(rename = "CYZ")]
+    Cyz,
+    #[serde(rename = "CXZ")]
+    Cxz,
+}
+
+impl LatticeType {
+    fn parse(text: &str) -> Option<Self> {
+        Some(match text.to_ascii_uppercase().as_str() {
+            "P" => LatticeType::P,
+            "H" => LatticeType::H,
+            "R" => LatticeType::R,
+            "F" => LatticeType::F,
+            "B" => LatticeType::B,
+            "CXY" => LatticeType::Cxy,
+            "CYZ" => LatticeType::Cyz,
+            "CXZ" => LatticeType::Cxz,
+            _ => return None,
+        })
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LatticeType::P => "P",
+            LatticeType::H => "H",
+            LatticeType::R => "R",
+            LatticeType::F => "F",
+            LatticeType::B => "B",
+            LatticeType::Cxy => "CXY",
+            LatticeType::Cyz => "CYZ",
+            LatticeType::Cxz => "CXZ",
+        }
+    }
+
+    /// Translations the lattice adds to each listed position, in the
+    /// conventional cell. Wien2k lists positions and operations without
+    /// them.
+    pub fn centring(self) -> &'static [[f64; 3]] {
+        match self {
+            LatticeType::P | LatticeType::H | LatticeType::R => &[],
+            LatticeType::F => &[[0.0, 0.5, 0.5], [0.5, 0.0, 0.5], [0.5, 0.5, 0.0]],
+            LatticeType::B => &[[0.5, 0.5, 0.5]],
+            LatticeType::Cxy => &[[0.5, 0.5, 0.0]],
+            LatticeType::Cyz => &[[0.0, 0.5, 0.5]],
+            LatticeType::Cxz => &[[0.5, 0.0, 0.5]],
+        }
+    }
+}
+
+/// A non-equivalent atom and its MULT equivalent positions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructAtom {
+    /// Up to 10 characters, starting with the element symbol.
+    pub name: String,
+    pub positions: Vec<[f64; 3]>,
+    pub isplit: i32,
+    /// Radial mesh points.
+    pub npt: u32,
+    /// First radial mesh point, in bohr.
+    pub r0: f64,
+    /// Muffin-tin radius, in bohr.
+    pub rmt: f64,
+    /// Nuclear charge.
+    pub z: f64,
+    pub local_rotation: [[f64; 3]; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Wien2kStruct {
+    pub title: String,
+    pub lattice: LatticeType,
+    pub space_group_number: Option<u32>,
+    pub space_group_symbol: Option<String>,
+    /// `RELA` rather than `NREL`.
+    pub relativistic: bool,
+    /// Lattice parameters; lengths in bohr, as in the file.
+    pub cell: [f64; 6],
+    pub atoms: Vec<StructAtom>,
+    pub symmetry_operations: Vec<SymmetryOperation>,
+}
+
+/// A fixed-width field of `line`, trimmed; empty past the end of the line.
+fn column(line: &str, start: usize, width: usize) -> &str {
+    let end = (start + width).min(line.len());
+    line.get(start.min(end)..end).unwrap_or("").trim()
+}
+
+/// The value after `key` on `line`, e.g. `MULT=` in `MULT= 2`.
+fn after<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.split_whitespace().next()
+}
+
+struct Lines<'a> {
+    lines: Vec<&'a str>,
+    next: usize,
+    file: &'a Path,
+}
+
+impl<'a> Lines<'a> {
+    fn error(&self, message: impl Into<String>) -> ReciprocalError {
+        ReciprocalError::Parse {
+            file: self.file.to_path_buf(),
+            line: Some(self.next.max(1)),
+            message: message.into(),
+        }
+    }
+
+    fn next(&mut self, what: &str) -> Result<&'a str> {
+        let line = self
+            .lines
+            .get(self.next)
+            .copied()
+            .ok_or_else(|| self.error(format!("File ends before the {}", what)))?;
+        self.next += 1;
+        Ok(line)
+    }
+
+    fn number<T: std::str::FromStr>(&self, text: Option<&str>, what: &str) -> Result<T> {
+        text.and_then(|t| t.parse().ok())
+            .ok_or_else(|| self.error(format!("Expected {}", what)))
+    }
+
+    /// Numbers in fixed columns of `width` from `start`, or failing that the
+    /// whitespace-separated values after `start`.
+    fn numbers<const N: usize>(
+        &self,
+        line: &str,
+        start: usize,
+        width: usize,
+        what: &str,
+    ) -> Result<[f64; N]> {
+        let fixed: Option<Vec<f64>> = (0..N)
+            .map(|i| column(line, start + i * width, width).parse().ok())
+            .collect();
+        let values = fixed.or_else(|| {
+            let free: Vec<f64> = line
+                .get(start..)?
+                .split_whitespace()
+                .map_while(|t| t.parse().ok())
+                .collect();
+            (free.len() >= N).then_some(free)
+        });
+        values
+            .map(|v| std::array::from_fn(|i| v[i]))
+            .ok_or_else(|| self.error(format!("Expected {} numbers for {}", N, what)))
+    }
+
+    fn position(&self, line: &str) -> Result<[f64; 3]> {
+        let coordinate = |key| self.number::<f64>(after(line, key), "an atom position");
+        Ok([coordinate("X=")?, coordinate("Y=")?, coordinate("Z=")?])
+    }
+}
+
+impl Wien2kStruct {
+    pub fn parse(text: &str, file: &Path) -> Result<Self> {
+        let mut lines = Lines {
+            lines: text.lines().collect(),
+            next: 0,
+            file,
+        };
+
+        let title = lines.next("title")?.trim_end().to_string();
+
+        let header = lines.next("lattice type")?;
+        let lattice = LatticeType::parse(column(header, 0, 4))
+            .ok_or_else(|| lines.error("Unknown lattice type"))?;
+        let mut counts = header
+            .split_once(':')
+            .map(|(_, rest)| rest)
+            .unwrap_or("")
+            .split_whitespace();
+        let atom_count: usize = lines.number(counts.next(), "the number of atoms")?;
+        let (space_group_number, space_group_symbol) = match counts.next() {
+            Some(group) => {
+                let (number, symbol) = group.split_once('_').unwrap_or((group, ""));
+                let symbol = if symbol.is_empty() {
+                    counts.next().unwrap_or("")
+                } else {
+                    symbol
+                };
+                (
+                    number.parse().ok(),
+                    (!symbol.is_empty()).then(|| symbol.to_string()),
+                )
+            }
+            None => (None, None),
+        };
+
+        let mode = lines.next("calculation mode")?;
+        let relativistic = !mode.to_ascii_uppercase().contains("NREL");
+
+        let cell_line = lines.next("lattice parameters")?;
+        let cell = lines.numbers::<6>(cell_line, 0, 10, "the lattice parameters")?;
+
+        let mut atoms = Vec::with_capacity(atom_count);
+        for _ in 0..atom_count {
+            let first = lines.next("atom")?;
+            if !first.trim_start().starts_with("ATOM") {
+                return Err(lines.error("Expected an ATOM line"));
+            }
+            let mut positions = vec![lines.position(first)?];
+            let mult_line = lines.next("MULT line")?;
+            let mult: usize = lines.number(after(mult_line, "MULT="), "MULT")?;
+            let isplit: i32 = lines.number(after(mult_line, "ISPLIT="), "ISPLIT")?;
+            for _ in 1..mult {
+                let line = lines.next("equivalent position")?;
+                positions.push(lines.position(line)?);
+            }
+
+            let line = lines.next("atom name")?;
+            let name = column(line, 0, 10).to_string();
+            let npt = lines.number(after(line, "NPT="), "NPT")?;
+            let r0 = lines.number(after(line, "R0="), "R0")?;
+            let rmt = lines.number(after(line, "RMT="), "RMT")?;
+            let z = lines.number(after(line, "Z:"), "Z")?;
+
+            let mut local_rotation = [[0.0; 3]; 3];
+            for row in &mut local_rotation {
+                let line = lines.next("local rotation matrix")?;
+                *row = lines.numbers::<3>(line, 20, 10, "the local rotation matrix")?;
+            }
+
+            atoms.push(StructAtom {
+                name,
+                positions,
+                isplit,
+                npt,
+                r0,
+                rmt,
+                z,
+                local_rotation,
+            });
+        }
+
+        let count_line = lines.next("symmetry operations")?;
+        if !count_line
+            .to_ascii_uppercase()
+            .contains("SYMMETRY OPERATIONS")
+        {
+            return Err(lines.error("Expected the number of symmetry operations"));
+        }
+        let op_count: usize = lines.number(
+            count_line.split_whitespace().next(),
+            "the number of symmetry operations",
+        )?;
+        let mut symmetry_operations = Vec::with_capacity(op_count);
+        for _ in 0..op_count {
+            let mut op = SymmetryOperation::identity();
+            for n in 0..3 {
+                let line = lines.next("symmetry operation")?;
+                // 3I2,F11.8: rotation digits may run together, as in " 0-1 0".
+                let fixed: Option<[i32; 3]> = (0..3)
+                    .map(|k| column(line, 2 * k, 2).parse().ok())
+                    .collect::<Option<Vec<_>>>()
+                    .map(|r| [r[0], r[1], r[2]]);
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let (rotation, translation) = match fixed {
+                    Some(rotation) => (Some(rotation), column(line, 6, 11).parse().ok()),
+                    None if fields.len() >= 4 => (
+                        fields[..3]
+                            .iter()
+                            .map(|f| f.parse().ok())
+                            .collect::<Option<Vec<i32>>>()
+                            .map(|r| [r[0], r[1], r[2]]),
+                        fields[3].parse().ok(),
+                    ),
+                    None => (None, None),
+                };
+                op.rotation[n] =
+                    rotation.ok_or_else(|| lines.error("Expected a symmetry operation row"))?;
+                op.translation[n] = translation
+                    .ok_or_else(|| lines.error("Expected a symmetry operation translation"))?;
+            }
+            lines.next("symmetry operation index")?;
+            symmetry_operations.push(op);
+        }
+
+        Ok(Wien2kStruct {
+            title,
+            lattice,
+            space_group_number,
+            space_group_symbol,
+            relativistic,
+            cell,
+            atoms,
+            symmetry_operations,
+        })
+    }
+
+    /// The file as Wien2k writes it.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let mut line = |text: String| {
+            out.push_str(&text);
+            out.push('\n');
+        };
+
+        line(self.title.clone());
+        let group = match (self.space_group_number, &self.space_group_symbol) {
+            (Some(number), Some(symbol)) => format!(" {}_{}", number, symbol),
+            (Some(number), None) => format!(" {}", number),
+            _ => String::new(),
+        };
+        line(format!(
+            "{:<4}LATTICE,NONEQUIV.ATOMS:{:>3}{}",
+            self.lattice.as_str(),
+            self.atoms.len(),
+            group
+        ));
+        line(format!(
+            "MODE OF CALC={} unit=bohr",
+            if self.relativistic { "RELA" } else { "NREL" }
+        ));
+        line(self.cell.iter().map(|v| format!("{:10.6}", v)).collect());
+
+        for (i, atom) in self.atoms.iter().enumerate() {
+            let index = -(i as i32 + 1);
+            for (n, p) in atom.positions.iter().enumerate() {
+                let prefix = if n == 0 { "ATOM" } else { "    " };
+                line(format!(
+                    "{}{:>4}: X={:10.8} Y={:10.8} Z={:10.8}",
+                    prefix, index, p[0], p[1], p[2]
+                ));
+                if n == 0 {
+                    line(format!(
+                        "          MULT={:>2}          ISPLIT={:>2}",
+                        atom.positions.len(),
+                        atom.isplit
+                    ));
+                }
+            }
+            line(format!(
+                "{:<10} NPT={:>5}  R0={:10.8} RMT={:10.4}   Z:{:10.5}",
+                atom.name, atom.npt, atom.r0, atom.rmt, atom.z
+            ));
+            for (n, row) in atom.local_rotation.iter().enumerate() {
+                let label = if n == 0 { "LOCAL ROT MATRIX:" } else { "" };
+                line(format!(
+                    "{:<20}{:10.7}{:10.7}{:10.7}",
+                    label, row[0], row[1], row[2]
+                ));
+            }
+        }
+
+        line(format!(
+            "{:>4}      NUMBER OF SYMMETRY OPERATIONS",
+            self.symmetry_operations.len()
+        ));
+        for (i, op) in self.symmetry_operations.iter().enumerate() {
+            for n in 0..3 {
+                let r = op.rotation[n];
+                line(format!(
+                    "{:>2}{:>2}{:>2}{:11.8}",
+                    r[0], r[1], r[2], op.translation[n]
+                ));
+            }
+            line(format!("{:>8}", i + 1));
+        }
+        out
+    }
+
+    pub fn cell(&self) -> Cell {
+        let [a, b, c, alpha, beta, gamma] = self.cell;
+        Cell {
+            a,
+            b,
+            c,
+            alpha,
+            beta,
+            gamma,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use reciprocal_lib::band_grid::GridColour;
+use reciprocal_lib::band_grid::HARTREE_TO_EV;
+use reciprocal_lib::bxsf;
+use reciprocal_lib::error::ReciprocalError;
+use reciprocal_lib::frmsf;
+use std::path::Path;
+
+const GRID: &str = "\
+BEGIN_INFO
+  # Launch as: xcrysden --bxsf case.bxsf
+  Fermi Energy: 0.5
+END_INFO
+BEGIN_BLOCK_BANDGRID_3D
+band_energies
+BEGIN_BANDGRID_3D_fermi
+2
+2 2 3
+0.0 0.0 0.0
+1.0 0.0 0.0
+0.0 1.0 0.0
+0.0 0.0 2.0
+BAND: 1
+0.000 0.001 0.002
+0.010 0.011 0.012
+0.100 0.101 0.102
+0.110 0.111 0.112
+BAND: 2
+1 1 1 1 1 1 1 1 1 1 1 1
+END_BANDGRID_3D
+END_BLOCK_BANDGRID_3D
+";
+
+#[test]
+fn reads_bands_in_ev_with_x_fastest() {
+    let grid = bxsf::parse(GRID, Path::new("case.bxsf")).unwrap();
+    assert_eq!((grid.nx, grid.ny, grid.nz), (2, 2, 3));
+    assert!((grid.fermi_energy - 0.5 * HARTREE_TO_EV).abs() < 1e-9);
+    assert_eq!(grid.reciprocal_vectors[2], [0.0, 0.0, 2.0]);
+    assert_eq!(grid.bands.len(), 2);
+
+    // The file lists z fastest: the value at (x, y, z) is x·0.1 + y·0.01 + z·0.001.
+    let band = &grid.bands[0];
+    for (x, y, z) in [(1, 0, 0), (0, 1, 2), (1, 1, 1)] {
+        let hartree = x as f64 * 0.1 + y as f64 * 0.01 + z as f64 * 0.001;
+        let ev = band[grid.index(x, y, z)] as f64;
+        assert!(
+            (ev - hartree * HARTREE_TO_EV).abs() < 1e-4,
+            "{:?}",
+            (x, y, z)
+        );
+    }
+    assert!(grid.bands[1]
+        .iter()
+        .all(|&e| (e as f64 - HARTREE_TO_EV).abs() < 1e-4));
+}
+
+#[test]
+fn short_band_is_reported_at_its_line() {
+    let text = GRID.replace("0.110 0.111 0.112\n", "");
+    match bxsf::parse(&text, Path::new("case.bxsf")) {
+        Err(ReciprocalError::Parse { line, message, .. }) => {
+            assert_eq!(line, Some(18), "{}", message);
+        }
+        other => panic!("expected a parse error, got {:?}", other.map(|g| g.nx)),
+    }
+}
+
+#[test]
+fn written_grid_reads_back_the_same() {
+    let grid = bxsf::parse(GRID, Path::new("case.bxsf")).unwrap();
+    let text = bxsf::to_text(&grid, &[1, 0], "test grid");
+    let copy = bxsf::parse(&text, Path::new("copy.bxsf")).unwrap();
+    assert!((copy.fermi_energy - grid.fermi_energy).abs() < 1e-6);
+    assert_eq!(copy.reciprocal_vectors, grid.reciprocal_vectors);
+    for (copied, original) in copy.bands[1].iter().zip(&grid.bands[0]) {
+        assert!((copied - original).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn frmsf_drops_the_periodic_end_points() {
+    let grid = bxsf::parse(GRID, Path::new("case.bxsf")).unwrap();
+    let text = frmsf::to_text(&grid, &[0], Some(GridColour::FermiVelocity));
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "1 1 2");
+    assert_eq!(lines[1], "0");
+    assert_eq!(lines[2], "1");
+    // Two energies relative to the Fermi energy, then two velocities.
+    assert_eq!(lines.len(), 6 + 4);
+    let first: f64 = lines[6].parse().unwrap();
+    assert!((first + 0.5 * HARTREE_TO_EV).abs() < 1e-4);
+    // With two points along z both neighbours are the same point, so the
+    // central difference is zero.
+    let speed: f64 = lines[8].parse().unwrap();
+    assert!(speed.abs() < 1e-6);
+}
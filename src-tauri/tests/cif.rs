@@ -0,0 +1,193 @@
+use reciprocal_lib::cif::{self, parse_number, Value};
+use reciprocal_lib::error::ReciprocalError;
+use std::path::Path;
+
+const SR2RUO4: &str = r#"#(C) 2024 by FIZ Karlsruhe
+data_publication
+_citation_journal_full 'Physical Review B'
+loop_
+_citation_author_name
+'O''Brien, M.'
+"Maeno, Y."
+
+data_95815-ICSD
+_database_code_ICSD 95815
+_audit_creation_date 2003-10-01
+_chemical_name_common 'Strontium ruthenate'
+_chemical_formula_sum 'O4 Ru1 Sr2'
+_citation_title
+;
+Low temperature structure
+ of Sr2 Ru O4
+;
+_cell_length_a 3.8603(1)
+_cell_length_b 3.8603(1)
+_cell_length_c 12.729(1)
+_cell_angle_alpha 90.
+_cell_angle_beta 90.
+_cell_angle_gamma 90.
+_cell_volume 189.69(2)
+_cell_formula_units_Z 2
+_symmetry_space_group_name_H-M 'I 4/m m m'
+_symmetry_Int_Tables_number 139
+_diffrn_ambient_temperature 100
+loop_
+_symmetry_equiv_pos_site_id
+_symmetry_equiv_pos_as_xyz
+1 'x, y, z'
+2 '-x, -y, -z' # comments end at the line
+loop_
+_atom_site_label
+_atom_site_type_symbol
+_atom_site_symmetry_multiplicity
+_atom_site_Wyckoff_symbol
+_atom_site_fract_x
+_atom_site_fract_y
+_atom_site_fract_z
+_atom_site_occupancy
+Sr1 Sr2+ 4 e 0 0 0.35319(3) 1.
+Ru1 Ru4+ 2 a 0 0 0 1.
+O1 O2- 4 c 0 0.5 0 ?
+O2 O2- 4 e 0 0 0.1615(2) 1.
+loop_
+_atom_site_aniso_label
+_atom_site_aniso_U_11
+_atom_site_aniso_U_22
+_atom_site_aniso_U_33
+Sr1 0.0050(1) 0.0050(1) .
+"#;
+
+#[test]
+fn parses_structure_from_the_block_that_has_one() {
+    let data = cif::parse_crystal_data(SR2RUO4, Path::new("Sr2RuO4.cif")).unwrap();
+
+    assert_eq!(data.formula_sum.as_deref(), Some("O4 Ru1 Sr2"));
+    assert_eq!(
+        data.chemical_name_common.as_deref(),
+        Some("Strontium ruthenate")
+    );
+    assert_eq!(data.database_code.as_deref(), Some("95815"));
+    assert_eq!(data.cell_length_a.value, 3.8603);
+    assert!((data.cell_length_c.uncertainty.unwrap() - 0.001).abs() < 1e-12);
+    assert_eq!(data.cell_angle_beta.uncertainty, None);
+    assert_eq!(data.cell_formula_units_z, Some(2));
+    assert_eq!(data.space_group_hm.as_deref(), Some("I 4/m m m"));
+    assert_eq!(data.space_group_it_number, Some(139));
+    assert_eq!(data.measurement_temperature, Some(100.0));
+    assert_eq!(data.symmetry_operations, ["x, y, z", "-x, -y, -z"]);
+
+    assert_eq!(data.atom_sites.len(), 4);
+    let o1 = &data.atom_sites[2];
+    assert_eq!((o1.label.as_str(), o1.type_symbol.as_str()), ("O1", "O2-"));
+    assert_eq!((o1.fract_y, o1.occupancy), (0.5, 1.0));
+    assert_eq!(o1.wyckoff_symbol.as_deref(), Some("c"));
+    assert_eq!(o1.symmetry_multiplicity, Some(4));
+
+    // U_ij in Å² become β_ij = 2π² a*² U for a tetragonal cell.
+    let sr = &data.anisotropic_params[0];
+    let a_star = 1.0 / 3.8603;
+    let expected = 2.0 * std::f64::consts::PI.powi(2) * a_star * a_star * 0.005;
+    assert!((sr.beta_11 - expected).abs() < 1e-12);
+    assert_eq!((sr.type_symbol.as_str(), sr.beta_33), ("Sr", 0.0));
+
+    let citation = data.citation.unwrap();
+    assert_eq!(
+        citation.title.as_deref(),
+        Some("Low temperature structure of Sr2 Ru O4")
+    );
+    // The publication block's citation isn't part of the structure's block.
+    assert_eq!(citation.journal, None);
+
+    let json =
+        serde_json::to_value(cif::parse_crystal_data(SR2RUO4, Path::new("x")).unwrap()).unwrap();
+    assert_eq!(json["cell_formula_units_Z"], 2);
+    assert_eq!(json["space_group_HM"], "I 4/m m m");
+    assert!(json.get("density").is_none());
+}
+
+#[test]
+fn follows_cif_quoting_rules() {
+    let text = "data_a\n_a 'it''s'\n_b 'O'Brien'\n_c '?'\n_d ?\n_e .\n_f x#y\n_g\n;line one\nline two\n;\n";
+    let cif = cif::parse(text, Path::new("a.cif")).unwrap();
+    let block = &cif.blocks[0];
+    assert_eq!(block.name, "a");
+    assert_eq!(block.text("_A"), Some("it''s"));
+    assert_eq!(block.text("_b"), Some("O'Brien"));
+    assert_eq!(block.value("_c"), Some(&Value::Text("?".into())));
+    assert_eq!(block.value("_d"), Some(&Value::Unknown));
+    assert_eq!(block.value("_e"), Some(&Value::Inapplicable));
+    assert_eq!(block.text("_f"), Some("x#y"));
+    assert_eq!(block.text("_g"), Some("line one\nline two"));
+}
+
+#[test]
+fn reads_non_ascii_unquoted_values() {
+    let text = "data_x\n_cell_length_a 5.0\n_chemical_name_common 1000°C\n_b é\n";
+    let cif = cif::parse(text, Path::new("a.cif")).unwrap();
+    let block = &cif.blocks[0];
+    assert_eq!(block.text("_chemical_name_common"), Some("1000°C"));
+    assert_eq!(block.text("_b"), Some("é"));
+}
+
+#[test]
+fn reports_where_syntax_errors_are() {
+    let cases = [
+        ("data_a\n_a 1\n_b 'open\n", 3),
+        ("data_a\nloop_\n_x\n_y\n1 2 3\n", 2),
+        ("_a 1\n", 1),
+        ("data_a\n_a\n;never closed\n", 3),
+    ];
+    for (text, expected) in cases {
+        match cif::parse(text, Path::new("bad.cif")) {
+            Err(ReciprocalError::Parse { line, .. }) => {
+                assert_eq!(line, Some(expected), "{}", text)
+            }
+            other => panic!("expected a parse error for {:?}, got {:?}", text, other),
+        }
+    }
+}
+
+#[test]
+fn numbers_carry_their_uncertainty() {
+    let n = parse_number("5.194(1)").unwrap();
+    assert_eq!(n.value, 5.194);
+    assert!((n.uncertainty.unwrap() - 0.001).abs() < 1e-15);
+    assert!((parse_number("1.2e-3(4)").unwrap().uncertainty.unwrap() - 0.0004).abs() < 1e-15);
+    assert_eq!(parse_number("12(3)").unwrap().uncertainty, Some(3.0));
+    assert_eq!(parse_number("-0.25").unwrap().uncertainty, None);
+    assert!(parse_number("Sr").is_none());
+}
+
+#[test]
+fn written_cif_reads_back_the_same() {
+    let mut data = cif::parse_crystal_data(SR2RUO4, Path::new("Sr2RuO4.cif")).unwrap();
+    let citation = data.citation.as_mut().unwrap();
+    citation.authors = vec!["O'Brien, M.".into(), "Maeno, Y.".into()];
+    citation.journal = Some("Physical Review B".into());
+    citation.year = Some(2003);
+    data.atom_sites[0].label = "data_1".into();
+    data.chemical_name_common = Some("it's \"quoted\"' both ways".into());
+
+    let text = cif::write_cif(&data, "Sr2RuO4 low T");
+    assert!(text.contains("data_Sr2RuO4_low_T\n"));
+    assert!(text.contains("_cell_length_c 12.729(1)\n"));
+
+    let back = cif::parse_crystal_data(&text, Path::new("written.cif")).unwrap();
+    assert_eq!(back, data);
+}
+
+#[test]
+fn null_numbers_from_the_frontend_are_written_as_unknown() {
+    let json = r#"{
+        "cell_length_a": {"value": 4.0}, "cell_length_b": {"value": 4.0},
+        "cell_length_c": {"value": 4.0}, "cell_angle_alpha": {"value": 90},
+        "cell_angle_beta": {"value": 90}, "cell_angle_gamma": {"value": 90},
+        "atom_sites": [{"label": "Fe1", "type_symbol": "Fe", "fract_x": 0,
+            "fract_y": 0, "fract_z": null, "occupancy": 1}],
+        "symmetry_operations": [], "anisotropic_params": []
+    }"#;
+    let data: cif::CrystalData = serde_json::from_str(json).unwrap();
+    assert!(data.atom_sites[0].fract_z.is_nan());
+    let text = cif::write_cif(&data, "Fe");
+    assert!(text.contains(" Fe1 Fe 0 0 ? 1\n"), "{}", text);
+}
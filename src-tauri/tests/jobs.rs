@@ -0,0 +1,58 @@
+use reciprocal_lib::error::ReciprocalError;
+use reciprocal_lib::jobs::{Job, JobKind, JobRegistry, JobState};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+fn recorder() -> (Arc<Mutex<Vec<Job>>>, impl Fn(&Job) + Send + Sync + 'static) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    (events, move |job: &Job| {
+        sink.lock().unwrap().push(job.clone())
+    })
+}
+
+#[test]
+fn finished_jobs_report_progress_and_hand_over_their_result() {
+    let jobs = JobRegistry::new();
+    let (events, notify) = recorder();
+    let (job, progress) = jobs.start(JobKind::VerifyLibrary, "library", notify);
+    assert_eq!(jobs.list()[0].state, JobState::Running);
+
+    progress.phase("Reading", Some(5), Some(1));
+    let mut text = String::new();
+    progress
+        .reader("hello".as_bytes())
+        .read_to_string(&mut text)
+        .unwrap();
+    progress.file_done();
+    progress.finish(Ok(text));
+
+    let last = events.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(last.state, JobState::Completed);
+    assert_eq!(last.progress.bytes_done, 5);
+    assert_eq!(last.progress.files_done, 1);
+
+    assert_eq!(jobs.take_result(&job.id).unwrap(), "hello");
+    assert!(jobs.list().is_empty());
+}
+
+#[test]
+fn cancelled_jobs_stop_at_their_next_check() {
+    let jobs = JobRegistry::new();
+    let (_events, notify) = recorder();
+    let (job, progress) = jobs.start(JobKind::ImportBandStructure, "bands", notify);
+
+    jobs.cancel(&job.id).unwrap();
+    let worker = thread::spawn(move || {
+        let result = progress.check().map(|_| "finished");
+        progress.finish(result);
+    });
+    worker.join().unwrap();
+
+    assert_eq!(jobs.list()[0].state, JobState::Cancelled);
+    assert!(matches!(
+        jobs.take_result(&job.id),
+        Err(ReciprocalError::Cancelled)
+    ));
+}
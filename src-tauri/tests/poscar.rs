@@ -0,0 +1,69 @@
+use reciprocal_lib::cif;
+use reciprocal_lib::poscar::Poscar;
+use std::path::Path;
+
+const SI_CONTCAR: &str = "\
+Si diamond
+   2.0
+     0.0000000000000000    1.3575000000000000    1.3575000000000000
+     1.3575000000000000    0.0000000000000000    1.3575000000000000
+     1.3575000000000000    1.3575000000000000    0.0000000000000000
+   Si_sv
+     2
+Selective dynamics
+Cartesian
+  0.0000000000000000  0.0000000000000000  0.0000000000000000   F   F   F
+  0.6787500000000000  0.6787500000000000  0.6787500000000000   T   T   T
+";
+
+#[test]
+fn reads_scaled_cartesian_positions_and_relaxation_flags() {
+    let poscar = Poscar::parse(SI_CONTCAR, Path::new("CONTCAR")).unwrap();
+    assert_eq!(poscar.comment, "Si diamond");
+    assert_eq!(poscar.lattice[0], [0.0, 2.715, 2.715]);
+    assert_eq!(poscar.species, [("Si_sv".to_string(), 2)]);
+    let p = poscar.positions[1];
+    assert!(p.iter().all(|x| (x - 0.25).abs() < 1e-12), "{:?}", p);
+    assert_eq!(poscar.selective_dynamics, Some(vec![[false; 3], [true; 3]]));
+
+    let data = poscar.to_crystal_data();
+    assert_eq!(data.atom_sites[1].label, "Si2");
+    assert_eq!(data.atom_sites[1].type_symbol, "Si");
+    assert!((data.cell_length_a.value - 2.715 * 2f64.sqrt()).abs() < 1e-9);
+    assert!((data.cell_angle_gamma.value - 60.0).abs() < 1e-9);
+}
+
+#[test]
+fn takes_species_from_the_comment_in_vasp_4_files() {
+    let text = "Fe Al\n-23.0\n1 0 0\n0 1 0\n0 0 1\n1 1\nDirect\n0 0 0\n0.5 0.5 0.5\n";
+    let poscar = Poscar::parse(text, Path::new("POSCAR")).unwrap();
+    let names: Vec<_> = poscar.species.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, ["Fe", "Al"]);
+    // A negative scale is the volume.
+    assert!((poscar.lattice[2][2] - 23f64.cbrt()).abs() < 1e-12);
+}
+
+#[test]
+fn written_poscar_has_every_atom_of_the_cell() {
+    let cif = "data_NaCl\n_cell_length_a 5.64\n_cell_length_b 5.64\n_cell_length_c 5.64\n\
+               _cell_angle_alpha 90\n_cell_angle_beta 90\n_cell_angle_gamma 90\n\
+               loop_\n_symmetry_equiv_pos_as_xyz\nx,y,z\nx,y+1/2,z+1/2\nx+1/2,y,z+1/2\n\
+               x+1/2,y+1/2,z\n\
+               loop_\n_atom_site_label\n_atom_site_type_symbol\n_atom_site_fract_x\n\
+               _atom_site_fract_y\n_atom_site_fract_z\n_atom_site_occupancy\n\
+               Na1 Na 0 0 0 0.9\nK1 K 0 0 0 0.1\nCl1 Cl 0.5 0.5 0.5 1\n";
+    let data = cif::parse_crystal_data(cif, Path::new("NaCl.cif")).unwrap();
+    let poscar = Poscar::from_crystal_data(&data, "NaCl", Path::new("NaCl.cif")).unwrap();
+    // K shares Na's site, which Na takes.
+    assert_eq!(
+        poscar.species,
+        [("Na".to_string(), 4), ("Cl".to_string(), 4)]
+    );
+
+    let reread = Poscar::parse(&poscar.to_text(), Path::new("POSCAR")).unwrap();
+    assert_eq!(reread.species, poscar.species);
+    for (p, q) in reread.positions.iter().zip(&poscar.positions) {
+        assert!((0..3).all(|i| (p[i] - q[i]).abs() < 1e-12));
+    }
+    assert!((reread.lattice[0][0] - 5.64).abs() < 1e-12);
+}
@@ -0,0 +1,857 @@
+use reciprocal_lib::error::{ReciprocalError, ResourceKind};
+use reciprocal_lib::ids::{DatasetId, ProjectId, TrashId};
+use reciprocal_lib::jobs::Progress;
+use reciprocal_lib::links::LinkStatus;
+use reciprocal_lib::{FermiSurfaceKind, ImportOptions, ProjectStore, Spin};
+use std::fs;
+use std::path::Path;
+
+fn store() -> (tempfile::TempDir, ProjectStore) {
+    let dir = tempfile::tempdir().unwrap();
+    let store = ProjectStore::new(dir.path());
+    (dir, store)
+}
+
+#[test]
+fn creates_and_lists_projects() {
+    let (_dir, store) = store();
+    assert!(store.load_projects().unwrap().items.is_empty());
+
+    let project = store
+        .create_project("Silicon".to_string(), "Si".to_string())
+        .unwrap();
+    let listing = store.load_projects().unwrap();
+    assert_eq!(listing.items.len(), 1);
+    assert_eq!(listing.items[0].id, project.id);
+    assert!(listing.diagnostics.is_empty());
+}
+
+#[test]
+fn missing_project_is_not_found() {
+    let (_dir, store) = store();
+    match store.read_cif_file(&ProjectId::new()) {
+        Err(ReciprocalError::NotFound { kind, .. }) => assert_eq!(kind, ResourceKind::Project),
+        other => panic!("expected NotFound, got {:?}", other),
+    }
+}
+
+const QTL: &str = "GaAs
+
+ LATTICE CONST.=  10.6800  10.6800  10.6800   FERMI ENERGY=  0.40000
+    90 < NMAT <   100   SPIN=1   NAT= 1      SO 0
+ JATOM  1 MULT= 2  ISPLIT= 2 tot,0,1
+ BAND:   1
+  0.100000   1   0.50000   0.30000   0.20000
+  0.100000   2   0.50000
+  0.200000   1   0.60000   0.10000   0.50000
+  0.200000   2   0.40000
+";
+
+const KLIST: &str = "\
+GAMMA              0         0         0        20  2.0
+X                 20         0         0        20  2.0
+END
+";
+
+#[test]
+fn band_structure_round_trips_through_the_trash() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    let qtl = dir.path().join("case.qtl");
+    let klist = dir.path().join("case.klist_band");
+    fs::write(&qtl, QTL).unwrap();
+    fs::write(&klist, KLIST).unwrap();
+
+    let info = store
+        .import_band_structure(
+            &project_id,
+            "Bands".to_string(),
+            &qtl,
+            "case.qtl".to_string(),
+            &klist,
+            "case.klist_band".to_string(),
+            Default::default(),
+            &Progress::none(),
+        )
+        .unwrap();
+    let band_id = DatasetId::parse(&info.id).unwrap();
+    assert_eq!(
+        store
+            .load_band_structure_files(&project_id, &band_id, &Progress::none())
+            .unwrap(),
+        (QTL.to_string(), KLIST.to_string())
+    );
+
+    let entry = store.delete_band_structure(&project_id, &band_id).unwrap();
+    assert!(store
+        .list_band_structures(&project_id)
+        .unwrap()
+        .items
+        .is_empty());
+
+    store
+        .restore_from_trash(&TrashId::parse(&entry.id).unwrap())
+        .unwrap();
+    let listing = store.list_band_structures(&project_id).unwrap();
+    assert_eq!(listing.items.len(), 1);
+    assert_eq!(listing.items[0].name, "Bands");
+}
+
+#[test]
+fn spin_polarized_band_structures_keep_both_channels() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("Fe".to_string(), "Fe".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    // One band over the two k-points of KLIST, with a line per k-point for
+    // the atom and the interstitial.
+    let qtl = |ef: &str, energies: [&str; 2]| {
+        let mut text = format!(
+            "Fe\n\n LATTICE CONST.=  5.4169  5.4169  5.4169   FERMI ENERGY=  {}\n\
+             \x20   40 < NMAT <    45   SPIN=2   NAT= 1      SO 0\n\
+             \x20JATOM  1 MULT= 1  ISPLIT= 2 tot,0,1\n\
+             \x20BAND:   1\n",
+            ef
+        );
+        for energy in energies {
+            text += &format!("  {}   1   0.70000   0.40000   0.30000\n", energy);
+            text += &format!("  {}   2   0.30000\n", energy);
+        }
+        text
+    };
+    let up = qtl("0.61000", ["0.100000", "0.200000"]);
+    for (name, contents) in [
+        ("case.qtlup", up.clone()),
+        ("case.qtldn", qtl("0.62000", ["0.150000", "0.260000"])),
+        ("case.qtl", QTL.to_string()),
+        ("case.klist_band", KLIST.to_string()),
+    ] {
+        fs::write(dir.path().join(name), contents).unwrap();
+    }
+
+    let info = store
+        .import_spin_band_structure(
+            &project_id,
+            "Fe bands".to_string(),
+            &dir.path().join("case.qtlup"),
+            "case.qtlup".to_string(),
+            &dir.path().join("case.qtldn"),
+            "case.qtldn".to_string(),
+            &dir.path().join("case.klist_band"),
+            "case.klist_band".to_string(),
+            Default::default(),
+            &Progress::none(),
+        )
+        .unwrap();
+    assert_eq!(info.spin, Spin::Polarized);
+    assert_eq!(info.qtl_dn_filename.as_deref(), Some("case.qtldn"));
+
+    let band_id = DatasetId::parse(&info.id).unwrap();
+    let (up_text, _, klist) = store
+        .load_spin_band_structure_files(&project_id, &band_id, &Progress::none())
+        .unwrap();
+    assert_eq!(up_text, up);
+    assert_eq!(klist, KLIST);
+
+    // Each channel keeps its own bands, both against the up channel's Fermi
+    // energy.
+    let data = store
+        .load_band_structure_data(&project_id, &band_id, &Progress::none())
+        .unwrap();
+    let ry = 13.605_693_122_994;
+    assert!((data.up.fermi_energy - 0.61 * ry).abs() < 1e-9);
+    let down = data.down.unwrap();
+    assert_eq!(down.fermi_energy, data.up.fermi_energy);
+    let energies = |qtl: &reciprocal_lib::wien2k::Qtl| -> Vec<f64> {
+        qtl.bands[0].energies.iter().map(|&e| e as f64).collect()
+    };
+    assert_eq!(energies(&data.up).len(), 2);
+    assert_eq!(energies(&down).len(), 2);
+    for (energy, expected) in energies(&data.up).into_iter().zip([0.1, 0.2]) {
+        assert!((energy - (expected - 0.61) * ry).abs() < 1e-4);
+    }
+    for (energy, expected) in energies(&down).into_iter().zip([0.15, 0.26]) {
+        assert!((energy - (expected - 0.61) * ry).abs() < 1e-4);
+    }
+    assert_eq!(down.bands[0].weights[..4], [0.7, 0.4, 0.3, 0.3]);
+
+    // A band structure with one channel has nothing to pair.
+    let single = import_bands(&store, &project_id, dir.path());
+    assert!(store
+        .load_spin_band_structure_files(&project_id, &single, &Progress::none())
+        .is_err());
+}
+
+#[test]
+fn band_structures_are_parsed_once_and_cached() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    fs::write(dir.path().join("case.qtl"), QTL).unwrap();
+    fs::write(dir.path().join("case.klist_band"), KLIST).unwrap();
+    let band_id = import_bands(&store, &project_id, dir.path());
+
+    let data = store
+        .load_band_structure_data(&project_id, &band_id, &Progress::none())
+        .unwrap();
+    let labels: Vec<_> = data.path.iter().map(|p| p.label.as_deref()).collect();
+    assert_eq!(labels, [Some("GAMMA"), Some("X")]);
+    assert_eq!(data.path[1].k, [1.0, 0.0, 0.0]);
+    assert_eq!(data.up.atoms[0].orbital_labels, ["tot", "0", "1"]);
+    let band = &data.up.bands[0];
+    assert!((band.energies[0] as f64 - -0.3 * 13.605_693_122_994).abs() < 1e-5);
+    assert_eq!(data.up.weight_stride(), 4);
+    assert_eq!(band.weights[4..], [0.6, 0.1, 0.5, 0.4]);
+    assert!(data.down.is_none());
+
+    // A cache that doesn't read is parsed again and rewritten.
+    let cache = store
+        .project_dir(&project_id)
+        .unwrap()
+        .join("band_structures")
+        .join(&band_id)
+        .join("bands.cache");
+    fs::write(&cache, "garbage").unwrap();
+    let reparsed = store
+        .load_band_structure_data(&project_id, &band_id, &Progress::none())
+        .unwrap();
+    assert_eq!(reparsed, data);
+    assert_ne!(fs::read(&cache).unwrap(), b"garbage");
+
+    // Files that don't parse are refused at import.
+    fs::write(dir.path().join("case.qtl"), "qtl").unwrap();
+    assert!(matches!(
+        store.import_band_structure(
+            &project_id,
+            "Bands".to_string(),
+            &dir.path().join("case.qtl"),
+            "case.qtl".to_string(),
+            &dir.path().join("case.klist_band"),
+            "case.klist_band".to_string(),
+            Default::default(),
+            &Progress::none(),
+        ),
+        Err(ReciprocalError::Parse { .. })
+    ));
+}
+
+fn import_bands(store: &ProjectStore, project_id: &ProjectId, dir: &Path) -> DatasetId {
+    let info = store
+        .import_band_structure(
+            project_id,
+            "Bands".to_string(),
+            &dir.join("case.qtl"),
+            "case.qtl".to_string(),
+            &dir.join("case.klist_band"),
+            "case.klist_band".to_string(),
+            Default::default(),
+            &Progress::none(),
+        )
+        .unwrap();
+    DatasetId::parse(&info.id).unwrap()
+}
+
+fn blob_files(root: &Path) -> Vec<std::path::PathBuf> {
+    let Ok(shards) = fs::read_dir(root.join("blobs")) else {
+        return Vec::new();
+    };
+    shards
+        .flatten()
+        .filter(|shard| shard.path().is_dir())
+        .flat_map(|shard| fs::read_dir(shard.path()).unwrap().flatten())
+        .map(|blob| blob.path())
+        .collect()
+}
+
+#[test]
+fn imported_files_are_deduplicated_and_verified() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    fs::write(dir.path().join("case.qtl"), QTL).unwrap();
+    fs::write(dir.path().join("case.klist_band"), KLIST).unwrap();
+
+    let first = import_bands(&store, &project_id, dir.path());
+    import_bands(&store, &project_id, dir.path());
+    assert_eq!(blob_files(store.root()).len(), 2);
+
+    let report = store.verify_library(&Progress::none()).unwrap();
+    assert_eq!(report.checked, 2);
+    assert!(report.corrupt.is_empty() && report.missing.is_empty());
+
+    let info = store.band_structure_info(&project_id, &first).unwrap();
+    let qtl = &info.files["data.qtl"];
+    let qtl_blob = blob_files(store.root())
+        .into_iter()
+        .find(|path| path.ends_with(&qtl.sha256))
+        .unwrap();
+    fs::write(&qtl_blob, "tampered").unwrap();
+    let report = store.verify_library(&Progress::none()).unwrap();
+    assert_eq!(report.corrupt.len(), 1);
+    assert_eq!(report.corrupt[0].path, qtl_blob);
+}
+
+#[test]
+fn archives_carry_blob_backed_files() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    fs::write(dir.path().join("case.qtl"), QTL).unwrap();
+    fs::write(dir.path().join("case.klist_band"), KLIST).unwrap();
+    import_bands(&store, &project_id, dir.path());
+
+    let archive = dir.path().join("project.zip");
+    store
+        .export_project_archive(&project_id, &archive, &Progress::none())
+        .unwrap();
+
+    let (_other_dir, other) = self::store();
+    let imported = other
+        .import_project_archive(&archive, &Progress::none())
+        .unwrap();
+    let imported_id = ProjectId::parse(&imported.id).unwrap();
+    let band = &other.list_band_structures(&imported_id).unwrap().items[0];
+    assert_eq!(
+        other
+            .load_band_structure_files(
+                &imported_id,
+                &DatasetId::parse(&band.id).unwrap(),
+                &Progress::none()
+            )
+            .unwrap(),
+        (QTL.to_string(), KLIST.to_string())
+    );
+    assert_eq!(blob_files(other.root()).len(), 2);
+}
+
+#[test]
+fn imports_record_provenance() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    fs::write(dir.path().join("case.qtl"), QTL).unwrap();
+    fs::write(dir.path().join("case.klist_band"), KLIST).unwrap();
+
+    let info = store
+        .import_band_structure(
+            &project_id,
+            "Bands".to_string(),
+            &dir.path().join("case.qtl"),
+            "case.qtl".to_string(),
+            &dir.path().join("case.klist_band"),
+            "case.klist_band".to_string(),
+            ImportOptions {
+                parameters: [("wien2k".to_string(), "23.2".to_string())].into(),
+                ..Default::default()
+            },
+            &Progress::none(),
+        )
+        .unwrap();
+    let provenance = store
+        .dataset_provenance(&project_id, &DatasetId::parse(&info.id).unwrap())
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(provenance.parameters["wien2k"], "23.2");
+    assert_eq!(provenance.sources.len(), 2);
+    let qtl = &provenance.sources[0];
+    assert_eq!(qtl.name, "data.qtl");
+    assert!(qtl.path.is_absolute());
+    assert_eq!(qtl.size, QTL.len() as u64);
+    assert_eq!(qtl.sha256, info.files["data.qtl"].sha256);
+}
+
+#[test]
+fn linked_files_are_checked_and_relinked() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("CeCoIn5".to_string(), "CeCoIn5".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    let case_dir = dir.path().join("case");
+    fs::create_dir(&case_dir).unwrap();
+    fs::write(case_dir.join("case.qtl"), QTL).unwrap();
+    fs::write(case_dir.join("case.klist_band"), KLIST).unwrap();
+
+    let info = store
+        .import_band_structure(
+            &project_id,
+            "Bands".to_string(),
+            &case_dir.join("case.qtl"),
+            "case.qtl".to_string(),
+            &case_dir.join("case.klist_band"),
+            "case.klist_band".to_string(),
+            ImportOptions {
+                link: true,
+                ..Default::default()
+            },
+            &Progress::none(),
+        )
+        .unwrap();
+    let band_id = DatasetId::parse(&info.id).unwrap();
+    assert!(info.files.is_empty());
+    assert!(!dir.path().join("blobs").exists());
+    let (qtl, _) = store
+        .load_band_structure_files(&project_id, &band_id, &Progress::none())
+        .unwrap();
+    assert_eq!(qtl, QTL);
+
+    // Moving the case directory breaks the links until they are relinked.
+    let moved = dir.path().join("moved");
+    fs::rename(&case_dir, &moved).unwrap();
+    match store.load_band_structure_files(&project_id, &band_id, &Progress::none()) {
+        Err(ReciprocalError::BrokenLink { status, .. }) => {
+            assert_eq!(status, LinkStatus::Missing)
+        }
+        other => panic!("expected BrokenLink, got {:?}", other),
+    }
+    let checks = store
+        .relink_dataset(&project_id, &band_id, &moved, false)
+        .unwrap();
+    assert!(checks.iter().all(|c| c.status == LinkStatus::Ok));
+
+    // Changed contents are only relinked when accepted.
+    fs::write(moved.join("case.qtl"), "new").unwrap();
+    let checks = store.check_dataset_links(&project_id, &band_id).unwrap();
+    assert_eq!(checks[0].name, "data.klist_band");
+    assert_eq!(checks[0].status, LinkStatus::Ok);
+    assert_eq!(checks[1].status, LinkStatus::Changed);
+    let checks = store
+        .relink_dataset(&project_id, &band_id, &moved, false)
+        .unwrap();
+    assert_eq!(checks[1].status, LinkStatus::Changed);
+    store
+        .relink_dataset(&project_id, &band_id, &moved, true)
+        .unwrap();
+    let (qtl, _) = store
+        .load_band_structure_files(&project_id, &band_id, &Progress::none())
+        .unwrap();
+    assert_eq!(qtl, "new");
+}
+
+#[test]
+fn schema_v2_datasets_are_migrated_with_defaults() {
+    let (_dir, store) = store();
+    let project = store
+        .create_project("Cu".to_string(), "Cu".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    let project_dir = store.root().join("projects").join(&project.id);
+
+    // A project written by a build from before Fermi surface kinds and spin.
+    let project_file = project_dir.join("project.json");
+    let mut project_json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&project_file).unwrap()).unwrap();
+    project_json["schema_version"] = 2.into();
+    fs::write(&project_file, project_json.to_string()).unwrap();
+    let id = DatasetId::new().to_string();
+    let surface_dir = project_dir.join("fermi_surfaces").join(&id);
+    fs::create_dir_all(&surface_dir).unwrap();
+    let info = serde_json::json!({
+        "schema_version": 2,
+        "id": id,
+        "name": "Cu",
+        "created_at": "2024-05-01T12:00:00Z",
+        "case_name": "Cu",
+    });
+    fs::write(surface_dir.join("info.json"), info.to_string()).unwrap();
+
+    let listing = store.list_fermi_surfaces(&project_id).unwrap();
+    assert_eq!(listing.items[0].kind, FermiSurfaceKind::Wien2k);
+    assert_eq!(listing.items[0].spin, Spin::Unpolarized);
+
+    let migrated: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(surface_dir.join("info.json")).unwrap()).unwrap();
+    assert_eq!(migrated["schema_version"], 3);
+    assert_eq!(migrated["kind"], "wien2k");
+    assert_eq!(migrated["spin"], "unpolarized");
+    assert_eq!(migrated["energy_files"], 0);
+    assert!(fs::read_to_string(project_dir.join("migrations.log"))
+        .unwrap()
+        .contains("v2 -> v3"));
+}
+
+#[test]
+fn chosen_cif_blocks_become_separate_structures() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("FeSe".to_string(), "FeSe".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    let cif = dir.path().join("phases.cif");
+    let block = |name: &str, a: &str| {
+        format!(
+            "data_{}\n_chemical_formula_sum 'Fe Se'\n_cell_length_a {}\n_cell_length_c 5.52\n",
+            name, a
+        )
+    };
+    let text = block("rt", "3.77") + &block("lt", "3.76") + &block("hp", "3.70");
+    fs::write(&cif, &text).unwrap();
+
+    let blocks = reciprocal_lib::cif::read_blocks(&cif).unwrap();
+    let names: Vec<_> = blocks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, ["rt", "lt", "hp"]);
+    assert_eq!(blocks[1].cell_length_a, 3.76);
+
+    let chosen = ["LT".to_string(), "hp".to_string()];
+    let project = store
+        .import_cif_file(&project_id, &cif, "phases.cif".to_string(), &chosen)
+        .unwrap();
+    assert!(project.has_cif);
+    assert_eq!(
+        store.read_cif_file(&project_id).unwrap(),
+        block("lt", "3.76")
+    );
+
+    let structures = store.list_structures(&project_id).unwrap().items;
+    assert_eq!(structures.len(), 1);
+    assert_eq!(structures[0].name, "hp");
+    let id = DatasetId::parse(&structures[0].id).unwrap();
+    let hp = store.load_structure(&project_id, &id).unwrap();
+    assert_eq!(hp.cell_length_a.value, 3.70);
+
+    store.delete_structure(&project_id, &id).unwrap();
+    assert!(store.list_structures(&project_id).unwrap().items.is_empty());
+
+    let missing = ["nope".to_string()];
+    assert!(matches!(
+        store.import_cif_file(&project_id, &cif, "phases.cif".to_string(), &missing),
+        Err(ReciprocalError::Parse { .. })
+    ));
+}
+
+const NACL_STRUCT: &str = "
+F   LATTICE,NONEQUIV.ATOMS:  2 225_Fm-3m
+MODE OF CALC=RELA unit=bohr
+ 10.658236 10.658236 10.658236 90.000000 90.000000 90.000000
+ATOM  -1: X=0.00000000 Y=0.00000000 Z=0.00000000
+          MULT= 1          ISPLIT= 2
+Na         NPT=  781  R0=0.00010000 RMT=    2.5000   Z:  11.00000
+LOCAL ROT MATRIX:    1.0000000 0.0000000 0.0000000
+                     0.0000000 1.0000000 0.0000000
+                     0.0000000 0.0000000 1.0000000
+ATOM  -2: X=0.50000000 Y=0.50000000 Z=0.50000000
+          MULT= 1          ISPLIT= 2
+Cl         NPT=  781  R0=0.00010000 RMT=    2.5000   Z:  17.00000
+LOCAL ROT MATRIX:    1.0000000 0.0000000 0.0000000
+                     0.0000000 1.0000000 0.0000000
+                     0.0000000 0.0000000 1.0000000
+   1      NUMBER OF SYMMETRY OPERATIONS
+ 1 0 0 0.00000000
+ 0 1 0 0.00000000
+ 0 0 1 0.00000000
+       1
+";
+
+#[test]
+fn projects_can_be_created_from_struct_and_poscar_files() {
+    let (dir, store) = store();
+
+    let poscar = dir.path().join("POSCAR");
+    fs::write(
+        &poscar,
+        "rocksalt NaCl\n1.0\n5.64 0 0\n0 5.64 0\n0 0 5.64\nNa Cl\n4 4\nDirect\n\
+         0 0 0\n0 0.5 0.5\n0.5 0 0.5\n0.5 0.5 0\n\
+         0.5 0.5 0.5\n0.5 0 0\n0 0.5 0\n0 0 0.5\n",
+    )
+    .unwrap();
+    let project = store.create_project_from_structure(&poscar).unwrap();
+    assert_eq!(project.name, "rocksalt NaCl");
+    assert_eq!(project.formula, "NaCl");
+    assert!(project.has_cif);
+    let data = store
+        .parse_cif_file(&ProjectId::parse(&project.id).unwrap())
+        .unwrap();
+    assert_eq!(data.atom_sites.len(), 8);
+    assert_eq!(data.atom_sites[5].label, "Cl2");
+    assert_eq!(data.space_group_it_number, Some(1));
+    // The frontend shows the saved crystal data rather than parsing the CIF.
+    assert!(store
+        .load_crystal_data(&ProjectId::parse(&project.id).unwrap())
+        .unwrap()
+        .is_some());
+
+    let structure = dir.path().join("case.struct");
+    fs::write(&structure, NACL_STRUCT).unwrap();
+    let project = store.create_project_from_structure(&structure).unwrap();
+    // No title, so the project is named after its formula.
+    assert_eq!(project.name, "NaCl");
+    assert_eq!(project.cif_filename.as_deref(), Some("case.struct"));
+    let data = store
+        .parse_cif_file(&ProjectId::parse(&project.id).unwrap())
+        .unwrap();
+    assert!((data.cell_length_a.value - 5.64).abs() < 1e-3);
+    assert_eq!(data.space_group_hm.as_deref(), Some("Fm-3m"));
+    let sites: Vec<_> = data.atom_sites.iter().map(|s| s.label.as_str()).collect();
+    assert_eq!(sites, ["Na", "Cl"]);
+    assert_eq!(data.atom_sites[0].symmetry_multiplicity, Some(4));
+    assert_eq!(data.symmetry_operations.len(), 4);
+}
+
+#[test]
+fn bxsf_fermi_surfaces_load_as_energy_grids() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("Copper".to_string(), "Cu".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    let source = dir.path().join("Cu.bxsf");
+    fs::write(
+        &source,
+        "BEGIN_INFO\n Fermi Energy: 0.25\nEND_INFO\nBEGIN_BLOCK_BANDGRID_3D\nCu\n\
+         BEGIN_BANDGRID_3D\n1\n2 2 2\n0 0 0\n1 0 0\n0 1 0\n0 0 1\n\
+         BAND: 5\n0 0.1 0.2 0.3 0.4 0.5 0.6 0.7\nEND_BANDGRID_3D\nEND_BLOCK_BANDGRID_3D\n",
+    )
+    .unwrap();
+    let info = store
+        .import_bxsf_fermi_surface(
+            &project_id,
+            "Cu".to_string(),
+            &source,
+            Default::default(),
+            &Progress::none(),
+        )
+        .unwrap();
+    assert_eq!(info.kind, FermiSurfaceKind::Bxsf);
+    assert_eq!(info.case_name, "Cu");
+    let listing = store.list_fermi_surfaces(&project_id).unwrap();
+    assert_eq!(listing.items[0].kind, FermiSurfaceKind::Bxsf);
+
+    let id = DatasetId::parse(&info.id).unwrap();
+    let grid = store
+        .load_fermi_surface_grid(&project_id, &id, &Progress::none())
+        .unwrap();
+    assert_eq!(grid.bands.len(), 1);
+    // 0.4 hartree sits at x = 1, y = 0, z = 0.
+    assert!((grid.bands[0][1] - 10.884_554).abs() < 1e-4);
+    assert!(store
+        .load_fermi_surface_files(&project_id, &id, &Progress::none())
+        .is_err());
+
+    // A file that isn't a band grid is refused.
+    fs::write(&source, "not a band grid\n").unwrap();
+    assert!(store
+        .import_bxsf_fermi_surface(
+            &project_id,
+            "Broken".to_string(),
+            &source,
+            Default::default(),
+            &Progress::none(),
+        )
+        .is_err());
+}
+
+/// Leaves `path` unreadable, with no backup to fall back to.
+fn corrupt(path: &Path) {
+    fs::write(path, "{").unwrap();
+    let _ = fs::remove_file(path.with_file_name(format!(
+        "{}.bak",
+        path.file_name().unwrap().to_string_lossy()
+    )));
+}
+
+#[test]
+fn repaired_fermi_surfaces_keep_their_kind() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("Copper".to_string(), "Cu".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    let source = dir.path().join("Cu.bxsf");
+    fs::write(
+        &source,
+        "BEGIN_INFO\n Fermi Energy: 0.25\nEND_INFO\nBEGIN_BLOCK_BANDGRID_3D\nCu\n\
+         BEGIN_BANDGRID_3D\n1\n2 2 2\n0 0 0\n1 0 0\n0 1 0\n0 0 1\n\
+         BAND: 5\n0 0.1 0.2 0.3 0.4 0.5 0.6 0.7\nEND_BANDGRID_3D\nEND_BLOCK_BANDGRID_3D\n",
+    )
+    .unwrap();
+    let info = store
+        .import_bxsf_fermi_surface(
+            &project_id,
+            "Cu".to_string(),
+            &source,
+            Default::default(),
+            &Progress::none(),
+        )
+        .unwrap();
+    let fermi_dir = store
+        .root()
+        .join("projects")
+        .join(&project.id)
+        .join("fermi_surfaces")
+        .join(&info.id);
+    corrupt(&fermi_dir.join("info.json"));
+
+    let report = store.repair_project(&project_id).unwrap();
+    assert!(report.unrecoverable.is_empty());
+    assert_eq!(report.repaired.len(), 1);
+    let id = DatasetId::parse(&info.id).unwrap();
+    let repaired = store.fermi_surface_info(&project_id, &id).unwrap();
+    assert_eq!(repaired.kind, FermiSurfaceKind::Bxsf);
+    assert_eq!(repaired.spin, Spin::Unpolarized);
+    assert_eq!(repaired.case_name, "Cu");
+    assert_eq!(repaired.files, info.files);
+    let grid = store
+        .load_fermi_surface_grid(&project_id, &id, &Progress::none())
+        .unwrap();
+    assert_eq!(grid.bands.len(), 1);
+}
+
+#[test]
+fn repair_rebuilds_unreadable_project_metadata() {
+    let (_dir, store) = store();
+    let project = store
+        .create_project("Silicon".to_string(), "Si".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    let project_file = store
+        .root()
+        .join("projects")
+        .join(&project.id)
+        .join("project.json");
+    corrupt(&project_file);
+    assert_eq!(store.load_projects().unwrap().diagnostics.len(), 1);
+
+    let report = store.repair_project(&project_id).unwrap();
+    assert_eq!(report.repaired.len(), 1);
+    assert_eq!(report.repaired[0].path, project_file);
+    assert!(report.unrecoverable.is_empty());
+    assert_eq!(report.project.id, project.id);
+    assert!(!report.project.has_cif);
+
+    let listing = store.load_projects().unwrap();
+    assert!(listing.diagnostics.is_empty());
+    assert_eq!(listing.items[0].id, project.id);
+}
+
+#[test]
+fn repair_rebuilds_datasets_kept_in_their_directory() {
+    let (_dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    // A band structure from before the blob store, with no provenance.
+    let id = DatasetId::new();
+    let band_dir = store
+        .root()
+        .join("projects")
+        .join(&project.id)
+        .join("band_structures")
+        .join(&id);
+    fs::create_dir_all(&band_dir).unwrap();
+    fs::write(band_dir.join("data.qtl"), QTL).unwrap();
+    fs::write(band_dir.join("data.klist_band"), KLIST).unwrap();
+    fs::write(band_dir.join("info.json"), "{").unwrap();
+
+    let report = store.repair_project(&project_id).unwrap();
+    assert_eq!(report.repaired.len(), 1);
+    assert_eq!(report.repaired[0].path, band_dir.join("info.json"));
+    let info = store.band_structure_info(&project_id, &id).unwrap();
+    assert_eq!(info.spin, Spin::Unpolarized);
+    assert!(info.files.is_empty());
+    assert_eq!(
+        store
+            .load_band_structure_files(&project_id, &id, &Progress::none())
+            .unwrap(),
+        (QTL.to_string(), KLIST.to_string())
+    );
+}
+
+#[test]
+fn repair_rebuilds_blob_backed_datasets() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    fs::write(dir.path().join("case.qtl"), QTL).unwrap();
+    fs::write(dir.path().join("case.klist_band"), KLIST).unwrap();
+    let id = import_bands(&store, &project_id, dir.path());
+    let imported = store.band_structure_info(&project_id, &id).unwrap();
+
+    let band_dir = store
+        .root()
+        .join("projects")
+        .join(&project.id)
+        .join("band_structures")
+        .join(&id);
+    corrupt(&band_dir.join("info.json"));
+    assert_eq!(
+        store
+            .verify_library(&Progress::none())
+            .unwrap()
+            .unreadable
+            .len(),
+        1
+    );
+
+    let report = store.repair_project(&project_id).unwrap();
+    assert!(report.unrecoverable.is_empty());
+    assert_eq!(report.repaired.len(), 1);
+    let info = store.band_structure_info(&project_id, &id).unwrap();
+    assert_eq!(info.files, imported.files);
+    assert_eq!(info.qtl_filename, "case.qtl");
+    assert_eq!(info.klist_filename, "case.klist_band");
+    assert_eq!(
+        store
+            .load_band_structure_files(&project_id, &id, &Progress::none())
+            .unwrap(),
+        (QTL.to_string(), KLIST.to_string())
+    );
+    assert!(store
+        .verify_library(&Progress::none())
+        .unwrap()
+        .unreadable
+        .is_empty());
+}
+
+#[test]
+fn repair_reports_datasets_whose_data_is_gone() {
+    let (dir, store) = store();
+    let project = store
+        .create_project("GaAs".to_string(), "GaAs".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+    fs::write(dir.path().join("case.qtl"), QTL).unwrap();
+    fs::write(dir.path().join("case.klist_band"), KLIST).unwrap();
+    let id = import_bands(&store, &project_id, dir.path());
+
+    let band_dir = store
+        .root()
+        .join("projects")
+        .join(&project.id)
+        .join("band_structures")
+        .join(&id);
+    corrupt(&band_dir.join("info.json"));
+    // Neither the blobs nor the files they were imported from are left.
+    fs::remove_dir_all(store.root().join("blobs")).unwrap();
+    fs::remove_file(dir.path().join("case.qtl")).unwrap();
+    fs::remove_file(dir.path().join("case.klist_band")).unwrap();
+
+    let report = store.repair_project(&project_id).unwrap();
+    assert!(report.repaired.is_empty());
+    assert_eq!(report.unrecoverable.len(), 1);
+    assert_eq!(report.unrecoverable[0].path, band_dir);
+    assert_eq!(report.unrecoverable[0].reason, "Missing data.qtl");
+    assert!(store.band_structure_info(&project_id, &id).is_err());
+}
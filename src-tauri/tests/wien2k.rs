@@ -0,0 +1,362 @@
+use reciprocal_lib::band_grid::SpinChannel;
+use reciprocal_lib::cif;
+use reciprocal_lib::ids::{DatasetId, ProjectId};
+use reciprocal_lib::jobs::Progress;
+use reciprocal_lib::wien2k::{self, LatticeType, Wien2kStruct, RY_TO_EV};
+use reciprocal_lib::{Eigenvalues, FermiSurfaceFiles, ProjectStore, Spin};
+use std::path::Path;
+
+const TIO2_STRUCT: &str = "\
+TiO2 rutile
+P   LATTICE,NONEQUIV.ATOMS:  2 136_P42/mnm
+MODE OF CALC=RELA unit=bohr
+  8.682053  8.682053  5.592343 90.000000 90.000000 90.000000
+ATOM  -1: X=0.00000000 Y=0.00000000 Z=0.00000000
+          MULT= 2          ISPLIT= 8
+      -1: X=0.50000000 Y=0.50000000 Z=0.50000000
+Ti1        NPT=  781  R0=0.00005000 RMT=    1.9400   Z:  22.00000
+LOCAL ROT MATRIX:    0.7071068 0.7071068 0.0000000
+                    -0.7071068 0.7071068 0.0000000
+                     0.0000000 0.0000000 1.0000000
+ATOM  -2: X=0.30479000 Y=0.30479000 Z=0.00000000
+          MULT= 4          ISPLIT= 8
+      -2: X=0.69521000 Y=0.69521000 Z=0.00000000
+      -2: X=0.19521000 Y=0.80479000 Z=0.50000000
+      -2: X=0.80479000 Y=0.19521000 Z=0.50000000
+O1         NPT=  781  R0=0.00010000 RMT=    1.6700   Z:   8.00000
+LOCAL ROT MATRIX:    0.0000000 0.7071068 0.7071068
+                     0.0000000-0.7071068 0.7071068
+                     1.0000000 0.0000000 0.0000000
+   2      NUMBER OF SYMMETRY OPERATIONS
+ 1 0 0 0.00000000
+ 0 1 0 0.00000000
+ 0 0 1 0.00000000
+       1
+ 0-1 0 0.50000000
+ 1 0 0 0.50000000
+ 0 0 1 0.50000000
+       2
+";
+
+fn nacl_cif(ops: &[&str], sites: &str, cell: (f64, f64, f64)) -> String {
+    let (a, c, gamma) = cell;
+    format!(
+        "data_test\n_cell_length_a {a}\n_cell_length_b {a}\n_cell_length_c {c}\n\
+         _cell_angle_alpha 90\n_cell_angle_beta 90\n_cell_angle_gamma {gamma}\n\
+         loop_\n_symmetry_equiv_pos_as_xyz\n{}\n\
+         loop_\n_atom_site_label\n_atom_site_type_symbol\n\
+         _atom_site_fract_x\n_atom_site_fract_y\n_atom_site_fract_z\n{sites}\n",
+        ops.iter()
+            .map(|op| format!("'{}'", op))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+#[test]
+fn reads_a_wien2k_struct_file() {
+    let s = Wien2kStruct::parse(TIO2_STRUCT, Path::new("TiO2.struct")).unwrap();
+    assert_eq!(s.title, "TiO2 rutile");
+    assert_eq!(s.lattice, LatticeType::P);
+    assert_eq!(s.space_group_number, Some(136));
+    assert_eq!(s.space_group_symbol.as_deref(), Some("P42/mnm"));
+    assert!(s.relativistic);
+    assert_eq!(s.cell[2], 5.592343);
+    assert_eq!(s.atoms.len(), 2);
+
+    let o = &s.atoms[1];
+    assert_eq!(o.name, "O1");
+    assert_eq!(o.positions.len(), 4);
+    assert_eq!(o.positions[2], [0.19521, 0.80479, 0.5]);
+    assert_eq!((o.npt, o.r0, o.rmt, o.z), (781, 0.0001, 1.67, 8.0));
+    assert_eq!(o.local_rotation[2], [1.0, 0.0, 0.0]);
+
+    assert_eq!(s.symmetry_operations.len(), 2);
+    assert_eq!(
+        s.symmetry_operations[1].rotation,
+        [[0, -1, 0], [1, 0, 0], [0, 0, 1]]
+    );
+    assert_eq!(s.symmetry_operations[1].translation, [0.5; 3]);
+}
+
+#[test]
+fn written_struct_reads_back_the_same() {
+    let s = Wien2kStruct::parse(TIO2_STRUCT, Path::new("TiO2.struct")).unwrap();
+    let text = s.to_text();
+    assert_eq!(text, TIO2_STRUCT);
+    assert_eq!(
+        Wien2kStruct::parse(&text, Path::new("out.struct")).unwrap(),
+        s
+    );
+}
+
+#[test]
+fn reports_the_line_of_a_bad_struct() {
+    let broken = TIO2_STRUCT.replace("MULT= 4", "MULT= x");
+    match Wien2kStruct::parse(&broken, Path::new("bad.struct")) {
+        Err(reciprocal_lib::error::ReciprocalError::Parse { line, .. }) => {
+            assert_eq!(line, Some(13))
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn face_centred_cif_becomes_an_f_lattice_struct() {
+    let text = nacl_cif(
+        &[
+            "x, y, z",
+            "x, y+1/2, z+1/2",
+            "x+1/2, y, z+1/2",
+            "x+1/2, y+1/2, z",
+            "-x, -y, -z",
+        ],
+        "Na1 Na 0 0 0\nCl1 Cl 0.5 0.5 0.5",
+        (5.64, 5.64, 90.0),
+    );
+    let data = cif::parse_crystal_data(&text, Path::new("NaCl.cif")).unwrap();
+    let s = Wien2kStruct::from_crystal_data(&data, "NaCl", Path::new("NaCl.cif")).unwrap();
+
+    assert_eq!(s.lattice, LatticeType::F);
+    assert!((s.cell[0] - 5.64 / 0.529_177_210_903).abs() < 1e-9);
+    let names: Vec<_> = s.atoms.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["Na1", "Cl1"]);
+    assert!(s.atoms.iter().all(|a| a.positions.len() == 1));
+    assert_eq!((s.atoms[0].z, s.atoms[1].z), (11.0, 17.0));
+    // Na–Cl is 2.82 Å, so half of it exceeds the largest RMT handed out.
+    assert_eq!(s.atoms[0].rmt, 2.5);
+    // The centring translations aren't listed as operations.
+    assert_eq!(s.symmetry_operations.len(), 2);
+
+    let reread = Wien2kStruct::parse(&s.to_text(), Path::new("NaCl.struct")).unwrap();
+    assert_eq!(reread.atoms.len(), 2);
+    assert_eq!(reread.lattice, LatticeType::F);
+}
+
+#[test]
+fn rhombohedral_cif_positions_are_given_on_rhombohedral_axes() {
+    let text = nacl_cif(
+        &["x, y, z", "x+2/3, y+1/3, z+1/3", "x+1/3, y+2/3, z+2/3"],
+        "Bi1 Bi 0 0 0.25",
+        (4.54, 11.86, 120.0),
+    );
+    let data = cif::parse_crystal_data(&text, Path::new("Bi.cif")).unwrap();
+    let s = Wien2kStruct::from_crystal_data(&data, "Bi", Path::new("Bi.cif")).unwrap();
+
+    assert_eq!(s.lattice, LatticeType::R);
+    assert_eq!(s.atoms[0].positions.len(), 1);
+    let p = s.atoms[0].positions[0];
+    assert!(p.iter().all(|x| (x - 0.25).abs() < 1e-9), "{:?}", p);
+}
+
+const OUTPUT1: &str = "\
+     K=  0.00000  0.00000  0.00000      1
+       MATRIX SIZE   120  LOs:  10  RKM= 7.00  WEIGHT= 1.00  PGR: Oh
+       EIGENVALUES ARE:
+      -0.5000000   -0.1000000    0.2000000
+        ********************************************************
+     K=  0.00000  0.00000  0.50000      2
+       MATRIX SIZE   118  LOs:  10  RKM= 7.00  WEIGHT= 6.00  PGR: C4v
+       EIGENVALUES ARE:
+      -0.4000000-0.0500000    0.3000000    0.9000000
+        EIGENVALUES BELOW THE ENERGY   -0.6
+";
+
+const OUTPUTKGEN: &str = "\
+  G1        G2        G3
+  1.000000  0.000000  0.000000
+  0.000000  1.000000  0.000000
+  0.000000  0.000000  1.000000
+  DIVISION OF RECIPROCAL LATTICE VECTORS (INTERVALS)=   1   1   1
+  point     coordinates     relation
+     1     0    0    0     1
+     2     0    0    1     2
+     3     0    1    0     2
+     4     0    1    1     3
+     5     1    0    0     2
+     6     1    0    1     2
+     7     1    1    0     2
+     8     1    1    1     2
+";
+
+#[test]
+fn unfolds_scf_eigenvalues_onto_the_full_mesh() {
+    let energies = wien2k::parse_output1(OUTPUT1);
+    // Bands beyond what every k-point has are dropped.
+    assert_eq!(energies.len(), 2);
+    assert_eq!(energies[1].len(), 3);
+    assert!((energies[1][1] + 0.05 * RY_TO_EV).abs() < 1e-9);
+
+    let output2 = ":FER  : F E R M I - ENERGY(TETRAH.M.)=   0.2500000\n";
+    let fermi = wien2k::parse_fermi_energy(output2, Path::new("case.output2")).unwrap();
+    assert!((fermi - 0.25 * RY_TO_EV).abs() < 1e-9);
+
+    let mesh = wien2k::parse_outputkgen(OUTPUTKGEN, Path::new("case.outputkgen")).unwrap();
+    assert_eq!(mesh.dims, [2, 2, 2]);
+    let grid = wien2k::band_grid(&energies, fermi, &mesh);
+    assert_eq!(grid.bands.len(), 3);
+    let ev = |ry: f64| (ry * RY_TO_EV) as f32;
+    assert_eq!(grid.bands[0][grid.index(0, 0, 0)], ev(-0.5));
+    assert_eq!(grid.bands[0][grid.index(1, 1, 1)], ev(-0.4));
+    // Point 4 relates to point 3, which itself relates to point 2.
+    assert_eq!(grid.bands[2][grid.index(0, 1, 1)], ev(0.3));
+    assert_eq!(grid.crossing_bands(), [2]);
+}
+
+#[test]
+fn spin_polarized_fermi_surfaces_tag_each_channel() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = ProjectStore::new(dir.path().join("library"));
+    let project = store
+        .create_project("Ni".to_string(), "Ni".to_string())
+        .unwrap();
+    let project_id = ProjectId::parse(&project.id).unwrap();
+
+    let file = |name: &str| dir.path().join(name);
+    for (name, contents) in [
+        ("case.output1up", OUTPUT1.to_string()),
+        (
+            "case.output1dn",
+            OUTPUT1.replace("-0.5000000", "-0.4500000"),
+        ),
+        (
+            "case.output2up",
+            ":FER  : F E R M I - ENERGY(TETRAH.M.)=   0.2500000\n".to_string(),
+        ),
+        ("case.outputkgen", OUTPUTKGEN.to_string()),
+        ("case.struct", TIO2_STRUCT.to_string()),
+    ] {
+        std::fs::write(file(name), contents).unwrap();
+    }
+    let (output1up, output1dn, output2, outputkgen, struct_file) = (
+        file("case.output1up"),
+        file("case.output1dn"),
+        file("case.output2up"),
+        file("case.outputkgen"),
+        file("case.struct"),
+    );
+
+    let info = store
+        .import_fermi_surface(
+            &project_id,
+            "Ni".to_string(),
+            FermiSurfaceFiles {
+                eigenvalues: Eigenvalues::Output1(&output1up),
+                down: Some(Eigenvalues::Output1(&output1dn)),
+                output2: &output2,
+                outputkgen: &outputkgen,
+                struct_file: &struct_file,
+            },
+            "case".to_string(),
+            Default::default(),
+            &Progress::none(),
+        )
+        .unwrap();
+    assert_eq!(info.spin, Spin::Polarized);
+    assert!(info
+        .data_file_names()
+        .contains(&"data.output1_dn".to_string()));
+
+    let grid = store
+        .load_fermi_surface_grid(
+            &project_id,
+            &DatasetId::parse(&info.id).unwrap(),
+            &Progress::none(),
+        )
+        .unwrap();
+    assert_eq!(grid.bands.len(), 6);
+    assert_eq!(grid.spins[..3], [SpinChannel::Up; 3]);
+    assert_eq!(grid.spins[3..], [SpinChannel::Down; 3]);
+    assert!((grid.fermi_energy - 0.25 * RY_TO_EV).abs() < 1e-9);
+    let ev = |ry: f64| (ry * RY_TO_EV) as f32;
+    assert_eq!(grid.bands[0][grid.index(0, 0, 0)], ev(-0.5));
+    assert_eq!(grid.bands[3][grid.index(0, 0, 0)], ev(-0.45));
+
+    // Channels read from different kinds of file can't be paired.
+    let klist = file("case.klist");
+    let mixed = store.import_fermi_surface(
+        &project_id,
+        "Ni".to_string(),
+        FermiSurfaceFiles {
+            eigenvalues: Eigenvalues::Output1(&output1up),
+            down: Some(Eigenvalues::Energy {
+                files: &[file("case.energydn")],
+                klist: &klist,
+            }),
+            output2: &output2,
+            outputkgen: &outputkgen,
+            struct_file: &struct_file,
+        },
+        "case".to_string(),
+        Default::default(),
+        &Progress::none(),
+    );
+    assert!(mixed.is_err());
+}
+
+const ENERGY_HEADER: &str = "\
+    0.30000    0.30000    0.30000    0.30000
+    0.30000-10.00000    0.30000    0.30000
+";
+
+const KLIST: &str = "\
+         1         0         0         0         2  1.0 -7.0  1.5         0 k, div: (  2  2  2)
+         2         0         0         1         2  6.0
+END
+";
+
+#[test]
+fn merges_k_parallel_energy_splits_in_klist_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = format!(
+        "{} 0.000000000000E+00 0.000000000000E+00 0.000000000000E+00         1   120     2  1.0\n\
+         \x20          1 -0.500000000000000E+00\n\
+         \x20          2 -0.100000000000000E+00\n",
+        ENERGY_HEADER
+    );
+    // A file rewritten with free-format fields.
+    let second = format!(
+        "{}0.0 0.0 0.5 2 118 3 6.0\n1 -0.4\n2 -0.05\n3 0.3\n",
+        ENERGY_HEADER
+    );
+    std::fs::write(dir.path().join("case.energyso_1"), first).unwrap();
+    std::fs::write(dir.path().join("case.energyso_2"), second).unwrap();
+    std::fs::write(dir.path().join("case.energyso"), "").unwrap();
+
+    // An empty case.energyso stands for its splits.
+    let files = wien2k::energy_splits(&dir.path().join("case.energyso"));
+    assert_eq!(files.len(), 2);
+    let reversed: Vec<_> = files.iter().rev().cloned().collect();
+    let kpoints = wien2k::read_energy(&reversed).unwrap();
+    assert_eq!(kpoints[0].k, [0.0; 3]);
+    assert_eq!(kpoints[1].k, [0.0, 0.0, 0.5]);
+    assert_eq!(kpoints[1].weight, 6.0);
+    assert!((kpoints[0].energies[1] + 0.1 * RY_TO_EV).abs() < 1e-9);
+
+    let klist = wien2k::parse_klist(KLIST, Path::new("case.klist")).unwrap();
+    assert_eq!(klist[1].k(), [0.0, 0.0, 0.5]);
+    let aligned =
+        wien2k::align_to_klist(kpoints.clone(), &klist, Path::new("case.energyso")).unwrap();
+    assert_eq!(wien2k::eigenvalues(&aligned)[1].len(), 2);
+
+    // A k-point the k-list doesn't have is reported.
+    let mut stray = kpoints;
+    stray[1].k = [0.0, 0.5, 0.0];
+    assert!(wien2k::align_to_klist(stray, &klist, Path::new("case.energyso")).is_err());
+}
+
+#[test]
+fn reads_reformatted_klists() {
+    // Free-format lines label only the named points, and the END line is
+    // missing.
+    let text = "GAMMA 0 0 0 20 2.0\n10 0 0 20 2.0\nX 20 0 0 20 2.0\n";
+    let klist = wien2k::parse_klist(text, Path::new("case.klist_band")).unwrap();
+    let names: Vec<_> = klist.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["GAMMA", "", "X"]);
+    assert_eq!(klist[1].k(), [0.5, 0.0, 0.0]);
+    assert_eq!(klist[2].weight, 2.0);
+
+    let unlabeled = wien2k::parse_klist("0 0 0 20 2.0\n", Path::new("case.klist")).unwrap();
+    assert_eq!(unlabeled[0].name, "");
+    assert_eq!(unlabeled[0].divisor, 20);
+}
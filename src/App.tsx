@@ -14,6 +14,7 @@ import {
   Project,
   loadProjects,
   createProject,
+  createProjectFromStructure,
   updateProject,
   markProjectOpened,
   deleteProject,
@@ -47,8 +48,11 @@ function App() {
 
   useEffect(() => {
     loadProjects()
-      .then((loadedProjects) => {
-        setProjects(loadedProjects);
+      .then(({ items, diagnostics }) => {
+        if (diagnostics.length > 0) {
+          console.warn("Some projects could not be loaded:", diagnostics);
+        }
+        setProjects(items);
         setLoading(false);
       })
       .catch((err) => {
@@ -82,6 +86,20 @@ function App() {
     }
   };
 
+  const handleCreateProjectFromFile = async (path: string) => {
+    try {
+      const newProject = await createProjectFromStructure(path);
+      setProjects((prev) => [newProject, ...prev]);
+      setNewlyCreatedId(newProject.id);
+      handleCloseNewProject();
+
+      setTimeout(() => setNewlyCreatedId(null), 2000);
+    } catch (err) {
+      console.error("Failed to create project from file:", err);
+      setError(String(err));
+    }
+  };
+
   const normalizedQuery = projectQuery.trim().toLowerCase();
   const filteredProjects = projects.filter((project) => {
     if (!normalizedQuery) return true;
@@ -437,6 +455,7 @@ function App() {
                 key={newProjectKey}
                 onClose={handleCloseNewProject}
                 onCreate={handleCreateProject}
+                onCreateFromFile={handleCreateProjectFromFile}
               />
             </div>
           </div>
@@ -5,8 +5,9 @@ import {
   Project,
   BandStructureInfo,
   importBandStructure,
+  importSpinBandStructure,
   listBandStructures,
-  loadBandStructureFiles,
+  loadBandStructureData,
   loadBandStructureLabels,
   updateBandStructureLabels,
   loadBandStructureAtomNames,
@@ -21,7 +22,7 @@ import {
 } from "../lib/brillouinZone";
 import {
   BandStructureData,
-  parseBandStructure,
+  fromParsedBandStructure,
   calculateKPathDistance,
   getProjectionWeight,
   getOrbitalIndices,
@@ -35,6 +36,7 @@ interface BandStructurePageProps {
 type ProjectionMode = "total" | "atom" | "orbital";
 type OrbitalType = keyof typeof ORBITAL_GROUPS;
 type ColorMode = "single" | "rainbow";
+type SpinChannels = "both" | "up" | "down";
 
 // Predefined colors for single-color mode
 const BAND_COLORS = {
@@ -61,6 +63,7 @@ interface PlotSettings {
   colorMode: ColorMode;
   bandColor: BandColorName;
   rainbowScale: number;  // Multiplier for rainbow color spread (1 = default, higher = faster color change)
+  spinChannels: SpinChannels;  // Which channels of a spin-polarized band structure to draw
 }
 
 const DEFAULT_SETTINGS: PlotSettings = {
@@ -74,6 +77,7 @@ const DEFAULT_SETTINGS: PlotSettings = {
   colorMode: "rainbow",
   bandColor: "blue",
   rainbowScale: 1,
+  spinChannels: "both",
 };
 
 export function BandStructurePage({ project }: BandStructurePageProps) {
@@ -81,6 +85,8 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
   const [bandStructures, setBandStructures] = useState<BandStructureInfo[]>([]);
   const [selectedBandStructure, setSelectedBandStructure] = useState<BandStructureInfo | null>(null);
   const [bandData, setBandData] = useState<BandStructureData | null>(null);
+  // Minority channel of a spin-polarized band structure; bandData is the majority
+  const [downData, setDownData] = useState<BandStructureData | null>(null);
   const [kPathDistances, setKPathDistances] = useState<number[]>([]);
   const [customLabels, setCustomLabels] = useState<Record<string, string>>({});
   const [customAtomNames, setCustomAtomNames] = useState<Record<number, string>>({});
@@ -151,7 +157,10 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
   const loadBandStructureList = async () => {
     setIsLoading(true);
     try {
-      const list = await listBandStructures(project.id);
+      const { items: list, diagnostics } = await listBandStructures(project.id);
+      if (diagnostics.length > 0) {
+        console.warn("Some band structures could not be loaded:", diagnostics);
+      }
       setBandStructures(list);
 
       // Auto-select the first one if available
@@ -173,8 +182,10 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
     setError(null);
 
     try {
-      const [qtlContent, klistContent] = await loadBandStructureFiles(project.id, info.id);
-      const data = parseBandStructure(qtlContent, klistContent);
+      const { up: data, down } = fromParsedBandStructure(
+        await loadBandStructureData(project.id, info.id)
+      );
+      setDownData(down);
       setBandData(data);
       setKPathDistances(calculateKPathDistance(data.kPoints));
 
@@ -217,7 +228,7 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
       // Select QTL file
       const qtlFile = await open({
         multiple: false,
-        filters: [{ name: "QTL Files", extensions: ["qtl"] }],
+        filters: [{ name: "QTL Files", extensions: ["qtl", "qtlup", "qtldn"] }],
         title: "Select .qtl file",
       });
 
@@ -226,6 +237,23 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
         return;
       }
 
+      // A spin-polarized run needs the other channel too
+      const channel = qtlFile.match(/\.qtl(up|dn)$/i)?.[1].toLowerCase();
+      let otherFile: string | null = null;
+      if (channel) {
+        const other = channel === "up" ? "qtldn" : "qtlup";
+        otherFile = await open({
+          multiple: false,
+          filters: [{ name: "QTL Files", extensions: [other] }],
+          title: `Select .${other} file`,
+        });
+
+        if (!otherFile) {
+          setIsImporting(false);
+          return;
+        }
+      }
+
       // Select klist_band file
       const klistFile = await open({
         multiple: false,
@@ -245,17 +273,32 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
       const klistFilename = klistPath.split(/[/\\]/).pop() || "data.klist_band";
 
       // Generate a name from the filename
-      const name = qtlFilename.replace(/\.qtl$/i, "");
+      const name = qtlFilename.replace(/\.qtl(up|dn)?$/i, "");
 
       // Import
-      const info = await importBandStructure(
-        project.id,
-        name,
-        qtlPath,
-        qtlFilename,
-        klistPath,
-        klistFilename
-      );
+      let info: BandStructureInfo;
+      if (otherFile) {
+        const [upPath, dnPath] = channel === "up" ? [qtlPath, otherFile] : [otherFile, qtlPath];
+        info = await importSpinBandStructure(
+          project.id,
+          name,
+          upPath,
+          upPath.split(/[/\\]/).pop() || "data.qtlup",
+          dnPath,
+          dnPath.split(/[/\\]/).pop() || "data.qtldn",
+          klistPath,
+          klistFilename
+        );
+      } else {
+        info = await importBandStructure(
+          project.id,
+          name,
+          qtlPath,
+          qtlFilename,
+          klistPath,
+          klistFilename
+        );
+      }
 
       // Reload list and select the new one
       await loadBandStructureList();
@@ -384,65 +427,80 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
       return BAND_COLORS[settings.bandColor];
     };
 
-    const totalBands = bandData.bands.length;
+    // Majority bands solid, minority dashed; fat bands red and blue
+    const channels: Array<{ data: BandStructureData; dash: number[]; fatColor: string }> = [];
+    if (!downData || settings.spinChannels !== "down") {
+      channels.push({ data: bandData, dash: [], fatColor: "220, 38, 38" });
+    }
+    if (downData && settings.spinChannels !== "up") {
+      channels.push({ data: downData, dash: [6, 4], fatColor: "37, 99, 235" });
+    }
 
-    // Draw bands
-    for (let bandIdx = 0; bandIdx < bandData.bands.length; bandIdx++) {
-      const band = bandData.bands[bandIdx];
-      if (band.points.length < 2) continue;
+    for (const { data, dash, fatColor } of channels) {
+      const totalBands = data.bands.length;
+      ctx.setLineDash(dash);
 
-      // Sort points by k-index
-      const sortedPoints = [...band.points].sort((a, b) => a.kPointIndex - b.kPointIndex);
+      // Draw bands
+      for (let bandIdx = 0; bandIdx < data.bands.length; bandIdx++) {
+        const band = data.bands[bandIdx];
+        if (band.points.length < 2) continue;
 
-      if (settings.projectionMode === "total") {
-        // Draw as connected lines
-        ctx.strokeStyle = getBandColor(bandIdx, totalBands);
-        ctx.lineWidth = 1.5;
-        ctx.beginPath();
+        // Sort points by k-index
+        const sortedPoints = [...band.points].sort((a, b) => a.kPointIndex - b.kPointIndex);
 
-        let started = false;
-        for (let i = 0; i < sortedPoints.length; i++) {
-          const point = sortedPoints[i];
-          const x = scaleX(kPathDistances[point.kPointIndex]);
-          const y = scaleY(point.energy);
+        if (settings.projectionMode === "total") {
+          // Draw as connected lines
+          ctx.strokeStyle = getBandColor(bandIdx, totalBands);
+          ctx.lineWidth = 1.5;
+          ctx.beginPath();
 
-          if (point.energy < yMin || point.energy > yMax) {
-            started = false;
-            continue;
+          let started = false;
+          for (let i = 0; i < sortedPoints.length; i++) {
+            const point = sortedPoints[i];
+            const x = scaleX(kPathDistances[point.kPointIndex]);
+            const y = scaleY(point.energy);
+
+            if (point.energy < yMin || point.energy > yMax) {
+              started = false;
+              continue;
+            }
+
+            if (!started) {
+              ctx.moveTo(x, y);
+              started = true;
+            } else {
+              ctx.lineTo(x, y);
+            }
           }
-
-          if (!started) {
-            ctx.moveTo(x, y);
-            started = true;
-          } else {
-            ctx.lineTo(x, y);
+          ctx.stroke();
+        } else {
+          // Draw as fat bands (scatter with varying sizes)
+          for (const point of sortedPoints) {
+            if (point.energy < yMin || point.energy > yMax) continue;
+
+            const x = scaleX(kPathDistances[point.kPointIndex]);
+            const y = scaleY(point.energy);
+
+            // Calculate projection weight
+            let weight = 0;
+            const atomIndices = settings.selectedAtom > 0 ? [settings.selectedAtom] : [];
+            const orbitalIndices = getOrbitalIndices(settings.selectedOrbital);
+
+            weight = getProjectionWeight(point, atomIndices, orbitalIndices);
+
+            // Draw circle with size based on weight
+            const radius = Math.max(1, weight * settings.fatBandScale);
+            ctx.fillStyle = downData
+              ? `rgba(${fatColor}, ${Math.min(1, weight * 2)})`
+              : getBandColor(bandIdx, totalBands, weight);
+            ctx.beginPath();
+            ctx.arc(x, y, radius, 0, Math.PI * 2);
+            ctx.fill();
           }
         }
-        ctx.stroke();
-      } else {
-        // Draw as fat bands (scatter with varying sizes)
-        for (const point of sortedPoints) {
-          if (point.energy < yMin || point.energy > yMax) continue;
-
-          const x = scaleX(kPathDistances[point.kPointIndex]);
-          const y = scaleY(point.energy);
-
-          // Calculate projection weight
-          let weight = 0;
-          const atomIndices = settings.selectedAtom > 0 ? [settings.selectedAtom] : [];
-          const orbitalIndices = getOrbitalIndices(settings.selectedOrbital);
-
-          weight = getProjectionWeight(point, atomIndices, orbitalIndices);
-
-          // Draw circle with size based on weight
-          const radius = Math.max(1, weight * settings.fatBandScale);
-          ctx.fillStyle = getBandColor(bandIdx, totalBands, weight);
-          ctx.beginPath();
-          ctx.arc(x, y, radius, 0, Math.PI * 2);
-          ctx.fill();
-        }
       }
     }
+    ctx.setLineDash([]);
 
     // Draw axes
     ctx.strokeStyle = "rgba(0, 0, 0, 0.8)";
@@ -527,7 +585,7 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
     ctx.strokeStyle = "rgba(0, 0, 0, 0.3)";
     ctx.strokeRect(margin.left, margin.top, plotWidth, plotHeight);
 
-  }, [bandData, kPathDistances, settings, customLabels]);
+  }, [bandData, downData, kPathDistances, settings, customLabels]);
 
   // Redraw when data or settings change
   useEffect(() => {
@@ -571,6 +629,10 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
                       <span className="text-primary font-mono">.qtl</span>
                       <span>- Contains band energies and orbital character for each k-point</span>
                     </li>
+                    <li className="flex items-start gap-2">
+                      <span className="text-primary font-mono">.qtlup/.qtldn</span>
+                      <span>- Instead of .qtl for a spin-polarized run; pick one and then the other</span>
+                    </li>
                     <li className="flex items-start gap-2">
                       <span className="text-primary font-mono">.klist_band</span>
                       <span>- Contains the k-point path with high-symmetry point labels</span>
@@ -881,6 +943,34 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
               </div>
             </div>
 
+            {/* Spin channels (shown for spin-polarized band structures) */}
+            {downData && (
+              <div className="mb-6">
+                <label className="block text-sm font-medium text-gray-700 mb-2">
+                  Spin Channels
+                </label>
+                <div className="flex gap-2">
+                  {(["both", "up", "down"] as SpinChannels[]).map(channels => (
+                    <button
+                      key={channels}
+                      onClick={() => setSettings(s => ({ ...s, spinChannels: channels }))}
+                      className={cn(
+                        "flex-1 px-3 py-2 rounded-xl text-sm font-medium transition-all border",
+                        settings.spinChannels === channels
+                          ? "bg-primary text-white border-primary shadow-sm"
+                          : "bg-white/50 text-gray-700 border-gray-200 hover:bg-white/70 hover:border-gray-300"
+                      )}
+                    >
+                      {channels === "both" ? "Both" : channels === "up" ? "↑ Up" : "↓ Down"}
+                    </button>
+                  ))}
+                </div>
+                <p className="text-xs text-gray-500 mt-1">
+                  Up bands are solid, down bands dashed
+                </p>
+              </div>
+            )}
+
             {/* Fermi level toggle */}
             <div className="mb-6">
               <label className="flex items-center gap-2 cursor-pointer">
@@ -957,7 +1047,10 @@ export function BandStructurePage({ project }: BandStructurePageProps) {
             {/* Info */}
             {bandData && (
               <div className="text-xs text-gray-500 space-y-1">
-                <p>Bands: {bandData.bands.length}</p>
+                <p>
+                  Bands: {bandData.bands.length}
+                  {downData && ` up, ${downData.bands.length} down`}
+                </p>
                 <p>K-points: {bandData.kPoints.length}</p>
                 <p>Atoms: {bandData.numAtoms}</p>
                 <p>Fermi Energy: {bandData.fermiEnergy.toFixed(3)} eV</p>
@@ -1,8 +1,13 @@
+import { useState } from "react";
 import { open } from "@tauri-apps/plugin-dialog";
 import {
   importCIFFile,
+  importPOSCAR,
+  listCIFBlocks,
+  loadCrystalData,
   readCIFFile,
   saveCrystalData,
+  CIFBlockSummary,
   CrystalData,
   Project,
 } from "../lib/projects";
@@ -13,7 +18,43 @@ interface CIFUploadZoneProps {
   onCIFImported: (project: Project, crystalData: CrystalData) => void;
 }
 
+interface PendingFile {
+  path: string;
+  filename: string;
+  blocks: CIFBlockSummary[];
+}
+
 export function CIFUploadZone({ projectId, onCIFImported }: CIFUploadZoneProps) {
+  // A file with several structure blocks waits here until blocks are chosen
+  const [pending, setPending] = useState<PendingFile | null>(null);
+  const [chosen, setChosen] = useState<string[]>([]);
+
+  const importFile = async (
+    filePath: string,
+    filename: string,
+    blocks?: string[]
+  ) => {
+    // Import the CIF file to the project folder
+    const updatedProject = await importCIFFile(
+      projectId,
+      filePath,
+      filename,
+      blocks
+    );
+
+    // Read the CIF content
+    const cifContent = await readCIFFile(projectId);
+
+    // Parse the CIF content
+    const crystalData = parseCIF(cifContent);
+
+    // Save the parsed data
+    await saveCrystalData(projectId, crystalData);
+
+    // Notify parent
+    onCIFImported(updatedProject, crystalData);
+  };
+
   const handleUpload = async () => {
     try {
       // Open file dialog
@@ -24,6 +65,10 @@ export function CIFUploadZone({ projectId, onCIFImported }: CIFUploadZoneProps)
             name: "CIF Files",
             extensions: ["cif"],
           },
+          {
+            name: "VASP POSCAR/CONTCAR",
+            extensions: ["vasp", "poscar", "*"],
+          },
         ],
       });
 
@@ -32,25 +77,104 @@ export function CIFUploadZone({ projectId, onCIFImported }: CIFUploadZoneProps)
       const filePath = selected as string;
       const filename = filePath.split("/").pop() || "structure.cif";
 
-      // Import the CIF file to the project folder
-      const updatedProject = await importCIFFile(projectId, filePath, filename);
+      // POSCARs are converted in the backend, which saves the crystal data
+      if (/^(poscar|contcar)|\.(vasp|poscar)$/i.test(filename)) {
+        const updatedProject = await importPOSCAR(projectId, filePath, filename);
+        const crystalData = await loadCrystalData(projectId);
+        if (crystalData) onCIFImported(updatedProject, crystalData);
+        return;
+      }
 
-      // Read the CIF content
-      const cifContent = await readCIFFile(projectId);
-
-      // Parse the CIF content
-      const crystalData = parseCIF(cifContent);
+      // Files the backend can't parse are still imported whole, as before
+      const blocks = await listCIFBlocks(filePath).catch((error) => {
+        console.warn("Could not list CIF blocks:", error);
+        return [];
+      });
+      if (blocks.length > 1) {
+        setPending({ path: filePath, filename, blocks });
+        setChosen([blocks[0].name]);
+        return;
+      }
 
-      // Save the parsed data
-      await saveCrystalData(projectId, crystalData);
+      await importFile(filePath, filename);
+    } catch (error) {
+      console.error("Failed to import CIF file:", error);
+    }
+  };
 
-      // Notify parent
-      onCIFImported(updatedProject, crystalData);
+  const handleImportChosen = async () => {
+    if (!pending || chosen.length === 0) return;
+    // Keep the file's block order; the first chosen block is the project's
+    const blocks = pending.blocks
+      .map((block) => block.name)
+      .filter((name) => chosen.includes(name));
+    try {
+      await importFile(pending.path, pending.filename, blocks);
+      setPending(null);
     } catch (error) {
       console.error("Failed to import CIF file:", error);
     }
   };
 
+  const toggleBlock = (name: string) => {
+    setChosen((current) =>
+      current.includes(name)
+        ? current.filter((n) => n !== name)
+        : [...current, name]
+    );
+  };
+
+  if (pending) {
+    return (
+      <div className="w-full h-full bg-black/90 rounded-2xl flex flex-col p-4 border-2 border-gray-700">
+        <span className="text-gray-300 text-sm font-medium">
+          {pending.filename} has {pending.blocks.length} structures
+        </span>
+        <span className="text-gray-500 text-xs mb-3">
+          The first one chosen becomes the project's structure; the others
+          are kept alongside it.
+        </span>
+        <div className="flex-1 overflow-y-auto space-y-1">
+          {pending.blocks.map((block) => (
+            <label
+              key={block.name}
+              className="flex items-center gap-2 px-2 py-1 rounded hover:bg-white/5 cursor-pointer"
+            >
+              <input
+                type="checkbox"
+                checked={chosen.includes(block.name)}
+                onChange={() => toggleBlock(block.name)}
+              />
+              <span className="text-gray-200 text-xs font-mono">
+                {block.name}
+              </span>
+              <span className="text-gray-400 text-xs">
+                {block.formula ?? "?"} · {block.space_group_HM ?? "?"} · a=
+                {block.cell_length_a} b={block.cell_length_b} c=
+                {block.cell_length_c}
+              </span>
+            </label>
+          ))}
+        </div>
+        <div className="flex justify-end gap-2 mt-3">
+          <button
+            onClick={() => setPending(null)}
+            className="px-3 py-1 text-xs text-gray-400 hover:text-gray-200"
+          >
+            Cancel
+          </button>
+          <button
+            onClick={handleImportChosen}
+            disabled={chosen.length === 0}
+            className="px-3 py-1 text-xs rounded bg-white/10 text-gray-200 hover:bg-white/20 disabled:opacity-40"
+          >
+            Import
+          </button>
+        </div>
+      </div>
+    );
+  }
+
   return (
     <div
       onClick={handleUpload}
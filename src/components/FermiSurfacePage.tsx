@@ -2,15 +2,26 @@ import { useState, useEffect, useMemo } from "react";
 import { Canvas } from "@react-three/fiber";
 import { OrbitControls, Line } from "@react-three/drei";
 import * as THREE from "three";
-import { open } from "@tauri-apps/plugin-dialog";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import {
   Project,
   FermiSurfaceInfo,
   listFermiSurfaces,
   loadFermiSurfaceFiles,
+  loadFermiSurfaceGrid,
   importFermiSurface,
+  importFermiSurfaceFromEnergy,
+  importSpinFermiSurface,
+  importBXSFFermiSurface,
+  exportFermiSurfaceGrid,
+  GridFormat,
+  SpinChannel,
   deleteFermiSurface,
+  relinkDataset,
+  cancelJob,
   formatRelativeTime,
+  Job,
+  ReciprocalError,
 } from "../lib/projects";
 import {
   buildWien2kBandGrid,
@@ -18,7 +29,12 @@ import {
   extractCaseName,
   Wien2kBandGrid,
 } from "../lib/wien2kFermiGrid";
-import { shiftToFermiLevel, EnergyGrid } from "../lib/gridInterpolation";
+import {
+  shiftToFermiLevel,
+  energyGridFromBandGrid,
+  findFermiCrossingBandsInGrid,
+  EnergyGrid,
+} from "../lib/gridInterpolation";
 import { marchingCubes, IsosurfaceMesh } from "../lib/marchingCubes";
 
 interface FermiSurfacePageProps {
@@ -155,6 +171,21 @@ function Scene({
   );
 }
 
+function isCancellation(err: unknown): boolean {
+  return err instanceof ReciprocalError && err.payload.type === "cancelled";
+}
+
+function formatJobProgress(job: Job): string {
+  const { phase, bytes_done, bytes_total, files_done, files_total } = job.progress;
+  if (bytes_total) {
+    return `${phase} (${Math.round((100 * bytes_done) / bytes_total)}%)`;
+  }
+  if (files_total) {
+    return `${phase} (${files_done}/${files_total})`;
+  }
+  return phase;
+}
+
 export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
   // State for Fermi surface data
   const [fermiSurfaces, setFermiSurfaces] = useState<FermiSurfaceInfo[]>([]);
@@ -162,30 +193,48 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
   const [rawData, setRawData] = useState<Wien2kBandGrid | null>(null);
   const [energyGrid, setEnergyGrid] = useState<EnergyGrid | null>(null);
   const [crossingBands, setCrossingBands] = useState<number[]>([]);
+  // Spin channel of each band, for spin-polarized surfaces
+  const [bandSpins, setBandSpins] = useState<SpinChannel[]>([]);
   const [enabledBands, setEnabledBands] = useState<Set<number>>(new Set());
 
   // UI state
   const [isLoading, setIsLoading] = useState(true);
   const [isProcessing, setIsProcessing] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  // Set when the selected surface's linked files are missing or changed
+  const [brokenLink, setBrokenLink] = useState(false);
+  const [reloadKey, setReloadKey] = useState(0);
+  // Backend job currently reading or importing files
+  const [job, setJob] = useState<Job | null>(null);
   const [showBZ, setShowBZ] = useState(true);
   const [showImportPanel, setShowImportPanel] = useState(false);
 
   // Import file state
   const [importFiles, setImportFiles] = useState<{
     output1?: string;
+    output1dn?: string;
     output2?: string;
     outputkgen?: string;
     struct?: string;
+    energy?: string;
+    energydn?: string;
+    klist?: string;
+    bxsf?: string;
     caseName?: string;
   }>({});
   const [importName, setImportName] = useState("");
+  const [linkFiles, setLinkFiles] = useState(false);
 
   // Load list of Fermi surfaces
   useEffect(() => {
     const load = async () => {
       try {
-        const surfaces = await listFermiSurfaces(project.id);
+        const { items: surfaces, diagnostics } = await listFermiSurfaces(
+          project.id
+        );
+        if (diagnostics.length > 0) {
+          console.warn("Some Fermi surfaces could not be loaded:", diagnostics);
+        }
         setFermiSurfaces(surfaces);
         if (surfaces.length > 0 && !selectedId) {
           setSelectedId(surfaces[0].id);
@@ -204,46 +253,77 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
       setRawData(null);
       setEnergyGrid(null);
       setCrossingBands([]);
+      setBandSpins([]);
       return;
     }
 
     const loadData = async () => {
       setIsProcessing(true);
       setError(null);
+      setBrokenLink(false);
 
       try {
-        const [output1, output2, outputkgen, _struct] = await loadFermiSurfaceFiles(project.id, selectedId);
         const surface = fermiSurfaces.find(s => s.id === selectedId);
 
-        // Parse Wien2k files using Xcrysden-style bandgrid construction
-        const data = buildWien2kBandGrid(
-          output1,
-          output2,
-          outputkgen,
-          surface?.case_name || "unknown"
-        );
+        let data: Wien2kBandGrid;
+        let crossing: number[];
+        let spins: SpinChannel[] = [];
+        if ((surface?.kind && surface.kind !== "wien2k") || surface?.spin === "polarized") {
+          // BXSF files hold the full grid already, and eigenvalues from
+          // case.energy files or both spin channels are unfolded onto it by
+          // the backend
+          const bandGrid = await loadFermiSurfaceGrid(project.id, selectedId, setJob);
+          spins = bandGrid.spins ?? [];
+          const grid = energyGridFromBandGrid(bandGrid);
+          data = {
+            grid,
+            numBands: grid.data.length,
+            irreducibleKPoints: 0,
+            reciprocalVectors: bandGrid.reciprocal_vectors,
+            caseName: surface.case_name,
+            energiesByKPoint: [],
+          };
+          crossing = findFermiCrossingBandsInGrid(grid);
+        } else {
+          const [output1, output2, outputkgen, _struct] = await loadFermiSurfaceFiles(project.id, selectedId, setJob);
+
+          // Parse Wien2k files using Xcrysden-style bandgrid construction
+          data = buildWien2kBandGrid(
+            output1,
+            output2,
+            outputkgen,
+            surface?.case_name || "unknown"
+          );
+
+          // Find bands crossing Fermi level
+          crossing = findFermiCrossingBandsFromGrid(
+            data.energiesByKPoint,
+            data.grid.fermiEnergy
+          );
+        }
         setRawData(data);
-
-        // Find bands crossing Fermi level
-        const crossing = findFermiCrossingBandsFromGrid(
-          data.energiesByKPoint,
-          data.grid.fermiEnergy
-        );
         setCrossingBands(crossing);
+        setBandSpins(spins);
         setEnabledBands(new Set(crossing.slice(0, 4))); // Enable first 4 by default
 
         const shiftedGrid = shiftToFermiLevel(data.grid);
         setEnergyGrid(shiftedGrid);
       } catch (err) {
-        console.error("Failed to process Fermi surface:", err);
-        setError(String(err));
+        if (!isCancellation(err)) {
+          console.error("Failed to process Fermi surface:", err);
+          setError(String(err));
+          setBrokenLink(
+            err instanceof ReciprocalError && err.payload.type === "broken_link"
+          );
+        }
       }
 
+      setJob(null);
       setIsProcessing(false);
     };
 
     loadData();
-  }, [selectedId, project.id, fermiSurfaces]);
+  }, [selectedId, project.id, fermiSurfaces, reloadKey]);
 
   // Generate meshes for enabled bands
   const meshes = useMemo(() => {
@@ -268,18 +348,36 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
   }, [energyGrid, enabledBands]);
 
   // Handle file selection for import
-  const selectFile = async (type: 'output1' | 'output2' | 'outputkgen' | 'struct') => {
+  const selectFile = async (
+    type:
+      | 'output1'
+      | 'output1dn'
+      | 'output2'
+      | 'outputkgen'
+      | 'struct'
+      | 'energy'
+      | 'energydn'
+      | 'klist'
+      | 'bxsf'
+  ) => {
     const extensions: Record<string, string[]> = {
-      output1: ['output1'],
-      output2: ['output2'],
+      output1: ['output1', 'output1up'],
+      output1dn: ['output1dn'],
+      output2: ['output2', 'output2up'],
       outputkgen: ['outputkgen'],
       struct: ['struct'],
+      klist: ['klist'],
+      bxsf: ['bxsf'],
     };
 
     try {
       const result = await open({
         multiple: false,
-        filters: [{ name: `Wien2k ${type} file`, extensions: extensions[type] }],
+        // Energy files come as energyso, energyup, energy_3, ... so any file goes
+        filters: type === 'energy' || type === 'energydn' ? [] : [{
+          name: type === 'bxsf' ? 'XCrysDen band grid' : `Wien2k ${type} file`,
+          extensions: extensions[type],
+        }],
       });
 
       if (result) {
@@ -293,7 +391,9 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
         }));
 
         if (!importName) {
-          setImportName(extractCaseName(filename));
+          setImportName(
+            type === 'bxsf' ? filename.replace(/\.bxsf$/i, '') : extractCaseName(filename)
+          );
         }
       }
     } catch (err) {
@@ -301,23 +401,82 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
     }
   };
 
+  // A BXSF band grid on its own, or the Wien2k files with eigenvalues from
+  // output1 or from energy files and their klist
+  const hasWien2kFiles = Boolean(
+    (importFiles.output1 || (importFiles.energy && importFiles.klist)) &&
+      importFiles.output2 &&
+      importFiles.outputkgen &&
+      importFiles.struct
+  );
+  const canImport = Boolean(importFiles.bxsf) || hasWien2kFiles;
+
   // Handle import
   const handleImport = async () => {
-    if (!importFiles.output1 || !importFiles.output2 || !importFiles.outputkgen || !importFiles.struct) {
+    if (!canImport) {
       return;
     }
 
     setIsProcessing(true);
     try {
-      const info = await importFermiSurface(
-        project.id,
-        importName || importFiles.caseName || "Fermi Surface",
-        importFiles.output1,
-        importFiles.output2,
-        importFiles.outputkgen,
-        importFiles.struct,
-        importFiles.caseName || "unknown"
-      );
+      const name = importName || importFiles.caseName || "Fermi Surface";
+      const caseName = importFiles.caseName || "unknown";
+      // The down channel's file, for a spin-polarized run
+      const down = importFiles.output1 ? importFiles.output1dn : importFiles.energydn;
+
+      let info: FermiSurfaceInfo;
+      if (importFiles.bxsf) {
+        info = await importBXSFFermiSurface(
+          project.id,
+          importName || "Fermi Surface",
+          importFiles.bxsf,
+          undefined,
+          linkFiles,
+          setJob
+        );
+      } else if (down) {
+        info = await importSpinFermiSurface(
+          project.id,
+          name,
+          importFiles.output1
+            ? { up: importFiles.output1, down }
+            : { up: importFiles.energy!, down, klist: importFiles.klist! },
+          importFiles.output2!,
+          importFiles.outputkgen!,
+          importFiles.struct!,
+          caseName,
+          undefined,
+          linkFiles,
+          setJob
+        );
+      } else if (importFiles.output1) {
+        info = await importFermiSurface(
+          project.id,
+          name,
+          importFiles.output1,
+          importFiles.output2!,
+          importFiles.outputkgen!,
+          importFiles.struct!,
+          caseName,
+          undefined,
+          linkFiles,
+          setJob
+        );
+      } else {
+        info = await importFermiSurfaceFromEnergy(
+          project.id,
+          name,
+          [importFiles.energy!],
+          importFiles.klist!,
+          importFiles.output2!,
+          importFiles.outputkgen!,
+          importFiles.struct!,
+          caseName,
+          undefined,
+          linkFiles,
+          setJob
+        );
+      }
 
       setFermiSurfaces(prev => [info, ...prev]);
       setSelectedId(info.id);
@@ -325,9 +484,76 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
       setImportFiles({});
       setImportName("");
     } catch (err) {
-      console.error("Failed to import Fermi surface:", err);
+      if (!isCancellation(err)) {
+        console.error("Failed to import Fermi surface:", err);
+        setError(String(err));
+      }
+    }
+    setJob(null);
+    setIsProcessing(false);
+  };
+
+  // Point a linked surface at its case directory's new location
+  const handleRelink = async () => {
+    if (!selectedId) return;
+    try {
+      const directory = await open({ directory: true, multiple: false });
+      if (!directory) return;
+
+      let checks = await relinkDataset(project.id, selectedId, directory as string);
+      const changed = checks.filter(c => c.status === "changed");
+      if (
+        changed.length > 0 &&
+        window.confirm(
+          `${changed.map(c => c.path).join(", ")} changed since import. Relink anyway?`
+        )
+      ) {
+        checks = await relinkDataset(project.id, selectedId, directory as string, true);
+      }
+
+      const missing = checks.filter(c => c.status !== "ok");
+      if (missing.length > 0) {
+        setError(`Could not relink ${missing.map(c => c.name).join(", ")}`);
+        return;
+      }
+      setReloadKey(key => key + 1);
+    } catch (err) {
+      console.error("Failed to relink Fermi surface:", err);
       setError(String(err));
     }
+  };
+
+  // Write the enabled bands for XCrysDen or FermiSurfer
+  const handleExportGrid = async (format: GridFormat) => {
+    if (!selectedId) return;
+    const surface = fermiSurfaces.find(s => s.id === selectedId);
+    try {
+      const dest = await save({
+        defaultPath: `${surface?.case_name || "fermi"}.${format}`,
+        filters: [{
+          name: format === "bxsf" ? "XCrysDen band grid" : "FermiSurfer file",
+          extensions: [format],
+        }],
+      });
+      if (!dest) return;
+
+      setIsProcessing(true);
+      await exportFermiSurfaceGrid(
+        project.id,
+        selectedId,
+        dest,
+        format,
+        Array.from(enabledBands).sort((a, b) => a - b),
+        format === "frmsf" ? "fermi_velocity" : undefined,
+        setJob
+      );
+    } catch (err) {
+      if (!isCancellation(err)) {
+        console.error("Failed to export Fermi surface:", err);
+        setError(String(err));
+      }
+    }
+    setJob(null);
     setIsProcessing(false);
   };
 
@@ -345,6 +571,16 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
   };
 
   // Toggle band
+  // "Band 3", or "Band 3 ↑" counted within its spin channel
+  const bandLabel = (bandIndex: number) => {
+    const spin = bandSpins[bandIndex];
+    if (!spin) {
+      return `Band ${bandIndex + 1}`;
+    }
+    const number = bandIndex - bandSpins.indexOf(spin) + 1;
+    return `Band ${number} ${spin === "up" ? "↑" : "↓"}`;
+  };
+
   const toggleBand = (bandIndex: number) => {
     setEnabledBands(prev => {
       const next = new Set(prev);
@@ -383,7 +619,7 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
             No Fermi Surfaces
           </h2>
           <p className="text-gray-600 mb-6">
-            Import Wien2k output files to visualize Fermi surfaces
+            Import Wien2k output files or a BXSF band grid to visualize Fermi surfaces
           </p>
           <button
             onClick={() => setShowImportPanel(true)}
@@ -418,7 +654,7 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
         <div className="absolute inset-0 z-50 flex items-center justify-center bg-black/30">
           <div className="glass rounded-3xl p-6 w-[500px] max-h-[80vh] overflow-y-auto">
             <div className="flex justify-between items-center mb-4">
-              <h3 className="text-lg font-semibold text-gray-800">Import Wien2k Files</h3>
+              <h3 className="text-lg font-semibold text-gray-800">Import Fermi Surface</h3>
               <button
                 onClick={() => setShowImportPanel(false)}
                 className="text-gray-500 hover:text-gray-700"
@@ -442,8 +678,18 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
                 />
               </div>
 
-              {/* File selectors */}
-              {(['output1', 'output2', 'outputkgen', 'struct'] as const).map((type) => (
+              {/* File selectors; energy and klist stand in for output1, and the
+                  dn files add a spin-polarized run's down channel */}
+              {([
+                'output1',
+                'output1dn',
+                'energy',
+                'energydn',
+                'klist',
+                'output2',
+                'outputkgen',
+                'struct',
+              ] as const).map((type) => (
                 <div key={type}>
                   <label className="block text-sm font-medium text-gray-700 mb-1 capitalize">
                     {type} file
@@ -453,7 +699,13 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
                       type="text"
                       value={importFiles[type]?.split('/').pop() || ''}
                       readOnly
-                      placeholder={`Select .${type} file`}
+                      placeholder={
+                        type === 'energy'
+                          ? 'Or .energy/.energyso (splits are merged) with .klist'
+                          : type.endsWith('dn')
+                          ? `Spin-polarized only: .${type}, with the up file above`
+                          : `Select .${type} file`
+                      }
                       className="flex-1 px-3 py-2 border rounded-lg bg-gray-50"
                     />
                     <button
@@ -466,6 +718,40 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
                 </div>
               ))}
 
+              {/* BXSF alternative */}
+              <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">
+                  Or a BXSF band grid
+                </label>
+                <div className="flex gap-2">
+                  <input
+                    type="text"
+                    value={importFiles.bxsf?.split('/').pop() || ''}
+                    readOnly
+                    placeholder="Select .bxsf file (Quantum ESPRESSO, VASP, XCrysDen)"
+                    className="flex-1 px-3 py-2 border rounded-lg bg-gray-50"
+                  />
+                  <button
+                    onClick={() => selectFile('bxsf')}
+                    className="px-4 py-2 bg-gray-200 text-gray-700 rounded-lg hover:bg-gray-300 transition-colors"
+                  >
+                    Browse
+                  </button>
+                </div>
+              </div>
+
+              <label className="flex items-center gap-2 cursor-pointer">
+                <input
+                  type="checkbox"
+                  checked={linkFiles}
+                  onChange={(e) => setLinkFiles(e.target.checked)}
+                  className="w-4 h-4 rounded"
+                />
+                <span className="text-sm text-gray-700">
+                  Link files in place instead of copying
+                </span>
+              </label>
+
               {/* Import button */}
               <div className="flex justify-end gap-2 mt-6">
                 <button
@@ -476,10 +762,14 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
                 </button>
                 <button
                   onClick={handleImport}
-                  disabled={!importFiles.output1 || !importFiles.output2 || !importFiles.outputkgen || !importFiles.struct || isProcessing}
+                  disabled={!canImport || isProcessing}
                   className="px-6 py-2 bg-blue-500 text-white rounded-lg hover:bg-blue-600 transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
                 >
-                  {isProcessing ? "Importing..." : "Import"}
+                  {isProcessing
+                    ? job?.progress.phase
+                      ? formatJobProgress(job)
+                      : "Importing..."
+                    : "Import"}
                 </button>
               </div>
             </div>
@@ -492,7 +782,19 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
         <div className="absolute inset-0 z-40 flex items-center justify-center bg-white/50">
           <div className="glass rounded-2xl p-6 text-center">
             <div className="animate-spin w-8 h-8 border-2 border-blue-500 border-t-transparent rounded-full mx-auto mb-3" />
-            <p className="text-gray-600">Processing Fermi surface...</p>
+            <p className="text-gray-600">
+              {job?.state === "running" && job.progress.phase
+                ? formatJobProgress(job)
+                : "Processing Fermi surface..."}
+            </p>
+            {job?.state === "running" && (
+              <button
+                onClick={() => cancelJob(job.id).catch(err => console.error("Failed to cancel job:", err))}
+                className="mt-3 text-sm text-gray-500 hover:text-gray-700"
+              >
+                Cancel
+              </button>
+            )}
           </div>
         </div>
       )}
@@ -505,6 +807,14 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
               <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M12 8v4m0 4h.01M21 12a9 9 0 11-18 0 9 9 0 0118 0z" />
             </svg>
             {error}
+            {brokenLink && (
+              <button
+                onClick={handleRelink}
+                className="ml-2 text-sm font-medium text-red-700 underline hover:text-red-900"
+              >
+                Relink...
+              </button>
+            )}
             <button onClick={() => setError(null)} className="ml-2 text-red-500 hover:text-red-700">
               <svg className="w-4 h-4" fill="none" stroke="currentColor" viewBox="0 0 24 24">
                 <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M6 18L18 6M6 6l12 12" />
@@ -571,10 +881,12 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
                 <span className="text-gray-500">Crossing Bands:</span>{" "}
                 <span className="text-gray-800 font-medium">{crossingBands.length}</span>
               </div>
-              <div>
-                <span className="text-gray-500">Irreducible k-points:</span>{" "}
-                <span className="text-gray-800 font-medium">{rawData.irreducibleKPoints}</span>
-              </div>
+              {rawData.irreducibleKPoints > 0 && (
+                <div>
+                  <span className="text-gray-500">Irreducible k-points:</span>{" "}
+                  <span className="text-gray-800 font-medium">{rawData.irreducibleKPoints}</span>
+                </div>
+              )}
               <div>
                 <span className="text-gray-500">Grid:</span>{" "}
                 <span className="text-gray-800 font-medium">
@@ -609,10 +921,23 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
                         : '#d1d5db'
                     }}
                   />
-                  <span className="text-sm text-gray-700">Band {bandIndex + 1}</span>
+                  <span className="text-sm text-gray-700">{bandLabel(bandIndex)}</span>
                 </label>
               ))}
             </div>
+            <div className="flex gap-2 mt-4">
+              {(["bxsf", "frmsf"] as const).map((format) => (
+                <button
+                  key={format}
+                  onClick={() => handleExportGrid(format)}
+                  title={`Export the checked bands as .${format}`}
+                  disabled={enabledBands.size === 0 || isProcessing}
+                  className="flex-1 px-3 py-1.5 text-sm bg-gray-200 text-gray-700 rounded-lg hover:bg-gray-300 transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+                >
+                  .{format}
+                </button>
+              ))}
+            </div>
           </div>
         </div>
       )}
@@ -654,7 +979,7 @@ export function FermiSurfacePage({ project }: FermiSurfacePageProps) {
                 className="w-4 h-4 rounded"
                 style={{ backgroundColor: m.color }}
               />
-              <span className="text-gray-600">Band {m.bandIndex + 1}</span>
+              <span className="text-gray-600">{bandLabel(m.bandIndex)}</span>
             </div>
           ))}
         </div>
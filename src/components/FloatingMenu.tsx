@@ -457,7 +457,7 @@ export function FloatingMenu({ currentProject, onDeleteProject }: FloatingMenuPr
                     Delete "{currentProject.name}"?
                   </p>
                   <p className="text-xs text-gray-500">
-                    This action cannot be undone. All data associated with this project will be permanently deleted.
+                    The project and all of its data will be moved to the trash, where it can be restored until the trash is emptied.
                   </p>
                 </div>
               </div>
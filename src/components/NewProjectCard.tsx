@@ -1,4 +1,5 @@
 import { useState, useEffect, useCallback } from "react";
+import { open } from "@tauri-apps/plugin-dialog";
 import { cn } from "../lib/utils";
 import {
   elements,
@@ -18,6 +19,7 @@ interface SelectedElement {
 interface NewProjectCardProps {
   onClose: () => void;
   onCreate?: (name: string, formula: string) => void;
+  onCreateFromFile?: (path: string) => void;
   onSave?: (name: string, formula: string) => void;
   mode?: "create" | "edit";
   initialName?: string;
@@ -79,6 +81,7 @@ function toDisplayFormula(selected: SelectedElement[]): string {
 export function NewProjectCard({
   onClose,
   onCreate,
+  onCreateFromFile,
   onSave,
   mode = "create",
   initialName = "",
@@ -198,6 +201,19 @@ export function NewProjectCard({
     }
   };
 
+  const handleCreateFromFile = async () => {
+    const path = await open({
+      multiple: false,
+      filters: [
+        { name: "Structure", extensions: ["cif", "struct", "vasp", "poscar"] },
+        { name: "All files", extensions: ["*"] },
+      ],
+    });
+    if (typeof path === "string" && onCreateFromFile) {
+      onCreateFromFile(path);
+    }
+  };
+
   // Group elements by row for rendering
   const mainTableElements = elements.filter((el) => el.row <= 7);
   const lanthanides = elements.filter((el) => el.row === 8);
@@ -211,24 +227,35 @@ export function NewProjectCard({
         <h2 className="text-2xl font-bold text-gray-800 font-kadwa">
           {mode === "edit" ? "Edit Project" : "New Project"}
         </h2>
-        <button
-          onClick={onClose}
-          className="w-8 h-8 rounded-full bg-gray-200/50 hover:bg-gray-300/50 flex items-center justify-center transition-colors"
-        >
-          <svg
-            className="w-5 h-5 text-gray-600"
-            fill="none"
-            stroke="currentColor"
-            viewBox="0 0 24 24"
+        <div className="flex items-center gap-3">
+          {mode === "create" && onCreateFromFile && (
+            <button
+              onClick={handleCreateFromFile}
+              title="Create from a CIF, Wien2k case.struct or POSCAR file"
+              className="px-4 py-1.5 rounded-full bg-white/30 hover:bg-white/50 border border-white/40 text-sm text-gray-700 transition-colors"
+            >
+              From structure file…
+            </button>
+          )}
+          <button
+            onClick={onClose}
+            className="w-8 h-8 rounded-full bg-gray-200/50 hover:bg-gray-300/50 flex items-center justify-center transition-colors"
           >
-            <path
-              strokeLinecap="round"
-              strokeLinejoin="round"
-              strokeWidth={2}
-              d="M6 18L18 6M6 6l12 12"
-            />
-          </svg>
-        </button>
+            <svg
+              className="w-5 h-5 text-gray-600"
+              fill="none"
+              stroke="currentColor"
+              viewBox="0 0 24 24"
+            >
+              <path
+                strokeLinecap="round"
+                strokeLinejoin="round"
+                strokeWidth={2}
+                d="M6 18L18 6M6 6l12 12"
+              />
+            </svg>
+          </button>
+        </div>
       </div>
 
       {/* Project Name Input */}
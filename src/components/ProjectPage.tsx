@@ -36,7 +36,7 @@ export function ProjectPage({ project, onProjectUpdate, onEditProject, onOpenMin
       // Check if band structures exist
       try {
         const bandStructures = await listBandStructures(project.id);
-        setHasBandStructures(bandStructures.length > 0);
+        setHasBandStructures(bandStructures.items.length > 0);
       } catch {
         setHasBandStructures(false);
       }
@@ -44,7 +44,7 @@ export function ProjectPage({ project, onProjectUpdate, onEditProject, onOpenMin
       // Check if fermi surfaces exist
       try {
         const fermiSurfaces = await listFermiSurfaces(project.id);
-        setHasFermiSurfaces(fermiSurfaces.length > 0);
+        setHasFermiSurfaces(fermiSurfaces.items.length > 0);
       } catch {
         setHasFermiSurfaces(false);
       }
@@ -1,17 +1,15 @@
 /**
- * Parser for Wien2k .qtl and .klist_band files
+ * Band structures from Wien2k .qtl and .klist_band files
  *
- * QTL file contains:
+ * The backend parses the files and caches the result; this turns it into
+ * per-point band data for plotting:
  * - Fermi energy
  * - Atom definitions with orbital labels
  * - Band energies and orbital character for each k-point
- *
- * klist_band file contains:
  * - K-point path with high-symmetry point labels
  */
 
-// Rydberg to eV conversion factor
-const RY_TO_EV = 13.605693122994;
+import type { ParsedBandStructure, Qtl } from "./projects";
 
 // Orbital types from Wien2k ISPLIT=8
 export const ORBITAL_LABELS = [
@@ -72,214 +70,61 @@ export interface BandStructureData {
 }
 
 /**
- * Parse a Wien2k .klist_band file
+ * Build the plotting model from a band structure the backend parsed, with
+ * the down channel of a spin-polarized one alongside
  */
-export function parseKlistBand(content: string): { kPoints: KPoint[]; highSymmetryIndices: number[] } {
-  const lines = content.split("\n");
-  const kPoints: KPoint[] = [];
-  const highSymmetryIndices: number[] = [];
-
-  let kIndex = 0;
-
-  for (const line of lines) {
-    const trimmed = line.trim();
-    if (!trimmed || trimmed === "END") continue;
-
-    // Check if this line has a label (starts with K.N or similar)
-    const labelMatch = trimmed.match(/^([A-Z]+\.?\d*)\s+/i);
-    const isHighSymmetry = labelMatch !== null;
-
-    // Parse k-point coordinates
-    // Format: [label] kx ky kz divisor weight [energy_range]
-    const parts = trimmed.split(/\s+/).filter(p => p.length > 0);
-
-    let startIdx = isHighSymmetry ? 1 : 0;
-
-    if (parts.length >= startIdx + 4) {
-      const kx = parseInt(parts[startIdx], 10);
-      const ky = parseInt(parts[startIdx + 1], 10);
-      const kz = parseInt(parts[startIdx + 2], 10);
-      const divisor = parseInt(parts[startIdx + 3], 10);
-
-      if (!isNaN(kx) && !isNaN(ky) && !isNaN(kz) && !isNaN(divisor) && divisor !== 0) {
-        const kPoint: KPoint = {
-          index: kIndex,
-          kx: kx / divisor,
-          ky: ky / divisor,
-          kz: kz / divisor,
-          isHighSymmetry,
-          label: isHighSymmetry ? labelMatch![1] : undefined,
-        };
-
-        if (isHighSymmetry) {
-          highSymmetryIndices.push(kIndex);
-        }
-
-        kPoints.push(kPoint);
-        kIndex++;
-      }
-    }
-  }
-
-  return { kPoints, highSymmetryIndices };
-}
-
-/**
- * Parse a Wien2k .qtl file
- */
-export function parseQtl(content: string, _kPoints: KPoint[]): Omit<BandStructureData, "kPoints" | "highSymmetryIndices"> {
-  const lines = content.split("\n");
-
-  // Line 1: Case name
-  const caseName = lines[0]?.trim() || "Unknown";
-
-  // Line 3: Lattice constants and Fermi energy
-  // Format: LATTICE CONST.= a b c FERMI ENERGY= ef
-  const line3 = lines[2] || "";
-  const latticeMatch = line3.match(/LATTICE CONST\.\s*=\s*([\d.]+)\s+([\d.]+)\s+([\d.]+)/);
-  const fermiMatch = line3.match(/FERMI ENERGY\s*=\s*([\d.-]+)/);
-
-  const latticeConstants = latticeMatch
-    ? { a: parseFloat(latticeMatch[1]), b: parseFloat(latticeMatch[2]), c: parseFloat(latticeMatch[3]) }
-    : { a: 0, b: 0, c: 0 };
-
-  // Convert Fermi energy from Rydberg to eV
-  const fermiEnergyRy = fermiMatch ? parseFloat(fermiMatch[1]) : 0;
-  const fermiEnergy = fermiEnergyRy * RY_TO_EV;
-
-  // Line 4: Band info
-  // Format: nmat < NMAT < nmat2 SPIN=spin NAT=nat SO so
-  const line4 = lines[3] || "";
-  const natMatch = line4.match(/NAT\s*=\s*(\d+)/);
-  const spinMatch = line4.match(/SPIN\s*=\s*(\d+)/);
-  const soMatch = line4.match(/SO\s+(\d+)/);
-
-  const numAtoms = natMatch ? parseInt(natMatch[1], 10) : 0;
-  const spinPolarized = spinMatch ? parseInt(spinMatch[1], 10) > 1 : false;
-  const spinOrbit = soMatch ? parseInt(soMatch[1], 10) > 0 : false;
-
-  // Parse atom definitions (JATOM lines)
-  const atoms: AtomDefinition[] = [];
-  let lineIdx = 4;
-
-  while (lineIdx < lines.length && lines[lineIdx]?.includes("JATOM")) {
-    const jatomLine = lines[lineIdx];
-    const jatomMatch = jatomLine.match(/JATOM\s+(\d+)\s+MULT\s*=\s*(\d+)\s+ISPLIT\s*=\s*(\d+)\s+(.*)/);
-
-    if (jatomMatch) {
-      const orbitalLabels = jatomMatch[4].split(",").map(s => s.trim().toLowerCase());
-      atoms.push({
-        index: parseInt(jatomMatch[1], 10),
-        multiplicity: parseInt(jatomMatch[2], 10),
-        isplit: parseInt(jatomMatch[3], 10),
-        orbitalLabels,
-      });
-    }
-    lineIdx++;
-  }
-
-  // Parse bands
-  const bands: Band[] = [];
-  let currentBand: Band | null = null;
-  let kPointIdx = 0;
-
-  for (let i = lineIdx; i < lines.length; i++) {
-    const line = lines[i]?.trim();
-    if (!line) continue;
-
-    // Check for BAND header
-    const bandMatch = line.match(/^BAND\s+(\d+)/);
-    if (bandMatch) {
-      if (currentBand) {
-        bands.push(currentBand);
-      }
-      currentBand = {
-        index: parseInt(bandMatch[1], 10),
-        points: [],
-      };
-      kPointIdx = 0;
-      continue;
-    }
-
-    // Parse data line
-    // Format: energy atomIndex total [orbital weights...]
-    if (currentBand && line.match(/^\s*-?\d/)) {
-      const parts = line.split(/\s+/).filter(p => p.length > 0);
-
-      if (parts.length >= 3) {
-        const energy = parseFloat(parts[0]);
-        const atomIdx = parseInt(parts[1], 10);
-        const weights = parts.slice(2).map(p => parseFloat(p));
-
-        if (!isNaN(energy) && !isNaN(atomIdx)) {
-          // Convert energy from Rydberg to eV and subtract Fermi level
-          const energyEv = energy * RY_TO_EV - fermiEnergy;
-
-          // Find or create the band point for this k-point
-          let bandPoint = currentBand.points.find(p => p.kPointIndex === kPointIdx);
-
-          if (!bandPoint) {
-            bandPoint = {
-              kPointIndex: kPointIdx,
-              energy: energyEv,
-              atomWeights: [],
-            };
-            currentBand.points.push(bandPoint);
-          }
-
-          // Store weights for this atom (atomIdx is 1-based, but could also have interstitial)
-          // We'll use atomIdx - 1 as the array index for atoms 1, 2, etc.
-          // Interstitial (if present) usually has higher index
-          while (bandPoint.atomWeights.length < atomIdx) {
-            bandPoint.atomWeights.push([]);
-          }
-          bandPoint.atomWeights[atomIdx - 1] = weights;
-
-          // If this is the last atom for this k-point, increment kPointIdx
-          if (atomIdx >= numAtoms || (atomIdx === atoms.length && !lines[i + 1]?.match(/^\s*-?\d.*\s+\d+\s+\d/))) {
-            // Check if next line is a new k-point (different energy or new atom 1)
-            const nextLine = lines[i + 1]?.trim();
-            if (nextLine) {
-              const nextParts = nextLine.split(/\s+/).filter(p => p.length > 0);
-              const nextAtomIdx = nextParts.length >= 2 ? parseInt(nextParts[1], 10) : 0;
-              if (nextAtomIdx === 1 || nextLine.match(/^BAND/)) {
-                kPointIdx++;
-              }
-            }
-          }
-        }
-      }
-    }
-  }
-
-  // Don't forget the last band
-  if (currentBand) {
-    bands.push(currentBand);
-  }
-
-  return {
-    caseName,
-    latticeConstants,
-    fermiEnergy,
-    numAtoms,
-    spinPolarized,
-    spinOrbit,
-    atoms,
-    bands,
+export function fromParsedBandStructure(
+  parsed: ParsedBandStructure
+): { up: BandStructureData; down: BandStructureData | null } {
+  const kPoints: KPoint[] = parsed.path.map((point, index) => ({
+    index,
+    kx: point.k[0],
+    ky: point.k[1],
+    kz: point.k[2],
+    label: point.label ?? undefined,
+    isHighSymmetry: point.label !== null,
+  }));
+  const highSymmetryIndices = kPoints.filter((k) => k.isHighSymmetry).map((k) => k.index);
+
+  const convert = (qtl: Qtl): BandStructureData => {
+    // Each k-point holds every atom's weights, then the interstitial's
+    const counts = [...qtl.atoms.map((atom) => atom.orbital_labels.length), 1];
+    const stride = counts.reduce((sum, count) => sum + count, 0);
+    const [a, b, c] = qtl.lattice_constants;
+
+    return {
+      caseName: qtl.case_name || "Unknown",
+      latticeConstants: { a, b, c },
+      fermiEnergy: qtl.fermi_energy,
+      numAtoms: qtl.atoms.length,
+      spinPolarized: qtl.spin_polarized,
+      spinOrbit: qtl.spin_orbit,
+      atoms: qtl.atoms.map((atom) => ({
+        index: atom.index,
+        multiplicity: atom.multiplicity,
+        isplit: atom.isplit,
+        orbitalLabels: atom.orbital_labels,
+      })),
+      kPoints,
+      highSymmetryIndices,
+      bands: qtl.bands.map((band) => ({
+        index: band.index,
+        points: band.energies.map((energy, kPointIndex) => {
+          let offset = kPointIndex * stride;
+          const atomWeights = counts.map((count) => {
+            const weights = band.weights.slice(offset, offset + count);
+            offset += count;
+            return weights;
+          });
+          return { kPointIndex, energy, atomWeights };
+        }),
+      })),
+    };
   };
-}
-
-/**
- * Parse both .qtl and .klist_band files and combine into BandStructureData
- */
-export function parseBandStructure(qtlContent: string, klistContent: string): BandStructureData {
-  const { kPoints, highSymmetryIndices } = parseKlistBand(klistContent);
-  const qtlData = parseQtl(qtlContent, kPoints);
 
   return {
-    ...qtlData,
-    kPoints,
-    highSymmetryIndices,
+    up: convert(parsed.up),
+    down: parsed.down ? convert(parsed.down) : null,
   };
 }
 
@@ -11,6 +11,7 @@ import {
   findInterpolationCube,
   findNearestKPoint
 } from './kpointSymmetry';
+import type { BandGrid } from './projects';
 
 export interface EnergyGrid {
   nx: number;
@@ -204,6 +205,39 @@ export function shiftToFermiLevel(grid: EnergyGrid): EnergyGrid {
   };
 }
 
+/**
+ * Energy grid from a band grid read by the backend (e.g. a BXSF file),
+ * which is already laid out x fastest in eV
+ */
+export function energyGridFromBandGrid(bandGrid: BandGrid): EnergyGrid {
+  return {
+    nx: bandGrid.nx,
+    ny: bandGrid.ny,
+    nz: bandGrid.nz,
+    data: bandGrid.bands.map(band => Float32Array.from(band)),
+    fermiEnergy: bandGrid.fermi_energy,
+  };
+}
+
+/**
+ * Indices of the bands with grid points on both sides of the Fermi level
+ */
+export function findFermiCrossingBandsInGrid(grid: EnergyGrid): number[] {
+  const crossing: number[] = [];
+  grid.data.forEach((bandData, bandIdx) => {
+    let hasAbove = false;
+    let hasBelow = false;
+    for (let i = 0; i < bandData.length && !(hasAbove && hasBelow); i++) {
+      if (bandData[i] > grid.fermiEnergy) hasAbove = true;
+      if (bandData[i] < grid.fermiEnergy) hasBelow = true;
+    }
+    if (hasAbove && hasBelow) {
+      crossing.push(bandIdx);
+    }
+  });
+  return crossing;
+}
+
 /**
  * Calculate the gradient of the energy at a grid point
  * Used for computing normals to the Fermi surface
@@ -1,4 +1,158 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import type { SymmetryMatrix } from "./wien2kFermiParser";
+
+// ============ Errors ============
+
+export type ResourceKind =
+  | "project"
+  | "band_structure"
+  | "fermi_surface"
+  | "structure"
+  | "dataset"
+  | "file"
+  | "trash_entry"
+  | "job";
+
+export type ReciprocalErrorPayload =
+  | {
+      type: "not_found" | "invalid_id" | "already_exists";
+      kind: ResourceKind;
+      id: string;
+      message: string;
+    }
+  | {
+      type: "io";
+      path: string;
+      op: "read" | "write" | "copy" | "create_dir" | "read_dir" | "delete";
+      io_kind: string;
+      message: string;
+    }
+  | { type: "parse"; file: string; line: number | null; message: string }
+  | { type: "corrupt"; path: string; message: string }
+  | { type: "unsupported_schema"; path: string; version: number; message: string }
+  | {
+      type: "broken_link";
+      path: string;
+      name: string;
+      status: LinkStatus;
+      message: string;
+    }
+  | { type: "cancelled"; message: string }
+  | { type: "internal"; message: string };
+
+/** Error thrown by backend commands, carrying the typed payload from Rust. */
+export class ReciprocalError extends Error {
+  readonly payload: ReciprocalErrorPayload;
+
+  constructor(payload: ReciprocalErrorPayload) {
+    super(payload.message);
+    this.name = "ReciprocalError";
+    this.payload = payload;
+  }
+
+  toString(): string {
+    return this.message;
+  }
+}
+
+function isErrorPayload(value: unknown): value is ReciprocalErrorPayload {
+  return (
+    typeof value === "object" &&
+    value !== null &&
+    "type" in value &&
+    "message" in value
+  );
+}
+
+async function call<T>(
+  command: string,
+  args?: Record<string, unknown>
+): Promise<T> {
+  try {
+    return await invoke<T>(command, args);
+  } catch (err) {
+    throw isErrorPayload(err) ? new ReciprocalError(err) : err;
+  }
+}
+
+// ============ Jobs ============
+
+export type JobKind =
+  | "import_band_structure"
+  | "import_fermi_surface"
+  | "load_band_structure"
+  | "load_fermi_surface"
+  | "export_fermi_surface"
+  | "export_archive"
+  | "import_archive"
+  | "verify_library";
+
+export type JobState = "running" | "completed" | "failed" | "cancelled";
+
+export interface JobProgress {
+  phase: string;
+  bytes_done: number;
+  bytes_total: number | null;
+  files_done: number;
+  files_total: number | null;
+}
+
+export interface Job {
+  id: string;
+  kind: JobKind;
+  label: string;
+  state: JobState;
+  progress: JobProgress;
+  started_at: string;
+  finished_at: string | null;
+}
+
+/** Event emitted with a Job whenever its state or progress changes. */
+export const JOB_EVENT = "job-progress";
+
+export type JobProgressCallback = (job: Job) => void;
+
+export async function listJobs(): Promise<Job[]> {
+  return call<Job[]>("list_jobs");
+}
+
+export async function cancelJob(jobId: string): Promise<Job> {
+  return call<Job>("cancel_job", { jobId });
+}
+
+/** Starts a job with `command` and resolves with its result once it
+ *  finishes. A cancelled job rejects with a "cancelled" ReciprocalError. */
+async function runJob<T>(
+  command: string,
+  args: Record<string, unknown>,
+  onProgress?: JobProgressCallback
+): Promise<T> {
+  let jobId: string | null = null;
+  // Updates that arrive before the command has returned the job's ID
+  const early: Job[] = [];
+  let finish = () => {};
+  const finished = new Promise<void>((resolve) => (finish = resolve));
+  const handle = (job: Job) => {
+    onProgress?.(job);
+    if (job.state !== "running") finish();
+  };
+
+  const unlisten = await listen<Job>(JOB_EVENT, ({ payload }) => {
+    if (jobId === null) early.push(payload);
+    else if (payload.id === jobId) handle(payload);
+  });
+  try {
+    const job = await call<Job>(command, args);
+    jobId = job.id;
+    handle(job);
+    early.filter((update) => update.id === job.id).forEach(handle);
+    await finished;
+    return await call<T>("job_result", { jobId });
+  } finally {
+    unlisten();
+  }
+}
 
 export interface LatticeParameter {
   value: number;
@@ -74,6 +228,7 @@ export interface CrystalData {
 }
 
 export interface Project {
+  schema_version?: number;
   id: string;
   name: string;
   formula: string;
@@ -84,43 +239,186 @@ export interface Project {
   cif_filename?: string;
 }
 
-export async function loadProjects(): Promise<Project[]> {
-  return invoke<Project[]>("load_projects");
+export interface Diagnostic {
+  path: string;
+  reason: string;
+}
+
+export interface Listing<T> {
+  items: T[];
+  diagnostics: Diagnostic[];
+}
+
+export async function loadProjects(): Promise<Listing<Project>> {
+  return call<Listing<Project>>("load_projects");
 }
 
 export async function createProject(
   name: string,
   formula: string
 ): Promise<Project> {
-  return invoke<Project>("create_project", { name, formula });
+  return call<Project>("create_project", { name, formula });
+}
+
+/**
+ * Create a project from a CIF, Wien2k case.struct or POSCAR file, with the
+ * formula and structure filled in from it.
+ */
+export async function createProjectFromStructure(path: string): Promise<Project> {
+  return call<Project>("create_project_from_structure", { path });
 }
 
 export async function updateProject(project: Project): Promise<Project> {
-  return invoke<Project>("update_project", { project });
+  return call<Project>("update_project", { project });
 }
 
 export async function markProjectOpened(projectId: string): Promise<Project> {
-  return invoke<Project>("mark_project_opened", { projectId });
+  return call<Project>("mark_project_opened", { projectId });
 }
 
-export async function deleteProject(id: string): Promise<void> {
-  return invoke<void>("delete_project", { id });
+export async function deleteProject(id: string): Promise<TrashEntry> {
+  return call<TrashEntry>("delete_project", { id });
 }
 
+/**
+ * Import a CIF file into a project. `blocks` names the data blocks to
+ * import: the first becomes the project's structure and the rest are kept
+ * as separate structures. Without it the whole file is imported.
+ */
 export async function importCIFFile(
+  projectId: string,
+  sourcePath: string,
+  originalFilename: string,
+  blocks?: string[]
+): Promise<Project> {
+  return call<Project>("import_cif_file", {
+    projectId,
+    sourcePath,
+    originalFilename,
+    blocks,
+  });
+}
+
+/** Make a VASP POSCAR or CONTCAR file the project's structure. */
+export async function importPOSCAR(
   projectId: string,
   sourcePath: string,
   originalFilename: string
 ): Promise<Project> {
-  return invoke<Project>("import_cif_file", {
+  return call<Project>("import_poscar", {
     projectId,
     sourcePath,
     originalFilename,
   });
 }
 
+/** Write the project's structure to `dest` as a VASP POSCAR. */
+export async function exportPOSCAR(projectId: string, dest: string): Promise<void> {
+  return call<void>("export_poscar", { projectId, dest });
+}
+
+export interface CIFBlockSummary {
+  name: string;
+  formula: string | null;
+  space_group_HM: string | null;
+  space_group_IT_number: number | null;
+  cell_length_a: number;
+  cell_length_b: number;
+  cell_length_c: number;
+  cell_angle_alpha: number;
+  cell_angle_beta: number;
+  cell_angle_gamma: number;
+  atom_sites: number;
+}
+
+/** The data blocks of a CIF file that describe a crystal structure. */
+export async function listCIFBlocks(path: string): Promise<CIFBlockSummary[]> {
+  return call<CIFBlockSummary[]>("list_cif_blocks", { path });
+}
+
+export interface StructureInfo {
+  schema_version?: number;
+  id: string;
+  name: string;
+  formula: string;
+  created_at: string;
+  cif_filename: string;
+}
+
+export async function listStructures(
+  projectId: string
+): Promise<Listing<StructureInfo>> {
+  return call<Listing<StructureInfo>>("list_structures", { projectId });
+}
+
+export async function loadStructure(
+  projectId: string,
+  structureId: string
+): Promise<CrystalData> {
+  return call<CrystalData>("load_structure", { projectId, structureId });
+}
+
+export async function deleteStructure(
+  projectId: string,
+  structureId: string
+): Promise<TrashEntry> {
+  return call<TrashEntry>("delete_structure", { projectId, structureId });
+}
+
 export async function readCIFFile(projectId: string): Promise<string> {
-  return invoke<string>("read_cif_file", { projectId });
+  return call<string>("read_cif_file", { projectId });
+}
+
+/**
+ * Parse a CIF file in the backend: the project's structure.cif, or the file
+ * at `path` if given.
+ */
+export async function parseCIFFile(
+  projectId: string | null,
+  path?: string
+): Promise<CrystalData> {
+  return call<CrystalData>("parse_cif", { projectId, path });
+}
+
+/** Write the project's structure to `dest` as a CIF 1.1 file. */
+export async function exportCIF(projectId: string, dest: string): Promise<void> {
+  return call<void>("export_cif", { projectId, dest });
+}
+
+// ============ Wien2k struct files ============
+
+export type Wien2kLatticeType = "P" | "H" | "R" | "F" | "B" | "CXY" | "CYZ" | "CXZ";
+
+export interface Wien2kStructAtom {
+  name: string;
+  positions: [number, number, number][];
+  isplit: number;
+  npt: number;
+  r0: number; // bohr
+  rmt: number; // bohr
+  z: number;
+  local_rotation: number[][]; // 3x3
+}
+
+export interface Wien2kStruct {
+  title: string;
+  lattice: Wien2kLatticeType;
+  space_group_number: number | null;
+  space_group_symbol: string | null;
+  relativistic: boolean;
+  cell: [number, number, number, number, number, number]; // a, b, c in bohr; angles in degrees
+  atoms: Wien2kStructAtom[];
+  symmetry_operations: SymmetryMatrix[];
+}
+
+/** Read a Wien2k case.struct file. */
+export async function parseStructFile(path: string): Promise<Wien2kStruct> {
+  return call<Wien2kStruct>("parse_struct", { path });
+}
+
+/** Write a Wien2k case.struct generated from the project's structure. */
+export async function exportStruct(projectId: string, dest: string): Promise<void> {
+  return call<void>("export_struct", { projectId, dest });
 }
 
 export async function saveCrystalData(
@@ -128,27 +426,71 @@ export async function saveCrystalData(
   crystalData: CrystalData
 ): Promise<void> {
   const crystalDataJson = JSON.stringify(crystalData);
-  return invoke<void>("save_crystal_data", { projectId, crystalDataJson });
+  return call<void>("save_crystal_data", { projectId, crystalDataJson });
 }
 
 export async function loadCrystalData(
   projectId: string
 ): Promise<CrystalData | null> {
-  const json = await invoke<string | null>("load_crystal_data", { projectId });
+  const json = await call<string | null>("load_crystal_data", { projectId });
   if (json) {
     return JSON.parse(json) as CrystalData;
   }
   return null;
 }
 
+// ============ Project Archives ============
+
+export interface ArchiveEntry {
+  path: string;
+  size: number;
+  sha256: string;
+}
+
+export interface ArchiveManifest {
+  format_version: number;
+  schema_version: number;
+  app_version: string;
+  exported_at: string;
+  project_id: string;
+  project_name: string;
+  files: ArchiveEntry[];
+}
+
+export async function exportProjectArchive(
+  projectId: string,
+  dest: string,
+  onProgress?: JobProgressCallback
+): Promise<ArchiveManifest> {
+  return runJob<ArchiveManifest>(
+    "export_project_archive",
+    { projectId, dest },
+    onProgress
+  );
+}
+
+export async function importProjectArchive(
+  path: string,
+  onProgress?: JobProgressCallback
+): Promise<Project> {
+  return runJob<Project>("import_project_archive", { path }, onProgress);
+}
+
 // ============ Band Structure Functions ============
 
+export type Spin = "unpolarized" | "polarized";
+
 export interface BandStructureInfo {
+  schema_version?: number;
   id: string;
   name: string;
   created_at: string;
   qtl_filename: string;
   klist_filename: string;
+  spin?: Spin;
+  qtl_dn_filename?: string | null;
+  files?: BlobFiles;
+  linked?: LinkedFiles;
 }
 
 export async function importBandStructure(
@@ -157,39 +499,124 @@ export async function importBandStructure(
   qtlSourcePath: string,
   qtlFilename: string,
   klistSourcePath: string,
-  klistFilename: string
+  klistFilename: string,
+  parameters?: CalculationParameters,
+  link?: boolean,
+  onProgress?: JobProgressCallback
 ): Promise<BandStructureInfo> {
-  return invoke<BandStructureInfo>("import_band_structure", {
-    projectId,
-    name,
-    qtlSourcePath,
-    qtlFilename,
-    klistSourcePath,
-    klistFilename,
-  });
+  return runJob<BandStructureInfo>(
+    "import_band_structure",
+    {
+      projectId,
+      name,
+      qtlSourcePath,
+      qtlFilename,
+      klistSourcePath,
+      klistFilename,
+      parameters,
+      link,
+    },
+    onProgress
+  );
+}
+
+export async function importSpinBandStructure(
+  projectId: string,
+  name: string,
+  qtlUpSourcePath: string,
+  qtlUpFilename: string,
+  qtlDnSourcePath: string,
+  qtlDnFilename: string,
+  klistSourcePath: string,
+  klistFilename: string,
+  parameters?: CalculationParameters,
+  link?: boolean,
+  onProgress?: JobProgressCallback
+): Promise<BandStructureInfo> {
+  return runJob<BandStructureInfo>(
+    "import_spin_band_structure",
+    {
+      projectId,
+      name,
+      qtlUpSourcePath,
+      qtlUpFilename,
+      qtlDnSourcePath,
+      qtlDnFilename,
+      klistSourcePath,
+      klistFilename,
+      parameters,
+      link,
+    },
+    onProgress
+  );
 }
 
 export async function listBandStructures(
   projectId: string
-): Promise<BandStructureInfo[]> {
-  return invoke<BandStructureInfo[]>("list_band_structures", { projectId });
+): Promise<Listing<BandStructureInfo>> {
+  return call<Listing<BandStructureInfo>>("list_band_structures", {
+    projectId,
+  });
+}
+
+export interface QtlAtom {
+  index: number;
+  multiplicity: number;
+  isplit: number;
+  orbital_labels: string[];
 }
 
-export async function loadBandStructureFiles(
+export interface QtlBand {
+  index: number;
+  // eV relative to the Fermi energy, one per k-point
+  energies: number[];
+  // Per k-point, each atom's orbital weights in order, then the interstitial's
+  weights: number[];
+}
+
+export interface Qtl {
+  case_name: string;
+  lattice_constants: [number, number, number];
+  fermi_energy: number;
+  spin_polarized: boolean;
+  spin_orbit: boolean;
+  atoms: QtlAtom[];
+  bands: QtlBand[];
+}
+
+export interface BandPathPoint {
+  label: string | null;
+  k: [number, number, number];
+}
+
+// A band structure as the backend parsed it (BandStructureData). The down
+// channel is relative to the up channel's Fermi energy.
+export interface ParsedBandStructure {
+  path: BandPathPoint[];
+  up: Qtl;
+  down: Qtl | null;
+}
+
+export async function loadBandStructureData(
   projectId: string,
-  bandStructureId: string
-): Promise<[string, string]> {
-  return invoke<[string, string]>("load_band_structure_files", {
-    projectId,
-    bandStructureId,
-  });
+  bandStructureId: string,
+  onProgress?: JobProgressCallback
+): Promise<ParsedBandStructure> {
+  return runJob<ParsedBandStructure>(
+    "load_band_structure_data",
+    { projectId, bandStructureId },
+    onProgress
+  );
 }
 
 export async function deleteBandStructure(
   projectId: string,
   bandStructureId: string
-): Promise<void> {
-  return invoke<void>("delete_band_structure", { projectId, bandStructureId });
+): Promise<TrashEntry> {
+  return call<TrashEntry>("delete_band_structure", {
+    projectId,
+    bandStructureId,
+  });
 }
 
 export async function updateBandStructureLabels(
@@ -198,7 +625,7 @@ export async function updateBandStructureLabels(
   labels: Record<string, string>
 ): Promise<void> {
   const labelsJson = JSON.stringify(labels);
-  return invoke<void>("update_band_structure_labels", {
+  return call<void>("update_band_structure_labels", {
     projectId,
     bandStructureId,
     labelsJson,
@@ -209,7 +636,7 @@ export async function loadBandStructureLabels(
   projectId: string,
   bandStructureId: string
 ): Promise<Record<string, string> | null> {
-  const json = await invoke<string | null>("load_band_structure_labels", {
+  const json = await call<string | null>("load_band_structure_labels", {
     projectId,
     bandStructureId,
   });
@@ -225,7 +652,7 @@ export async function updateBandStructureAtomNames(
   atomNames: Record<number, string>
 ): Promise<void> {
   const atomNamesJson = JSON.stringify(atomNames);
-  return invoke<void>("update_band_structure_atom_names", {
+  return call<void>("update_band_structure_atom_names", {
     projectId,
     bandStructureId,
     atomNamesJson,
@@ -236,7 +663,7 @@ export async function loadBandStructureAtomNames(
   projectId: string,
   bandStructureId: string
 ): Promise<Record<number, string> | null> {
-  const json = await invoke<string | null>("load_band_structure_atom_names", {
+  const json = await call<string | null>("load_band_structure_atom_names", {
     projectId,
     bandStructureId,
   });
@@ -248,11 +675,24 @@ export async function loadBandStructureAtomNames(
 
 // ============ Fermi Surface Functions ============
 
+export type FermiSurfaceKind = "wien2k" | "wien2k_energy" | "bxsf";
+
 export interface FermiSurfaceInfo {
+  schema_version?: number;
   id: string;
   name: string;
   created_at: string;
   case_name: string;
+  /** Absent on Fermi surfaces imported before BXSF support, which are Wien2k. */
+  kind?: FermiSurfaceKind;
+  /** Number of data.energy_N files, for "wien2k_energy" surfaces. */
+  energy_files?: number;
+  /** "polarized" surfaces also hold the down channel's eigenvalues. */
+  spin?: Spin;
+  /** Number of data.energy_dn_N files. */
+  energy_files_dn?: number;
+  files?: BlobFiles;
+  linked?: LinkedFiles;
 }
 
 export async function importFermiSurface(
@@ -262,40 +702,347 @@ export async function importFermiSurface(
   output2SourcePath: string,
   outputkgenSourcePath: string,
   structSourcePath: string,
-  caseName: string
+  caseName: string,
+  parameters?: CalculationParameters,
+  link?: boolean,
+  onProgress?: JobProgressCallback
 ): Promise<FermiSurfaceInfo> {
-  return invoke<FermiSurfaceInfo>("import_fermi_surface", {
-    projectId,
-    name,
-    output1SourcePath,
-    output2SourcePath,
-    outputkgenSourcePath,
-    structSourcePath,
-    caseName,
-  });
+  return runJob<FermiSurfaceInfo>(
+    "import_fermi_surface",
+    {
+      projectId,
+      name,
+      output1SourcePath,
+      output2SourcePath,
+      outputkgenSourcePath,
+      structSourcePath,
+      caseName,
+      parameters,
+      link,
+    },
+    onProgress
+  );
+}
+
+/**
+ * Import a Wien2k Fermi surface with eigenvalues from case.energy files
+ * (energyso, energyup, ...) instead of output1. A single path brings in the
+ * k-parallel splits beside it; the result is checked against the klist.
+ */
+export async function importFermiSurfaceFromEnergy(
+  projectId: string,
+  name: string,
+  energySourcePaths: string[],
+  klistSourcePath: string,
+  output2SourcePath: string,
+  outputkgenSourcePath: string,
+  structSourcePath: string,
+  caseName: string,
+  parameters?: CalculationParameters,
+  link?: boolean,
+  onProgress?: JobProgressCallback
+): Promise<FermiSurfaceInfo> {
+  return runJob<FermiSurfaceInfo>(
+    "import_fermi_surface",
+    {
+      projectId,
+      name,
+      energySourcePaths,
+      klistSourcePath,
+      output2SourcePath,
+      outputkgenSourcePath,
+      structSourcePath,
+      caseName,
+      parameters,
+      link,
+    },
+    onProgress
+  );
+}
+
+/**
+ * Import both channels of a spin-polarized Wien2k run as one Fermi surface.
+ * `up` and `down` are output1up/output1dn files, or energyup/energydn files
+ * when a klist is given. output2 is the up channel's; both share its Fermi
+ * energy.
+ */
+export async function importSpinFermiSurface(
+  projectId: string,
+  name: string,
+  eigenvalues: { up: string; down: string; klist?: string },
+  output2SourcePath: string,
+  outputkgenSourcePath: string,
+  structSourcePath: string,
+  caseName: string,
+  parameters?: CalculationParameters,
+  link?: boolean,
+  onProgress?: JobProgressCallback
+): Promise<FermiSurfaceInfo> {
+  const { up, down, klist } = eigenvalues;
+  return runJob<FermiSurfaceInfo>(
+    "import_fermi_surface",
+    {
+      projectId,
+      name,
+      ...(klist
+        ? {
+            energySourcePaths: [up],
+            energyDnSourcePaths: [down],
+            klistSourcePath: klist,
+          }
+        : { output1SourcePath: up, output1DnSourcePath: down }),
+      output2SourcePath,
+      outputkgenSourcePath,
+      structSourcePath,
+      caseName,
+      parameters,
+      link,
+    },
+    onProgress
+  );
+}
+
+export async function importBXSFFermiSurface(
+  projectId: string,
+  name: string,
+  bxsfSourcePath: string,
+  parameters?: CalculationParameters,
+  link?: boolean,
+  onProgress?: JobProgressCallback
+): Promise<FermiSurfaceInfo> {
+  return runJob<FermiSurfaceInfo>(
+    "import_bxsf_fermi_surface",
+    { projectId, name, bxsfSourcePath, parameters, link },
+    onProgress
+  );
 }
 
 export async function listFermiSurfaces(
   projectId: string
-): Promise<FermiSurfaceInfo[]> {
-  return invoke<FermiSurfaceInfo[]>("list_fermi_surfaces", { projectId });
+): Promise<Listing<FermiSurfaceInfo>> {
+  return call<Listing<FermiSurfaceInfo>>("list_fermi_surfaces", { projectId });
 }
 
 export async function loadFermiSurfaceFiles(
   projectId: string,
-  fermiSurfaceId: string
+  fermiSurfaceId: string,
+  onProgress?: JobProgressCallback
 ): Promise<[string, string, string, string]> {
-  return invoke<[string, string, string, string]>("load_fermi_surface_files", {
-    projectId,
-    fermiSurfaceId,
-  });
+  return runJob<[string, string, string, string]>(
+    "load_fermi_surface_files",
+    { projectId, fermiSurfaceId },
+    onProgress
+  );
+}
+
+/** Band energies in eV on a full grid, x fastest, end points included. */
+export type SpinChannel = "up" | "down";
+
+export interface BandGrid {
+  nx: number;
+  ny: number;
+  nz: number;
+  fermi_energy: number;
+  origin: number[];
+  reciprocal_vectors: number[][];
+  bands: number[][];
+  /** Channel of each band for spin-polarized surfaces; empty otherwise. */
+  spins: SpinChannel[];
+}
+
+export async function loadFermiSurfaceGrid(
+  projectId: string,
+  fermiSurfaceId: string,
+  onProgress?: JobProgressCallback
+): Promise<BandGrid> {
+  return runJob<BandGrid>(
+    "load_fermi_surface_grid",
+    { projectId, fermiSurfaceId },
+    onProgress
+  );
+}
+
+export type GridFormat = "bxsf" | "frmsf";
+export type GridColour = "fermi_velocity";
+
+/**
+ * Write a Fermi surface's band grid for XCrysDen (.bxsf) or FermiSurfer
+ * (.frmsf). Bands default to those crossing the Fermi energy.
+ */
+export async function exportFermiSurfaceGrid(
+  projectId: string,
+  fermiSurfaceId: string,
+  dest: string,
+  format: GridFormat,
+  bands?: number[],
+  colour?: GridColour,
+  onProgress?: JobProgressCallback
+): Promise<void> {
+  return runJob<void>(
+    "export_fermi_surface_grid",
+    { projectId, fermiSurfaceId, dest, format, bands, colour },
+    onProgress
+  );
 }
 
 export async function deleteFermiSurface(
   projectId: string,
   fermiSurfaceId: string
-): Promise<void> {
-  return invoke<void>("delete_fermi_surface", { projectId, fermiSurfaceId });
+): Promise<TrashEntry> {
+  return call<TrashEntry>("delete_fermi_surface", { projectId, fermiSurfaceId });
+}
+
+// ============ Trash ============
+
+export interface TrashEntry {
+  schema_version?: number;
+  id: string;
+  kind: ResourceKind;
+  item_id: string;
+  project_id: string | null;
+  name: string;
+  deleted_at: string;
+}
+
+export async function listTrash(): Promise<Listing<TrashEntry>> {
+  return call<Listing<TrashEntry>>("list_trash");
+}
+
+export async function restoreFromTrash(trashId: string): Promise<TrashEntry> {
+  return call<TrashEntry>("restore_from_trash", { trashId });
+}
+
+export async function emptyTrash(): Promise<number> {
+  return call<number>("empty_trash");
+}
+
+// ============ Settings ============
+
+export interface LibrarySettings {
+  schema_version?: number;
+  trash_retention_days: number | null;
+}
+
+export async function getSettings(): Promise<LibrarySettings> {
+  return call<LibrarySettings>("get_settings");
+}
+
+export async function updateSettings(
+  settings: LibrarySettings
+): Promise<LibrarySettings> {
+  return call<LibrarySettings>("update_settings", { settings });
+}
+
+// ============ Repair ============
+
+export interface RepairReport {
+  project: Project;
+  repaired: Diagnostic[];
+  unrecoverable: Diagnostic[];
+}
+
+export async function repairProject(projectId: string): Promise<RepairReport> {
+  return call<RepairReport>("repair_project", { projectId });
+}
+
+// ============ Blob Store ============
+
+export interface BlobRef {
+  sha256: string;
+  size: number;
+}
+
+/** Stored file name (e.g. "data.qtl") to the blob holding its contents. */
+export type BlobFiles = Record<string, BlobRef>;
+
+export interface VerifyReport {
+  checked: number;
+  corrupt: Diagnostic[];
+  missing: Diagnostic[];
+  unreadable: Diagnostic[];
+}
+
+export async function verifyLibrary(
+  onProgress?: JobProgressCallback
+): Promise<VerifyReport> {
+  return runJob<VerifyReport>("verify_library", {}, onProgress);
+}
+
+// ============ Provenance ============
+
+/** Free-form calculation parameters recorded with an import. */
+export type CalculationParameters = Record<string, string>;
+
+export interface SourceFile {
+  name: string;
+  path: string;
+  size: number;
+  modified_at: string | null;
+  sha256: string;
+}
+
+export interface Provenance {
+  schema_version?: number;
+  imported_at: string;
+  host: string | null;
+  app_version: string;
+  sources: SourceFile[];
+  parameters: CalculationParameters;
+}
+
+/** Where a band structure or Fermi surface was imported from; null for
+ *  datasets imported before provenance was recorded. */
+export async function getDatasetProvenance(
+  projectId: string,
+  datasetId: string
+): Promise<Provenance | null> {
+  return call<Provenance | null>("get_dataset_provenance", {
+    projectId,
+    datasetId,
+  });
+}
+
+// ============ Linked Files ============
+
+/** A file referenced in place instead of copied into the library. */
+export interface LinkedFile {
+  path: string;
+  size: number;
+  modified_at: string | null;
+  sha256: string;
+}
+
+export type LinkedFiles = Record<string, LinkedFile>;
+
+export type LinkStatus = "ok" | "missing" | "changed";
+
+export interface LinkCheck {
+  name: string;
+  path: string;
+  status: LinkStatus;
+}
+
+export async function checkDatasetLinks(
+  projectId: string,
+  datasetId: string
+): Promise<LinkCheck[]> {
+  return call<LinkCheck[]>("check_dataset_links", { projectId, datasetId });
+}
+
+/** Points a linked dataset at the files of the same names in `directory`.
+ *  Files whose contents changed are only relinked with `acceptChanges`. */
+export async function relinkDataset(
+  projectId: string,
+  datasetId: string,
+  directory: string,
+  acceptChanges?: boolean
+): Promise<LinkCheck[]> {
+  return call<LinkCheck[]>("relink_dataset", {
+    projectId,
+    datasetId,
+    directory,
+    acceptChanges,
+  });
 }
 
 export function formatRelativeTime(isoString: string): string {
@@ -257,12 +257,23 @@ export function findFermiCrossingBandsFromGrid(
 
 export function extractCaseName(filename: string): string {
   const extensions = [
+    ".output1up",
+    ".output1dn",
+    ".output2up",
+    ".output2dn",
     ".output1",
     ".output2",
     ".outputkgen",
     ".struct",
+    ".klist",
   ];
 
+  // case.energy, energyso, energyup/dn and their k-parallel splits
+  const energy = filename.match(/^(.*)\.energy(?:so)?(?:up|dn)?(?:_\d+)?$/i);
+  if (energy) {
+    return energy[1];
+  }
+
   let name = filename;
   for (const ext of extensions) {
     if (name.toLowerCase().endsWith(ext)) {